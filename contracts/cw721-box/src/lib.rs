@@ -1,21 +1,61 @@
+use cosmwasm_schema::cw_serde;
 pub use cosmwasm_std::Empty;
-pub use cw721_base::{ContractError, InstantiateMsg, MinterResponse, Extension};
+use cosmwasm_std::{Coin, Timestamp};
+use cw2981_royalty::RoyaltyMetadata;
+pub use cw2981_royalty::{
+    validate_royalty, CheckRoyaltiesResponse, Cw2981QueryMsg, RoyaltiesInfoResponse,
+};
+pub use cw721_base::{ContractError, InstantiateMsg, MinterResponse};
+
+pub mod query;
 
 // Version info for migration
 const _CONTRACT_NAME: &str = "crates.io:cw721-box";
 const _CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
-pub type Cw721MetadataContract<'a> = cw721_base::Cw721Contract<'a, Extension, Empty, Empty, Empty>;
+// see: https://docs.opensea.io/docs/metadata-standards
+#[cw_serde]
+#[derive(Default)]
+pub struct Metadata {
+    pub image: Option<String>,
+    pub description: Option<String>,
+    pub name: Option<String>,
+    /// id and name of mystery box event the box was bought from
+    pub event_id: Option<u32>,
+    pub event_name: Option<String>,
+    /// price paid for the box and time it was bought
+    pub price: Option<Coin>,
+    pub purchase_time: Option<Timestamp>,
+    /// address receiving royalties, see cw2981
+    pub royalty_payment_address: Option<String>,
+    /// percentage of sale price paid as royalties (0 - 100)
+    pub royalty_percentage: Option<u64>,
+}
+
+impl RoyaltyMetadata for Metadata {
+    fn royalty_payment_address(&self) -> Option<String> {
+        self.royalty_payment_address.clone()
+    }
+
+    fn royalty_percentage(&self) -> Option<u64> {
+        self.royalty_percentage
+    }
+}
+
+pub type Extension = Option<Metadata>;
+
+pub type Cw721MetadataContract<'a> = cw721_base::Cw721Contract<'a, Extension, Empty, Empty, Cw2981QueryMsg>;
 pub type ExecuteMsg = cw721_base::ExecuteMsg<Extension, Empty>;
-pub type QueryMsg = cw721_base::QueryMsg<Empty>;
+pub type QueryMsg = cw721_base::QueryMsg<Cw2981QueryMsg>;
 pub type MintMsg = cw721_base::MintMsg<Extension>;
 
 #[cfg(not(feature = "library"))]
 pub mod entry {
     use super::*;
 
+    use crate::query::{check_royalties, query_royalties_info};
     use cosmwasm_std::entry_point;
-    use cosmwasm_std::{Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult};
+    use cosmwasm_std::{to_json_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult};
 
     // This makes a conscious choice on the various generics used by the contract
     #[entry_point]
@@ -37,11 +77,24 @@ pub mod entry {
         info: MessageInfo,
         msg: ExecuteMsg,
     ) -> Result<Response, ContractError> {
+        if let ExecuteMsg::Mint(mint_msg) = &msg {
+            validate_royalty(&mint_msg.extension)?;
+        }
+
         Cw721MetadataContract::default().execute(deps, env, info, msg)
     }
 
     #[entry_point]
     pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
-        Cw721MetadataContract::default().query(deps, env, msg)
+        match msg {
+            QueryMsg::Extension { msg } => match msg {
+                Cw2981QueryMsg::RoyaltyInfo {
+                    token_id,
+                    sale_price,
+                } => to_json_binary(&query_royalties_info(deps, token_id, sale_price)?),
+                Cw2981QueryMsg::CheckRoyalties {} => to_json_binary(&check_royalties(deps)?),
+            },
+            _ => Cw721MetadataContract::default().query(deps, env, msg),
+        }
     }
 }
\ No newline at end of file
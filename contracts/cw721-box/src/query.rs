@@ -0,0 +1,21 @@
+use cosmwasm_std::{Deps, StdResult, Uint128};
+use cw2981_royalty::{royalties_info, CheckRoyaltiesResponse, RoyaltiesInfoResponse};
+
+use crate::Cw721MetadataContract;
+
+/// royalty of a token, royalty_percentage of sale_price is paid to royalty_payment_address
+pub fn query_royalties_info(
+    deps: Deps,
+    token_id: String,
+    sale_price: Uint128,
+) -> StdResult<RoyaltiesInfoResponse> {
+    let contract = Cw721MetadataContract::default();
+    let token_info = contract.tokens.load(deps.storage, &token_id)?;
+
+    Ok(royalties_info(&token_info.extension, sale_price))
+}
+
+/// all tokens of this contract may have royalties
+pub fn check_royalties(_deps: Deps) -> StdResult<CheckRoyaltiesResponse> {
+    Ok(cw2981_royalty::check_royalties())
+}
@@ -0,0 +1,214 @@
+use cosmwasm_std::{DepsMut, MessageInfo, Response, StdError};
+
+use crate::state::{is_frozen, COLLECTION_FROZEN, FROZEN_TOKENS};
+use crate::{validate_royalty, ContractError, Cw721MetadataContract, Extension};
+
+/// update token uri and metadata of a minted token, e.g. to reveal artwork of the token
+pub fn update_metadata(
+    deps: DepsMut,
+    info: MessageInfo,
+    token_id: String,
+    token_uri: Option<String>,
+    extension: Extension,
+) -> Result<Response, ContractError> {
+    let contract = Cw721MetadataContract::default();
+
+    // only minter can update metadata
+    let minter = contract.minter.load(deps.storage)?;
+    if info.sender != minter {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // frozen metadata can not be changed
+    if is_frozen(deps.storage, &token_id)? {
+        return Err(ContractError::Std(StdError::generic_err("Metadata is frozen")));
+    }
+
+    validate_royalty(&extension)?;
+
+    contract.tokens.update(deps.storage, &token_id, |token| match token {
+        Some(mut token) => {
+            if token_uri.is_some() {
+                token.token_uri = token_uri;
+            }
+            if extension.is_some() {
+                token.extension = extension;
+            }
+            Ok(token)
+        }
+        None => Err(ContractError::Std(StdError::not_found("token"))),
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "update_metadata")
+        .add_attribute("token_id", token_id))
+}
+
+/// freeze metadata of a token, or of the whole collection if token_id is not set
+pub fn freeze_metadata(
+    deps: DepsMut,
+    info: MessageInfo,
+    token_id: Option<String>,
+) -> Result<Response, ContractError> {
+    let contract = Cw721MetadataContract::default();
+
+    // only minter can freeze metadata
+    let minter = contract.minter.load(deps.storage)?;
+    if info.sender != minter {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let res = Response::new().add_attribute("action", "freeze_metadata");
+    match token_id {
+        Some(token_id) => {
+            if !contract.tokens.has(deps.storage, &token_id) {
+                return Err(ContractError::Std(StdError::not_found("token")));
+            }
+            FROZEN_TOKENS.save(deps.storage, &token_id, &true)?;
+            Ok(res.add_attribute("token_id", token_id))
+        }
+        None => {
+            COLLECTION_FROZEN.save(deps.storage, &true)?;
+            Ok(res.add_attribute("collection", "true"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::DepsMut;
+
+    use crate::{ExecuteMsg, InstantiateMsg, Metadata, MintMsg};
+
+    const MINTER: &str = "minter";
+    const USER: &str = "user";
+    const TOKEN_ID: &str = "token";
+    const TOKEN_URI: &str = "ipfs://placeholder";
+
+    fn setup(mut deps: DepsMut) {
+        let contract = Cw721MetadataContract::default();
+        contract
+            .instantiate(
+                deps.branch(),
+                mock_env(),
+                mock_info(MINTER, &[]),
+                InstantiateMsg {
+                    name: String::from("items"),
+                    symbol: String::from("ITEM"),
+                    minter: String::from(MINTER),
+                },
+            )
+            .unwrap();
+
+        let mint = ExecuteMsg::Mint(MintMsg {
+            token_id: String::from(TOKEN_ID),
+            owner: String::from(USER),
+            token_uri: Some(String::from(TOKEN_URI)),
+            extension: Some(Metadata {
+                rarity: String::from("common"),
+                ..Metadata::default()
+            }),
+        });
+        contract
+            .execute(deps, mock_env(), mock_info(MINTER, &[]), mint)
+            .unwrap();
+    }
+
+    fn metadata(rarity: &str) -> Extension {
+        Some(Metadata {
+            rarity: String::from(rarity),
+            ..Metadata::default()
+        })
+    }
+
+    #[test]
+    fn update_metadata_by_minter() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+
+        let err = update_metadata(
+            deps.as_mut(),
+            mock_info(USER, &[]),
+            String::from(TOKEN_ID),
+            None,
+            metadata("rare"),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        // token uri is kept when not set
+        update_metadata(
+            deps.as_mut(),
+            mock_info(MINTER, &[]),
+            String::from(TOKEN_ID),
+            None,
+            metadata("rare"),
+        )
+        .unwrap();
+        let token = Cw721MetadataContract::default()
+            .tokens
+            .load(&deps.storage, TOKEN_ID)
+            .unwrap();
+        assert_eq!(token.token_uri, Some(String::from(TOKEN_URI)));
+        assert_eq!(token.extension.unwrap().rarity, "rare");
+
+        // unknown token
+        update_metadata(
+            deps.as_mut(),
+            mock_info(MINTER, &[]),
+            String::from("unknown"),
+            None,
+            metadata("rare"),
+        )
+        .unwrap_err();
+    }
+
+    #[test]
+    fn frozen_metadata() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+
+        let err = freeze_metadata(deps.as_mut(), mock_info(USER, &[]), None).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        // frozen token
+        freeze_metadata(
+            deps.as_mut(),
+            mock_info(MINTER, &[]),
+            Some(String::from(TOKEN_ID)),
+        )
+        .unwrap();
+        let err = update_metadata(
+            deps.as_mut(),
+            mock_info(MINTER, &[]),
+            String::from(TOKEN_ID),
+            Some(String::from("ipfs://revealed")),
+            None,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Std(StdError::generic_err("Metadata is frozen"))
+        );
+        assert!(!is_frozen(&deps.storage, "other").unwrap());
+
+        // frozen collection
+        freeze_metadata(deps.as_mut(), mock_info(MINTER, &[]), None).unwrap();
+        assert!(is_frozen(&deps.storage, "other").unwrap());
+        let err = update_metadata(
+            deps.as_mut(),
+            mock_info(MINTER, &[]),
+            String::from("other"),
+            None,
+            metadata("rare"),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Std(StdError::generic_err("Metadata is frozen"))
+        );
+    }
+}
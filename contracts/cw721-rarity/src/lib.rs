@@ -1,7 +1,18 @@
 use cosmwasm_schema::cw_serde;
 use cosmwasm_std::Empty;
+use cw2981_royalty::RoyaltyMetadata;
+pub use cw2981_royalty::{
+    validate_royalty, CheckRoyaltiesResponse, Cw2981QueryMsg, RoyaltiesInfoResponse,
+};
 pub use cw721_base::{ContractError, InstantiateMsg, MinterResponse};
 
+pub mod execute;
+pub mod msg;
+pub mod query;
+pub mod state;
+
+pub use msg::{MetadataFrozenResponse, MetadataMsg, MetadataQueryMsg, RarityQueryMsg};
+
 // Version info for migration
 const _CONTRACT_NAME: &str = "crates.io:cw721-rarity";
 const _CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -29,21 +40,37 @@ pub struct Metadata {
     /// this is rarity of nft
     pub rarity: String,
     pub purity: String,
+    /// address receiving royalties, see cw2981
+    pub royalty_payment_address: Option<String>,
+    /// percentage of sale price paid as royalties (0 - 100)
+    pub royalty_percentage: Option<u64>,
+}
+
+impl RoyaltyMetadata for Metadata {
+    fn royalty_payment_address(&self) -> Option<String> {
+        self.royalty_payment_address.clone()
+    }
+
+    fn royalty_percentage(&self) -> Option<u64> {
+        self.royalty_percentage
+    }
 }
 
 pub type Extension = Option<Metadata>;
 
-pub type Cw721MetadataContract<'a> = cw721_base::Cw721Contract<'a, Extension, Empty, Empty, Empty>;
-pub type ExecuteMsg = cw721_base::ExecuteMsg<Extension, Empty>;
-pub type QueryMsg = cw721_base::QueryMsg<Empty>;
+pub type Cw721MetadataContract<'a> = cw721_base::Cw721Contract<'a, Extension, Empty, MetadataMsg, RarityQueryMsg>;
+pub type ExecuteMsg = cw721_base::ExecuteMsg<Extension, MetadataMsg>;
+pub type QueryMsg = cw721_base::QueryMsg<RarityQueryMsg>;
 pub type MintMsg = cw721_base::MintMsg<Extension>;
 
 #[cfg(not(feature = "library"))]
 pub mod entry {
     use super::*;
 
+    use crate::execute::{freeze_metadata, update_metadata};
+    use crate::query::{check_royalties, query_metadata_frozen, query_royalties_info};
     use cosmwasm_std::entry_point;
-    use cosmwasm_std::{Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult};
+    use cosmwasm_std::{to_json_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult};
 
     // This makes a conscious choice on the various generics used by the contract
     #[entry_point]
@@ -65,11 +92,41 @@ pub mod entry {
         info: MessageInfo,
         msg: ExecuteMsg,
     ) -> Result<Response, ContractError> {
-        Cw721MetadataContract::default().execute(deps, env, info, msg)
+        match msg {
+            ExecuteMsg::Extension { msg } => match msg {
+                MetadataMsg::UpdateMetadata {
+                    token_id,
+                    token_uri,
+                    extension,
+                } => update_metadata(deps, info, token_id, token_uri, extension),
+                MetadataMsg::FreezeMetadata { token_id } => freeze_metadata(deps, info, token_id),
+            },
+            _ => {
+                if let ExecuteMsg::Mint(mint_msg) = &msg {
+                    validate_royalty(&mint_msg.extension)?;
+                }
+
+                Cw721MetadataContract::default().execute(deps, env, info, msg)
+            }
+        }
     }
 
     #[entry_point]
     pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
-        Cw721MetadataContract::default().query(deps, env, msg)
+        match msg {
+            QueryMsg::Extension { msg } => match msg {
+                RarityQueryMsg::Royalty(Cw2981QueryMsg::RoyaltyInfo {
+                    token_id,
+                    sale_price,
+                }) => to_json_binary(&query_royalties_info(deps, token_id, sale_price)?),
+                RarityQueryMsg::Royalty(Cw2981QueryMsg::CheckRoyalties {}) => {
+                    to_json_binary(&check_royalties(deps)?)
+                }
+                RarityQueryMsg::Metadata(MetadataQueryMsg::MetadataFrozen { token_id }) => {
+                    to_json_binary(&query_metadata_frozen(deps, token_id)?)
+                }
+            },
+            _ => Cw721MetadataContract::default().query(deps, env, msg),
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,55 @@
+use cosmwasm_schema::{cw_serde, QueryResponses};
+use cosmwasm_std::CustomMsg;
+use cw2981_royalty::Cw2981QueryMsg;
+
+use crate::Extension;
+
+/// messages of cw721-rarity sent as extension of cw721-base execute message
+#[cw_serde]
+#[allow(clippy::large_enum_variant)] // messages are deserialized once, boxing metadata is not worth it
+pub enum MetadataMsg {
+    /// update token uri and metadata of a minted token, only minter can update
+    /// token_uri and extension are kept if not set
+    UpdateMetadata {
+        token_id: String,
+        token_uri: Option<String>,
+        extension: Extension,
+    },
+
+    /// freeze metadata of a token, or of the whole collection if token_id is not set
+    /// frozen metadata can not be updated anymore, only minter can freeze
+    FreezeMetadata {
+        token_id: Option<String>,
+    },
+}
+
+impl CustomMsg for MetadataMsg {}
+
+/// queries about metadata of tokens
+#[cw_serde]
+#[derive(QueryResponses)]
+pub enum MetadataQueryMsg {
+    /// check if metadata of token can not be updated anymore
+    #[returns(MetadataFrozenResponse)]
+    MetadataFrozen {
+        token_id: String,
+    },
+}
+
+/// queries of cw721-rarity sent as extension of cw721-base query message
+/// cw2981 queries keep their standard format, metadata queries are added next to them
+#[cw_serde]
+#[derive(QueryResponses)]
+#[serde(untagged)]
+#[query_responses(nested)]
+pub enum RarityQueryMsg {
+    Royalty(Cw2981QueryMsg),
+    Metadata(MetadataQueryMsg),
+}
+
+impl CustomMsg for RarityQueryMsg {}
+
+#[cw_serde]
+pub struct MetadataFrozenResponse {
+    pub frozen: bool,
+}
@@ -0,0 +1,30 @@
+use cosmwasm_std::{Deps, StdResult, Uint128};
+use cw2981_royalty::{royalties_info, CheckRoyaltiesResponse, RoyaltiesInfoResponse};
+
+use crate::msg::MetadataFrozenResponse;
+use crate::state::is_frozen;
+use crate::Cw721MetadataContract;
+
+/// royalty of a token, royalty_percentage of sale_price is paid to royalty_payment_address
+pub fn query_royalties_info(
+    deps: Deps,
+    token_id: String,
+    sale_price: Uint128,
+) -> StdResult<RoyaltiesInfoResponse> {
+    let contract = Cw721MetadataContract::default();
+    let token_info = contract.tokens.load(deps.storage, &token_id)?;
+
+    Ok(royalties_info(&token_info.extension, sale_price))
+}
+
+/// all tokens of this contract may have royalties
+pub fn check_royalties(_deps: Deps) -> StdResult<CheckRoyaltiesResponse> {
+    Ok(cw2981_royalty::check_royalties())
+}
+
+/// metadata of a frozen token can not be updated anymore
+pub fn query_metadata_frozen(deps: Deps, token_id: String) -> StdResult<MetadataFrozenResponse> {
+    Ok(MetadataFrozenResponse {
+        frozen: is_frozen(deps.storage, &token_id)?,
+    })
+}
@@ -0,0 +1,13 @@
+use cosmwasm_std::{StdResult, Storage};
+use cw_storage_plus::{Item, Map};
+
+/// tokens whose metadata can not be updated anymore
+pub const FROZEN_TOKENS: Map<&str, bool> = Map::new("frozen_tokens");
+
+/// if true, metadata of every token can not be updated anymore
+pub const COLLECTION_FROZEN: Item<bool> = Item::new("collection_frozen");
+
+/// check if metadata of token is frozen, by itself or with the whole collection
+pub fn is_frozen(storage: &dyn Storage, token_id: &str) -> StdResult<bool> {
+    Ok(COLLECTION_FROZEN.may_load(storage)?.unwrap_or(false) || FROZEN_TOKENS.has(storage, token_id))
+}
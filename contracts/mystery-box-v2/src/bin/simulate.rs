@@ -0,0 +1,243 @@
+//! simulate opening boxes of a mystery box event with the contract's own draw code
+//!
+//! usage: simulate <rate_distribution.json> <opens> [--seed N] [--box-supply N] [--same-wallet] [--format csv|json]
+//!
+//! rate_distribution.json holds a `RateDistributionMsg` as sent in `UpdateMysteryBox`.
+//! randomness is generated from seed in the same range as aurand (0..=10000).
+//! box supply is the number of boxes of the event (deck size in deck mode), default is opens.
+//! with --same-wallet all boxes are opened by one wallet, so pity counters carry over between opens.
+//! csv prints one row per drawn item with chances after its box is opened, final distribution goes to stderr.
+//! json prints draws and final distribution in one object.
+
+use std::process;
+
+use cosmwasm_std::testing::MockStorage;
+use cosmwasm_std::{from_json, to_json_string, Coin, Decimal, Timestamp};
+use cw_utils::Scheduled;
+use serde::Serialize;
+
+use mystery_box_v2::msg::RateDistributionMsg;
+use mystery_box_v2::state::{DrawMode, MysteryBox, PoolSwaps, RateDistribution};
+use mystery_box_v2::utils::SplitMix64;
+
+/// max value of aurand randomness requested by the contract
+const MAX_RANGE_RANDOM: u64 = 10000u64;
+
+struct Args {
+    rate_distribution: String,
+    opens: u64,
+    seed: u64,
+    box_supply: Option<u64>,
+    same_wallet: bool,
+    json: bool,
+}
+
+#[derive(Serialize)]
+struct DrawRow {
+    open: u64,
+    item: u32,
+    item_type: String,
+    purity: Decimal,
+    rates: Vec<Decimal>, // chance of each item type after the box is opened
+}
+
+#[derive(Serialize)]
+struct ItemTypeSummary {
+    name: String,
+    drawn: u64,
+    supply: u32, // remaining supply
+    rate: Decimal, // final rate
+}
+
+#[derive(Serialize)]
+struct Output {
+    item_types: Vec<String>,
+    draws: Vec<DrawRow>,
+    distribution: Vec<ItemTypeSummary>,
+    sold_out: bool, // true if boxes ran out before all opens were simulated
+}
+
+fn usage() -> ! {
+    eprintln!("usage: simulate <rate_distribution.json> <opens> [--seed N] [--box-supply N] [--same-wallet] [--format csv|json]");
+    process::exit(2);
+}
+
+fn parse_number(value: Option<String>) -> u64 {
+    value.and_then(|v| v.parse::<u64>().ok()).unwrap_or_else(|| usage())
+}
+
+fn parse_args() -> Args {
+    let mut args = std::env::args().skip(1);
+    let rate_distribution = args.next().unwrap_or_else(|| usage());
+    let opens = parse_number(args.next());
+
+    let mut parsed = Args {
+        rate_distribution,
+        opens,
+        seed: 0,
+        box_supply: None,
+        same_wallet: false,
+        json: false,
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => parsed.seed = parse_number(args.next()),
+            "--box-supply" => parsed.box_supply = Some(parse_number(args.next())),
+            "--same-wallet" => parsed.same_wallet = true,
+            "--format" => match args.next().as_deref() {
+                Some("csv") => parsed.json = false,
+                Some("json") => parsed.json = true,
+                _ => usage(),
+            },
+            _ => usage(),
+        }
+    }
+    parsed
+}
+
+/// mystery box event holding the simulated rate distribution
+/// token uri are drawn without replacement, so only rate math is simulated
+fn mystery_box(rate_distribution: RateDistribution, box_supply: u64) -> MysteryBox {
+    MysteryBox {
+        id: 0,
+        name: String::from("simulation"),
+        description: String::new(),
+        start: Scheduled::AtHeight(0),
+        end: Scheduled::AtHeight(0),
+        rate_distribution: Some(rate_distribution),
+        prefix_uri: Some(String::new()),
+        total_supply: box_supply.max(1),
+        remaining_tokens: box_supply.max(1),
+        max_minted_box: box_supply,
+        minted_box: 0,
+        bought_back_box: 0,
+        replacement: false,
+        price: Coin::new(0, "simulation"),
+        reroll_fee: None,
+        royalty: None,
+        item_metadata: None,
+        box_image: None,
+        minted_item: 0,
+        created_time: Timestamp::from_seconds(0),
+        has_sold: false,
+        has_opened: false,
+        placeholder_uri: None,
+        provenance_hash: None,
+    }
+}
+
+/// chance of drawing each item type
+/// in rate mode default item type is not chosen by its rate, it gets the rest of the range
+fn chances(rate_distribution: &RateDistribution) -> Vec<Decimal> {
+    let mut rates: Vec<Decimal> = rate_distribution.vec.iter().map(|item_type| item_type.rate).collect();
+    if rate_distribution.mode == DrawMode::Rate {
+        let last = rates.len() - 1;
+        let total = rates[..last].iter().fold(Decimal::zero(), |total, rate| total + rate);
+        rates[last] = Decimal::one().saturating_sub(total);
+    }
+    rates
+}
+
+fn main() {
+    let args = parse_args();
+
+    let msg = std::fs::read(&args.rate_distribution).unwrap_or_else(|err| {
+        eprintln!("can not read {}: {}", args.rate_distribution, err);
+        process::exit(1);
+    });
+    let msg: RateDistributionMsg = from_json(&msg).unwrap_or_else(|err| {
+        eprintln!("invalid rate distribution: {}", err);
+        process::exit(1);
+    });
+
+    let box_supply = args.box_supply.unwrap_or(args.opens);
+    let rate_distribution = RateDistribution::new(msg, box_supply).unwrap_or_else(|err| {
+        eprintln!("invalid rate distribution: {}", err);
+        process::exit(1);
+    });
+    let item_types: Vec<String> = rate_distribution.vec.iter().map(|item_type| item_type.name.clone()).collect();
+    let items_per_box = rate_distribution.items_per_box;
+
+    let mut mystery_box = mystery_box(rate_distribution, box_supply);
+    let storage = MockStorage::new();
+    let mut swaps = PoolSwaps::new(&storage, mystery_box.id);
+    let mut rng = SplitMix64(args.seed);
+    let mut pity_counters = vec![0u32; item_types.len()];
+    // no prizes are escrowed in simulation
+    let mut prize_counts = vec![0u64; item_types.len()];
+    let mut drawn = vec![0u64; item_types.len()];
+    let mut draws: Vec<DrawRow> = Vec::new();
+    let mut sold_out = false;
+
+    if !args.json {
+        println!("open,item,item_type,purity,{}", item_types.join(","));
+    }
+
+    for open in 0..args.opens {
+        if open >= box_supply {
+            sold_out = true;
+            break;
+        }
+
+        // each box is opened by a new wallet unless same wallet is set
+        if !args.same_wallet {
+            pity_counters.iter_mut().for_each(|counter| *counter = 0);
+        }
+
+        let randomness: Vec<(u128, usize)> = (0..items_per_box)
+            .map(|_| (rng.range(MAX_RANGE_RANDOM) as u128, rng.range(MAX_RANGE_RANDOM) as usize))
+            .collect();
+        // boxes running out is handled above, any other error is a bug in the distribution
+        let bundle = mystery_box.draw_bundle(&mut swaps, &randomness, MAX_RANGE_RANDOM as u128, &mut pity_counters, &mut prize_counts)
+            .unwrap_or_else(|err| {
+                eprintln!("draw failed at open {}: {}", open, err);
+                process::exit(1);
+            });
+
+        let rates = chances(mystery_box.rate_distribution.as_ref().unwrap());
+        for (item, draw) in bundle.into_iter().enumerate() {
+            if let Some(index) = item_types.iter().position(|name| name == &draw.item_type) {
+                drawn[index] += 1;
+            }
+
+            let row = DrawRow {
+                open,
+                item: item as u32,
+                item_type: draw.item_type,
+                purity: draw.purity,
+                rates: rates.clone(),
+            };
+            if args.json {
+                draws.push(row);
+            } else {
+                let rates: Vec<String> = row.rates.iter().map(|rate| rate.to_string()).collect();
+                println!("{},{},{},{},{}", row.open, row.item, row.item_type, row.purity, rates.join(","));
+            }
+        }
+    }
+
+    let rate_distribution = mystery_box.rate_distribution.unwrap();
+    let distribution: Vec<ItemTypeSummary> = chances(&rate_distribution).into_iter()
+        .zip(rate_distribution.vec)
+        .zip(drawn)
+        .map(|((rate, item_type), drawn)| ItemTypeSummary {
+            name: item_type.name,
+            drawn,
+            supply: item_type.supply,
+            rate,
+        })
+        .collect();
+
+    if args.json {
+        println!("{}", to_json_string(&Output { item_types, draws, distribution, sold_out }).unwrap());
+    } else {
+        // final distribution goes to stderr so csv output stays clean
+        eprintln!("item_type,drawn,supply,rate");
+        for summary in distribution.iter() {
+            eprintln!("{},{},{},{}", summary.name, summary.drawn, summary.supply, summary.rate);
+        }
+        if sold_out {
+            eprintln!("sold out before all boxes were opened");
+        }
+    }
+}
@@ -1,20 +1,31 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_binary, Binary, Deps, DepsMut, Env, Addr, Api, SubMsg, QueryRequest,
+    to_json_binary, Binary, Deps, DepsMut, Env, Addr, Api, SubMsg, QueryRequest,
     MessageInfo, Response, StdResult, WasmMsg, ReplyOn, WasmQuery,
-    Reply, Timestamp, Uint128, Coin, BankMsg, Storage
+    Reply, Timestamp, Uint128, Coin, BankMsg, Storage, StdError, Order, from_json,
+    CosmosMsg, Decimal, Event,
 };
+use cw20::{Cw20ExecuteMsg, Cw20QueryMsg, BalanceResponse as Cw20BalanceResponse};
+use cw_storage_plus::Bound;
 use cw2::set_contract_version;
-use cw721::{Cw721QueryMsg, Expiration as Cw721Expiration};
+use cw721::{
+    Cw721QueryMsg, Cw721ReceiveMsg, AllNftInfoResponse, OwnerOfResponse,
+    Cw721ExecuteMsg as Cw721BaseExecuteMsg,
+    Expiration as Cw721Expiration
+};
 use cw721_rarity::{
     MintMsg as Cw721RarityMintMsg,
     ExecuteMsg as Cw721RarityExecuteMsg,
     InstantiateMsg as Cw721RarityInstantiateMsg,
     Metadata as Cw721RarityMetadata,
+    Extension as Cw721RarityExtension,
+    MetadataMsg as Cw721RarityMetadataMsg,
+    Trait,
 };
 use cw721_box::{
     MintMsg as Cw721MintMsg,
+    Metadata as Cw721Metadata,
     InstantiateMsg as Cw721InstantiateMsg,
     ExecuteMsg as Cw721ExecuteMsg
 };
@@ -23,17 +34,33 @@ use cw_utils::parse_reply_instantiate_data;
 use crate::error::ContractError;
 use crate::msg::{
     InstantiateMsg, ExecuteMsg, QueryMsg, AurandExecuteMsg,
-    BoxInfo, RateDistributionMsg, LinkedArress,
+    BoxInfo, BoxUpdate, LinkedArress,
+    SimulateOpenResponse, SimulatedItem, ItemTypeRate, PendingJob, PendingJobsResponse,
+    PityCount, PityCounterResponse, ReceiveNftMsg, PrizesResponse, PrizeCollectionsResponse,
 };
 use crate::state::{
     CONFIG, Config,
-    JOBS, Job, RateDistribution,
+    JOBS, PENDING_JOBS, Job, RateDistribution,
     MYSTERY_BOX, MysteryBox, 
-    PurchasedBox, PURCHASED_BOXES, MYSTERY_BOX_HISTORY
+    PurchasedBox, PURCHASED_BOXES, MYSTERY_BOX_HISTORY,
+    Draw, PITY_COUNTERS, DrawMode,
+    Prize, PRIZES, PRIZE_COUNTS, PRIZE_COLLECTIONS, Reward,
+    JobKind, FusionOutput, FusionRecipe, FUSION_RECIPES, choose_fusion_output,
+    MintedItem, MINTED_ITEMS, UNREVEALED_ITEMS, ITEMS_FROZEN, PoolSwaps, Royalty, BoxBuyback, BOX_BUYBACK, ITEM_BUYBACKS,
 };
 use crate::utils::{
+    sha256_hash,
     make_id,
-    convert_datetime_string
+    random_u128,
+    render_template,
+    convert_event_time,
+};
+use crate::validation::{
+    validate_box_info,
+    validate_box_supply,
+    validate_name,
+    validate_price,
+    validate_schedule,
 };
 
 // version info for migration info
@@ -49,6 +76,14 @@ const MAX_RANGE_RANDOM: i32 = 10000i32;
 
 const SECONDS_PER_HOUR: u64 = 3600u64;
 
+// settings for pagination
+const DEFAULT_LIMIT: u32 = 10u32;
+const MAX_LIMIT: u32 = 30u32;
+
+// settings for pruning purchase history
+const DEFAULT_PRUNE_LIMIT: u32 = 100u32;
+const MAX_PRUNE_LIMIT: u32 = 500u32;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
@@ -72,7 +107,7 @@ pub fn instantiate(
     let sub_msg: Vec<SubMsg> = vec![SubMsg {
         msg: WasmMsg::Instantiate {
             code_id: msg.box_supplier_code_id,
-            msg: to_binary(&Cw721InstantiateMsg {
+            msg: to_json_binary(&Cw721InstantiateMsg {
                 name: msg.box_supplier_name,
                 symbol: msg.box_supplier_symbol,
                 minter: env.contract.address.to_string(),
@@ -88,7 +123,7 @@ pub fn instantiate(
     },SubMsg {
         msg: WasmMsg::Instantiate {
             code_id: msg.item_supplier_code_id,
-            msg: to_binary(&Cw721RarityInstantiateMsg {
+            msg: to_json_binary(&Cw721RarityInstantiateMsg {
                 name: msg.item_supplier_name,
                 symbol: msg.item_supplier_symbol,
                 minter: env.contract.address.to_string(),
@@ -155,9 +190,17 @@ pub fn execute(
         } => execute_create_mystery_box(deps,env,info,box_info),
 
         ExecuteMsg::UpdateMysteryBox {
+            box_update
+        } => execute_update_mystery_box(deps,env,info,box_update),
+
+        ExecuteMsg::Reveal {
             prefix_uri,
-            rate_distribution
-        } => execute_update_mystery_box(deps,env,info,prefix_uri,rate_distribution),
+            provenance_hash,
+        } => execute_reveal(deps,info,prefix_uri,provenance_hash),
+
+        ExecuteMsg::FreezeItemMetadata {
+            token_id,
+        } => execute_freeze_item_metadata(deps,info,token_id),
 
         ExecuteMsg::UpdateConfig {
             aurand_address
@@ -178,10 +221,73 @@ pub fn execute(
             token_id
         } => execute_re_request_randomness(deps, info, env, token_id),
 
+        ExecuteMsg::RetryJobs {
+            request_ids
+        } => execute_retry_jobs(deps, env, info, request_ids),
+
+        ExecuteMsg::CancelJob {
+            request_id
+        } => execute_cancel_job(deps, info, request_id),
+
         ExecuteMsg::Withdraw {
             amount,
             receiver,
         } => execute_withdraw(deps,env,info,amount,receiver),
+
+        ExecuteMsg::WithdrawCw20 {
+            contract_addr,
+            amount,
+            receiver,
+        } => execute_withdraw_cw20(deps,env,info,contract_addr,amount,receiver),
+
+        ExecuteMsg::PruneHistory {
+            event_id,
+            limit,
+        } => execute_prune_history(deps,info,event_id,limit),
+
+        ExecuteMsg::SetBoxBuyback {
+            buyback,
+        } => execute_set_box_buyback(deps,info,buyback),
+
+        ExecuteMsg::SetItemBuyback {
+            rarity,
+            payout,
+        } => execute_set_item_buyback(deps,info,rarity,payout),
+
+        ExecuteMsg::SellBox {
+            token_id,
+        } => execute_sell_box(deps,env,info,token_id),
+
+        ExecuteMsg::SellItem {
+            token_id,
+        } => execute_sell_item(deps,env,info,token_id),
+
+        ExecuteMsg::Reroll {
+            token_id,
+        } => execute_reroll(deps,env,info,token_id),
+
+        ExecuteMsg::Fuse {
+            token_ids,
+        } => execute_fuse(deps,env,info,token_ids),
+
+        ExecuteMsg::SetFusionRecipe {
+            rarity,
+            recipe,
+        } => execute_set_fusion_recipe(deps,info,rarity,recipe),
+
+        ExecuteMsg::SetPrizeCollection {
+            contract_addr,
+            allowed,
+        } => execute_set_prize_collection(deps,info,contract_addr,allowed),
+
+        ExecuteMsg::ReceiveNft(msg) => execute_receive_nft(deps,env,info,msg),
+
+        ExecuteMsg::WithdrawPrizes {
+            id,
+            item_type,
+            limit,
+            receiver,
+        } => execute_withdraw_prizes(deps,env,info,id,item_type,limit,receiver),
     }
 }
 
@@ -214,81 +320,99 @@ fn execute_create_mystery_box(
     if let Some(mystery_box) = MYSTERY_BOX.may_load(deps.storage)?{
         // if mystery-box has expired, save current mystery-box to history and create the new one
         // else return error
-        if mystery_box.end_time < block_time {
+        if mystery_box.end.is_triggered(&env.block) {
             if mystery_box.id == u32::MAX {
                 return Err(ContractError::CustomError{val: String::from("The number of events has reached the limit!")});
             }
 
+            // items of delayed reveal event must get their uri before the event is moved to history
+            if mystery_box.provenance_hash.is_some() && !is_revealed(deps.storage, &mystery_box) {
+                return Err(ContractError::MysteryBoxNotRevealed{});
+            }
+
+            // pending jobs draw from current event, so it can not be replaced until they are fulfilled
+            if PENDING_JOBS.may_load(deps.storage, mystery_box.id)?.unwrap_or(0) > 0 {
+                return Err(ContractError::JobsPending{});
+            }
+
             // the new mystery box event's ID is equal to the old mystery box's ID plus one
             cid = mystery_box.id + 1;
-            MYSTERY_BOX_HISTORY.save(deps.storage, mystery_box.id.clone(), &mystery_box)?;
-
-            // clear purchased history of old mystery-box event
-            PURCHASED_BOXES.clear(deps.storage);
+            MYSTERY_BOX_HISTORY.save(deps.storage, mystery_box.id, &mystery_box)?;
         }else{
             return Err(ContractError::MysteryBoxInOperation{});
         }
     }
 
+    let (start, end) = validate_box_info(&box_info, &env.block)?;
+
+    // items of delayed reveal event could never get their uri once metadata of all items is frozen
+    if box_info.provenance_hash.is_some() && ITEMS_FROZEN.may_load(deps.storage)?.unwrap_or(false) {
+        return Err(ContractError::ItemsFrozen{});
+    }
+
     let BoxInfo{
         name,
         description,
-        start_time,
-        end_time,
         total_supply,
         max_minted_box,
         replacement,
-        price
+        price,
+        reroll_fee,
+        royalty_payment_address,
+        royalty_percentage,
+        item_metadata,
+        box_image,
+        placeholder_uri,
+        provenance_hash,
+        ..
     } = box_info;
 
-    // convert start_time and end_time string to Timestamp
-    let start_time: Timestamp = convert_datetime_string(start_time)?;
-    let end_time: Timestamp = convert_datetime_string(end_time)?;
-
-    // check if the end time is past and start_time <= end_time
-    if end_time <= block_time && start_time <= end_time {
-        return Err(ContractError::InvalidTime{});
-    } 
-    
-    // if not set, max_minted_box will be u64::MAX
-    let max_minted_box = if max_minted_box.is_some(){
-        max_minted_box.unwrap()
-    }else{
-        u64::MAX
+    // royalty requires both payment address and percentage
+    let royalty = match (royalty_payment_address, royalty_percentage) {
+        (None, None) => None,
+        (Some(payment_address), Some(percentage)) if percentage <= 100 => Some(Royalty {
+            payment_address: optional_addr_validate(deps.api, payment_address)?,
+            percentage,
+        }),
+        _ => return Err(ContractError::InvalidRoyalty{}),
     };
 
-    // because of replacement is true minted NFTs will be generated from unique uri
-    // so the number of boxes sold must be less than or equal to the number of uri provided
-    if replacement && max_minted_box > total_supply {
-        return Err(ContractError::CustomError {
-            val: String::from("[replacement == true] but [max_minted_box > total_supply]")
-        });
-    }
+    // if not set, max_minted_box will be u64::MAX
+    let max_minted_box = max_minted_box.unwrap_or(u64::MAX);
 
-    // list of nft id from 0 to total_supply-1
-    let tokens_id = (0u64..=total_supply-1).collect::<Vec<_>>();
 
     MYSTERY_BOX.save(deps.storage, &MysteryBox {  
         description,
-        start_time, 
-        end_time, 
-        tokens_id,
+        start, 
+        end, 
         total_supply,
+        remaining_tokens: total_supply,
         replacement,
         max_minted_box,
+        minted_box: 0,
+        bought_back_box: 0,
         price,
+        reroll_fee,
+        royalty,
+        item_metadata,
+        box_image,
+        minted_item: 0,
         id: cid,
         name: name.clone(),
         rate_distribution: None,
         prefix_uri: None,
         created_time: block_time, 
+        has_sold: false,
+        has_opened: false,
+        placeholder_uri,
+        provenance_hash,
     })?;
 
     Ok(Response::new().add_attribute("action", "create_mystery_box")
                 .add_attribute("id", cid.to_string())
                 .add_attribute("name", name)
-                .add_attribute("start_time", start_time.to_string())
-                .add_attribute("end_time", end_time.to_string())
+                .add_attribute("start", start.to_string())
+                .add_attribute("end", end.to_string())
                 .add_attribute("create_time", block_time.to_string()))
 }
 
@@ -305,8 +429,7 @@ fn execute_update_mystery_box(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    prefix_url: String,
-    rate_distribution: RateDistributionMsg
+    box_update: BoxUpdate,
 ) -> Result<Response, ContractError> {
     let config = CONFIG.load(deps.storage)?;
     // check if sender is owner of this contract
@@ -317,26 +440,283 @@ fn execute_update_mystery_box(
     // get mystery-box event
     let mut mystery_box = get_mystery_box(deps.storage)?;
 
-    // check if mystery box already updated
-    if mystery_box.prefix_uri.is_some() {
-        return Err(ContractError::CustomError {val: String::from("mystery box already updated!")});
-    }
-
     // check if mystery box has ended
-    let block_time = env.block.time;
-    if block_time > mystery_box.end_time {
+    if mystery_box.end.is_triggered(&env.block) {
         return Err(ContractError::MysteryBoxEnded{});
     }
 
-    // init rate distribution for mystery box
-    let rate_distribution: RateDistribution = RateDistribution::new(rate_distribution)?;
+    let BoxUpdate {
+        name,
+        description,
+        start,
+        end,
+        price,
+        max_minted_box,
+        prefix_uri,
+        rate_distribution,
+    } = box_update;
+
+    // buyers paid for the event as it was, so sale fields can not change after the first box is sold
+    // rate distribution which is already set is a sale field too, a missing one can be set until the first box is opened
+    let locked_fields = [
+        ("name", name.is_some() && mystery_box.has_sold),
+        ("description", description.is_some() && mystery_box.has_sold),
+        ("start", start.is_some() && mystery_box.has_sold),
+        ("end", end.is_some() && mystery_box.has_sold),
+        ("price", price.is_some() && mystery_box.has_sold),
+        ("max_minted_box", max_minted_box.is_some() && mystery_box.has_sold),
+        // in delayed reveal mode prefix_uri is only set by reveal
+        ("prefix_uri", prefix_uri.is_some() && (mystery_box.has_opened || mystery_box.provenance_hash.is_some())),
+        ("rate_distribution", rate_distribution.is_some()
+            && (mystery_box.has_opened || (mystery_box.has_sold && mystery_box.rate_distribution.is_some()))),
+    ];
+    if let Some((field, _)) = locked_fields.iter().find(|(_, locked)| *locked) {
+        return Err(ContractError::FieldLocked{field: field.to_string()});
+    }
+
+    // number of items drawn from boxes depends on both fields
+    let supply_changed = max_minted_box.is_some() || rate_distribution.is_some();
+    let mut events: Vec<Event> = Vec::new();
+
+    if let Some(name) = name {
+        validate_name(&name)?;
+        events.push(change_event("name", &mystery_box.name, &name));
+        mystery_box.name = name;
+    }
+
+    if let Some(description) = description {
+        events.push(change_event("description", &mystery_box.description, &description));
+        mystery_box.description = description;
+    }
+
+    if start.is_some() || end.is_some() {
+        let new_start = start.map(|start| convert_event_time(&start)).transpose()?.unwrap_or(mystery_box.start);
+        let new_end = end.map(|end| convert_event_time(&end)).transpose()?.unwrap_or(mystery_box.end);
+        validate_schedule(&new_start, &new_end, &env.block)?;
+
+        if new_start != mystery_box.start {
+            events.push(change_event("start", mystery_box.start, new_start));
+            mystery_box.start = new_start;
+        }
+        if new_end != mystery_box.end {
+            events.push(change_event("end", mystery_box.end, new_end));
+            mystery_box.end = new_end;
+        }
+    }
+
+    if let Some(price) = price {
+        validate_price(&price)?;
+        events.push(change_event("price", &mystery_box.price, &price));
+        mystery_box.price = price;
+    }
+
+    if let Some(max_minted_box) = max_minted_box {
 
-    mystery_box.prefix_uri = Some(prefix_url);
-    mystery_box.rate_distribution = Some(rate_distribution);
+        // deck is built for number of boxes, so it must be built again
+        let is_deck = matches!(&mystery_box.rate_distribution, Some(distribution) if distribution.mode == DrawMode::Deck);
+        if is_deck && rate_distribution.is_none() && max_minted_box != mystery_box.max_minted_box {
+            return Err(ContractError::InvalidDeckSize{});
+        }
+
+        events.push(change_event("max_minted_box", mystery_box.max_minted_box, max_minted_box));
+        mystery_box.max_minted_box = max_minted_box;
+    }
+
+    if let Some(prefix_uri) = prefix_uri {
+        events.push(change_event("prefix_uri", mystery_box.prefix_uri.clone().unwrap_or_default(), &prefix_uri));
+        mystery_box.prefix_uri = Some(prefix_uri);
+    }
+
+    if let Some(rate_distribution) = rate_distribution {
+        // init rate distribution for mystery box
+        // deck size is total number of boxes of this event (sold and in stock)
+        let box_supply = mystery_box.max_minted_box.saturating_add(mystery_box.minted_box);
+        let rate_distribution: RateDistribution = RateDistribution::new(rate_distribution, box_supply)?;
+
+        // in delayed reveal mode only uri of mystery box pool are hidden behind provenance hash
+        if mystery_box.provenance_hash.is_some() && rate_distribution.vec.iter().any(|item_type| item_type.pool.is_some()) {
+            return Err(ContractError::InvalidUriPool{});
+        }
+
+        // contract must hold enough to pay rewards for the whole supply of item types
+        for item_type in rate_distribution.vec.iter() {
+            if let Some(Reward::Cw20 { contract_addr, .. }) = &item_type.reward {
+                optional_addr_validate(deps.api, contract_addr.clone())?;
+            }
+        }
+        for liability in rate_distribution.reward_liabilities()?.iter() {
+            if asset_balance(deps.as_ref(), &env, liability)? < liability.amount() {
+                return Err(ContractError::InsufficientEscrow{});
+            }
+        }
+
+        // distribution is too large for an attribute, only item types are shown
+        let item_types = |distribution: &RateDistribution| distribution.vec.iter()
+            .map(|item_type| item_type.name.clone())
+            .collect::<Vec<String>>()
+            .join(",");
+        let old_item_types = mystery_box.rate_distribution.as_ref().map(item_types).unwrap_or_default();
+        events.push(change_event("rate_distribution", old_item_types, item_types(&rate_distribution)));
+        mystery_box.rate_distribution = Some(rate_distribution);
+    }
+
+    // every item drawn from boxes which are not opened yet must get an uri
+    // both fields are locked once a box is opened, so every box is still counted
+    if supply_changed {
+        validate_box_supply(
+            Some(mystery_box.max_minted_box.saturating_add(mystery_box.minted_box)),
+            mystery_box.remaining_tokens,
+            mystery_box.replacement,
+            mystery_box.rate_distribution.as_ref(),
+        )?;
+    }
 
     MYSTERY_BOX.save(deps.storage, &mystery_box)?;
 
-    Ok(Response::new())
+    Ok(Response::new().add_attribute("action", "update_mystery_box")
+        .add_attribute("id", mystery_box.id.to_string())
+        .add_events(events))
+}
+
+/// event emitted for each changed field of mystery box event
+/// attributes can not be empty, so empty old or new value is left out
+fn change_event(field: &str, old: impl ToString, new: impl ToString) -> Event {
+    let event = Event::new("mystery_box_change").add_attribute("field", field);
+    [("old", old.to_string()), ("new", new.to_string())].into_iter()
+        .filter(|(_, value)| !value.is_empty())
+        .fold(event, |event, (key, value)| event.add_attribute(key, value))
+}
+
+fn execute_reveal(
+    deps: DepsMut,
+    info: MessageInfo,
+    prefix_uri: String,
+    provenance_hash: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    // check if sender is owner of this contract
+    if config.owner != info.sender {
+        return Err(ContractError::Unauthorized{});
+    }
+
+    // must link to a cw721 item contract
+    if config.item_supplier.is_none() {
+        return Err(ContractError::ItemSupplierNotLinked{});
+    }
+    let item_supplier = config.item_supplier.unwrap();
+
+    // get mystery-box event
+    let mut mystery_box = get_mystery_box(deps.storage)?;
+
+    // check if mystery box is in delayed reveal mode
+    let committed_hash = match &mystery_box.provenance_hash {
+        Some(committed_hash) => committed_hash.clone(),
+        None => return Err(ContractError::RevealNotAllowed{}),
+    };
+
+    // prefix uri must be the one committed when mystery box was created
+    let hash = hex::encode(sha256_hash(prefix_uri.as_bytes()));
+    if !provenance_hash.eq_ignore_ascii_case(&committed_hash) || !hash.eq_ignore_ascii_case(&committed_hash) {
+        return Err(ContractError::InvalidProvenance{});
+    }
+
+    if mystery_box.prefix_uri.is_none() {
+        mystery_box.prefix_uri = Some(prefix_uri.clone());
+        MYSTERY_BOX.save(deps.storage, &mystery_box)?;
+    }
+
+    // update uri of items minted before reveal, a batch at a time
+    let items = UNREVEALED_ITEMS.prefix(mystery_box.id)
+        .range(deps.storage, None, None, Order::Ascending)
+        .take(MAX_LIMIT as usize)
+        .collect::<StdResult<Vec<(String, u64)>>>()?;
+
+    let mut update_msgs: Vec<WasmMsg> = Vec::new();
+    for (token_id, token_index) in items.iter() {
+        UNREVEALED_ITEMS.remove(deps.storage, (mystery_box.id, token_id));
+
+        // item may have been burned by its owner, fused or re-rolled
+        let owner: StdResult<OwnerOfResponse> = deps.querier.query_wasm_smart(
+            item_supplier.to_string(),
+            &Cw721QueryMsg::OwnerOf {
+                token_id: token_id.clone(),
+                include_expired: Some(true),
+            },
+        );
+        if owner.is_err() {
+            continue;
+        }
+
+        update_msgs.push(WasmMsg::Execute {
+            contract_addr: item_supplier.to_string(),
+            msg: to_json_binary(&Cw721RarityExecuteMsg::Extension {
+                msg: Cw721RarityMetadataMsg::UpdateMetadata {
+                    token_id: token_id.clone(),
+                    token_uri: Some(format!("{}{}", prefix_uri, token_index)),
+                    extension: None,
+                },
+            })?,
+            funds: vec![],
+        });
+    }
+
+    Ok(Response::new().add_messages(update_msgs)
+        .add_attribute("action", "reveal")
+        .add_attribute("id", mystery_box.id.to_string())
+        .add_attribute("prefix_uri", prefix_uri)
+        .add_attribute("revealed_items", items.len().to_string())
+        .add_attribute("finished", is_revealed(deps.storage, &mystery_box).to_string()))
+}
+
+fn execute_freeze_item_metadata(
+    deps: DepsMut,
+    info: MessageInfo,
+    token_id: Option<String>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    // check if sender is owner of this contract
+    if config.owner != info.sender {
+        return Err(ContractError::Unauthorized{});
+    }
+
+    // must link to a cw721 item contract
+    if config.item_supplier.is_none() {
+        return Err(ContractError::ItemSupplierNotLinked{});
+    }
+    let item_supplier = config.item_supplier.unwrap();
+
+    // items minted before reveal must get their uri first
+    if let Some(mystery_box) = MYSTERY_BOX.may_load(deps.storage)? {
+        if mystery_box.provenance_hash.is_some() && !is_revealed(deps.storage, &mystery_box) {
+            return Err(ContractError::MysteryBoxNotRevealed{});
+        }
+    }
+
+    if token_id.is_none() {
+        ITEMS_FROZEN.save(deps.storage, &true)?;
+    }
+
+    let freeze_msg = WasmMsg::Execute {
+        contract_addr: item_supplier.to_string(),
+        msg: to_json_binary(&Cw721RarityExecuteMsg::Extension {
+            msg: Cw721RarityMetadataMsg::FreezeMetadata {
+                token_id: token_id.clone(),
+            },
+        })?,
+        funds: vec![],
+    };
+
+    Ok(Response::new().add_message(freeze_msg)
+        .add_attribute("action", "freeze_item_metadata")
+        .add_attribute("token_id", token_id.unwrap_or_else(|| String::from("all"))))
+}
+
+// check if prefix uri of mystery box is set and all items minted before reveal have their uri
+fn is_revealed(storage: &dyn Storage, mystery_box: &MysteryBox) -> bool {
+    mystery_box.prefix_uri.is_some() && UNREVEALED_ITEMS.prefix(mystery_box.id)
+        .keys_raw(storage, None, None, Order::Ascending)
+        .next()
+        .is_none()
 }
 
 fn execute_update_config(
@@ -406,44 +786,64 @@ fn execute_mint_box(
 
     // check if mystery box expired
     let block_time = env.block.time;
-    if block_time >= mystery_box.end_time {
+    if mystery_box.end.is_triggered(&env.block) {
         return Err(ContractError::MysteryBoxEnded{});
     }
 
     // prefix_token_id is box id
     // can be used to check which mystery box the NFT item belongs to
-    let prefix_token_id = mystery_box.id.clone();
-    // generate suffix token id using mystery box address, block time, current box supply
+    let prefix_token_id = mystery_box.id;
+    // number of boxes ever minted, bought back boxes are counted too so it never repeats
+    let box_number = mystery_box.minted_box + mystery_box.bought_back_box + 1;
+
+    // generate suffix token id using mystery box address, block time, box number
     let suffix_token_id = make_id(vec![
         env.contract.address.to_string(),
         block_time.to_string(), 
-        mystery_box.max_minted_box.to_string()
+        box_number.to_string()
     ]);
     
     // unique token id is combine of prefix_token_id and suffix_token_id
     let token_id = format!("{}_{}",prefix_token_id,suffix_token_id);
 
+    // box metadata carries mystery box event and purchase info
+    let (royalty_payment_address, royalty_percentage) = Royalty::metadata_fields(&mystery_box.royalty);
+    let extension = Some(Cw721Metadata {
+        image: mystery_box.box_image.clone(),
+        description: Some(mystery_box.description.clone()),
+        name: Some(format!("{} #{}", mystery_box.name, box_number)),
+        event_id: Some(mystery_box.id),
+        event_name: Some(mystery_box.name.clone()),
+        price: Some(Coin { denom: denom.clone(), amount: price }),
+        purchase_time: Some(block_time),
+        royalty_payment_address,
+        royalty_percentage,
+    });
+
     // create mint message NFT for the sender
     let mint_msg = WasmMsg::Execute {
         contract_addr: box_supplier.to_string(),
-        msg: to_binary(&Cw721ExecuteMsg::Mint(Cw721MintMsg {
+        msg: to_json_binary(&Cw721ExecuteMsg::Mint(Cw721MintMsg {
             token_id: token_id.clone(),
             owner: info.sender.to_string(),
             token_uri: None,
-            extension: None,
+            extension,
         }))?,
         funds: vec![],
     };
     
     // only max_item_supply - 1 box in stock
     mystery_box.max_minted_box -= 1;
+    mystery_box.minted_box += 1;
+    mystery_box.has_sold = true;
     MYSTERY_BOX.save(deps.storage, &mystery_box)?;
 
     // update purchased box history
-    PURCHASED_BOXES.save(deps.storage, token_id.clone(), &PurchasedBox { 
+    PURCHASED_BOXES.save(deps.storage, (mystery_box.id, &token_id), &PurchasedBox { 
         is_opened: false,
         open_time: None,
         is_received_randomness: false,
+        price: Coin { denom, amount: price },
     })?;
 
     Ok(Response::new().add_message(mint_msg)
@@ -467,22 +867,25 @@ fn execute_open_box(
     let box_supplier = config.box_supplier.unwrap();
 
     // get mystery-box event
-    let mystery_box = get_mystery_box(deps.storage)?;
+    let mut mystery_box = get_mystery_box(deps.storage)?;
     
     // check if mystery box has been updated
-    if mystery_box.prefix_uri.is_none(){
+    // in delayed reveal mode boxes can be opened before prefix uri is set
+    if mystery_box.rate_distribution.is_none()
+        || (mystery_box.prefix_uri.is_none() && mystery_box.placeholder_uri.is_none()) {
         return Err(ContractError::MysteryBoxNotUpdated{});
     }
 
     // check if box with id exist
-    if !PURCHASED_BOXES.has(deps.storage, token_id.clone()) {
+    if !PURCHASED_BOXES.has(deps.storage, (mystery_box.id, &token_id)) {
         return Err(ContractError::TokenNotRecognized{});
     }
     let PurchasedBox{
         is_opened,
         open_time: _,
-        is_received_randomness
-    } = PURCHASED_BOXES.load(deps.storage, token_id.clone())?;
+        is_received_randomness,
+        price,
+    } = PURCHASED_BOXES.load(deps.storage, (mystery_box.id, &token_id))?;
 
     // if the box has been opened return error
     if is_opened {
@@ -491,12 +894,12 @@ fn execute_open_box(
 
     let block_time = env.block.time;
     // user only allowed to open box when time start
-    if mystery_box.start_time > block_time {
+    if !mystery_box.start.is_triggered(&env.block) {
         return Err(ContractError::MysteryBoxNotStarted{});
     }
 
     // user cannot open box when time out
-    if mystery_box.end_time <= block_time {
+    if mystery_box.end.is_triggered(&env.block) {
         return Err(ContractError::MysteryBoxEnded{});
     }
 
@@ -508,7 +911,7 @@ fn execute_open_box(
     let owner_response: StdResult<cw721::OwnerOfResponse> =
         deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
             contract_addr: box_supplier.to_string(),
-            msg: to_binary(&query_owner_msg)?,
+            msg: to_json_binary(&query_owner_msg)?,
         }));
     match owner_response {
         Ok(owner) => {
@@ -522,64 +925,48 @@ fn execute_open_box(
     };
 
     // check that user approves this contract to manage this token
-    // for now, we require never expired approval
-    let query_approval_msg = Cw721QueryMsg::Approval {
-        token_id: token_id.clone(),
-        spender: env.contract.address.to_string(),
-        include_expired: Some(true),
-    };
-    let approval_response: StdResult<cw721::ApprovalResponse> =
-        deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
-            contract_addr: box_supplier.to_string(),
-            msg: to_binary(&query_approval_msg)?,
-        }));
-
-    // check if approval is never expired
-    match approval_response {
-        Ok(approval) => match approval.approval.expires {
-            Cw721Expiration::Never {} => {}
-            _ => return Err(ContractError::Unauthorized {}),
-        },
-        Err(_) => {
-            return Err(ContractError::CustomError {
-                val: "Require never expired approval".to_string(),
-            });
-        }
-    }
+    check_burn_approval(deps.as_ref(), &env, &box_supplier, &token_id, &info.sender)?;
 
     // generate request id for receiving randomness
     let request_id = token_id.clone();
     
     // request randomness from aurand contract
-    let random_msg = WasmMsg::Execute {
-        contract_addr: config.aurand_address.to_string(),
-        msg: to_binary(&AurandExecuteMsg::RequestIntRandomness { 
-                        request_id: request_id.clone(),
-                        num: NUMBER_OF_RANDOM,
-                        min: MIN_RANGE_RANDOM,
-                        max: MAX_RANGE_RANDOM,
-                    })?,
-        funds: info.funds,
-    };
+    let random_msg = request_randomness_msg(
+        config.aurand_address, 
+        request_id.clone(), 
+        mystery_box.items_per_box(),
+        info.funds
+    )?;
 
     let burn_msg = WasmMsg::Execute {
         contract_addr: box_supplier.to_string(),
-        msg: to_binary(&Cw721ExecuteMsg::Burn{
+        msg: to_json_binary(&Cw721ExecuteMsg::Burn{
             token_id: token_id.clone()
         })?,
         funds: vec![],
     };
 
     // save request open box job, wait for randomness
-    JOBS.save(deps.storage, request_id.clone(), &Job{
+    save_job(deps.storage, &request_id, &Job{
+        id: mystery_box.id,
         sender: info.sender,
+        created_time: block_time,
+        requested_time: block_time,
+        kind: JobKind::OpenBox {},
     })?;
 
+    // rate distribution is used from the first opening on
+    if !mystery_box.has_opened {
+        mystery_box.has_opened = true;
+        MYSTERY_BOX.save(deps.storage, &mystery_box)?;
+    }
+
     // set box is_opened status to true and open_time to block timestamp
-    PURCHASED_BOXES.save(deps.storage, token_id.clone(), &PurchasedBox { 
+    PURCHASED_BOXES.save(deps.storage, (mystery_box.id, &token_id), &PurchasedBox { 
         is_opened: true,
         open_time: Some(block_time),
-        is_received_randomness
+        is_received_randomness,
+        price,
     })?;
 
     Ok(Response::new()
@@ -610,20 +997,38 @@ fn execute_receive_hex_randomness(
     }
     let item_supplier = config.item_supplier.unwrap();
 
-    // get mystery-box event
-    let mut mystery_box = get_mystery_box(deps.storage)?;
-
     // check if a job with job_id exist
     if !JOBS.has(deps.storage, request_id.clone()) {
         return Err(ContractError::JobNotExist{});
     }
 
     // get job by request id
-    let Job{sender}= if let Some(job) = JOBS.may_load(deps.storage, request_id.clone())?{
+    let Job{id, sender, kind, ..}= if let Some(job) = JOBS.may_load(deps.storage, request_id.clone())?{
         job
     }else{
         return Err(ContractError::JobNotExist{});
     };
+
+    match kind {
+        // randomness of fusion job is used to choose fusion output
+        JobKind::Fuse { outputs } => {
+            return receive_fusion_randomness(deps, item_supplier, request_id, id, sender, outputs, randomness);
+        },
+        // randomness of reroll job is used to draw one item
+        JobKind::Reroll { burned_token_id } => {
+            return receive_reroll_randomness(deps, item_supplier, request_id, id, sender, burned_token_id, randomness);
+        },
+        JobKind::OpenBox {} => {},
+    }
+
+    // get mystery-box event
+    let mut mystery_box = get_mystery_box(deps.storage)?;
+
+    // box is drawn from the event it was bought in
+    // a new event can not be created while jobs of current event are pending
+    if mystery_box.id != id {
+        return Err(ContractError::EventNotCurrent{});
+    }
     
     // request id is also id for user's purchased box
     // it will be use to create unique token id for nft item
@@ -632,8 +1037,9 @@ fn execute_receive_hex_randomness(
     // check if a box with an ID exists and hasn't been opened
     let purchased_box = if let Some(pb) = PURCHASED_BOXES.may_load(
         deps.storage, 
-        item_token_id.clone()
+        (id, &item_token_id)
     )?{
+        // unreachable, open box job is only saved together with is_opened of its box
         if !pb.is_opened {
             return Err(ContractError::BoxNotOpened{})
         }
@@ -643,142 +1049,679 @@ fn execute_receive_hex_randomness(
     };
 
     // check if randomness valid
-    if randomness.len() != 2 || 
-    randomness[0] < MIN_RANGE_RANDOM || randomness[0] > MAX_RANGE_RANDOM ||
-    randomness[1] < MIN_RANGE_RANDOM || randomness[1] > MAX_RANGE_RANDOM {
-        return Err(ContractError::InvalidRandomness{});
-    }
-
-    // randomness received from aurand contract 
-    // it between MIN_RANGE_RANDOM..MAX_RANGE_RANDOM
-    let (random_type, random_index) = (randomness[0] as usize, randomness[1] as usize);
-    
-    let mut rate_distribution = mystery_box.rate_distribution.clone().unwrap();
-
-    // get index of item_type based on aurand randomness
-    let index = rate_distribution.get_item_type_index(
-        random_type as u128, 
-        MAX_RANGE_RANDOM as u128
-    )?;
-
-    // get current purity of item_type at specified index
-    let purity = rate_distribution.purity(index)?;
-
-    // update supply and rate for item_type at specifed index
-    rate_distribution.update_item_type(index)?;
-
-    // get item_type by index
-    let item_type = rate_distribution.vec[index].clone();
-
-    // get a list of token ids provided
-    let tokens_id = mystery_box.tokens_id.clone();
-
-    // random tokens id index using aurand randomnesss
-    let tokens_id_index = random_index % tokens_id.len();
-
-    // get item token id by index
-    let token_id = tokens_id[tokens_id_index];
-
-    // prefix_uri of NFTs resource collection
-    let prefix_uri = mystery_box.prefix_uri.clone();
-    
-    // token uri made by combining prefix_uri and token_id
-    let unique_token_uri = format!("{}{}",prefix_uri.unwrap(),token_id);
+    let items_per_box = mystery_box.items_per_box();
+    let randomness = parse_randomness(&randomness, items_per_box)?;
+
+    // pity counters of the opener
+    let mut pity_counters = load_pity_counters(deps.storage, &mystery_box, &sender)?;
+    let mut prize_counts = load_prize_counts(deps.storage, &mystery_box)?;
+
+    // draw items from mystery box, this also update rate distribution and uri pools of mystery box
+    let mut swaps = PoolSwaps::new(deps.storage, mystery_box.id);
+    let draws = mystery_box.draw_bundle(&mut swaps, &randomness, MAX_RANGE_RANDOM as u128, &mut pity_counters, &mut prize_counts)?;
+    swaps.into_changes().save(deps.storage)?;
+
+    save_pity_counters(deps.storage, &mystery_box, &sender, &pity_counters)?;
+
+    // messages to send items and rewards of drawn item_types to the opener
+    let mut reward_msgs: Vec<CosmosMsg> = Vec::new();
+    let mut attributes: Vec<(String, String)> = Vec::new();
+    for (i, (draw, (random_type, random_index))) in draws.into_iter().zip(randomness).enumerate() {
+        let token_id = derive_item_token_id(&item_token_id, i, items_per_box);
+        attributes.push((String::from("item_token_id"), token_id.clone()));
+        if draw.mint_item && !draw.prize {
+            attributes.push((String::from("token_uri"), draw.token_uri.clone()));
+        }
 
-    // if replacement == true, replace selected uri from tokens_id
-    // to make all minted Item NFTs unique
-    if mystery_box.replacement {
-        mystery_box.remove_token_id(tokens_id_index);
+        reward_msgs.extend(draw_reward_msgs(
+            deps.storage,
+            &mut mystery_box,
+            &item_supplier,
+            &sender,
+            token_id,
+            draw,
+            random_u128(&[random_type, random_index as u128]),
+        )?);
     }
 
-    // update mystery-box rate distribution
-    mystery_box.rate_distribution = Some(rate_distribution);
-
-    // cw721rarity metadata
-    let extension = Some(Cw721RarityMetadata {
-        rarity: item_type.name,  
-        purity: purity.to_string(),
-        ..Cw721RarityMetadata::default()
-    });
+    MYSTERY_BOX.save(deps.storage, &mystery_box)?;
 
-    // create mint message NFT for the sender
-    let mint_msg = WasmMsg::Execute {
-        contract_addr: item_supplier.to_string(),
-        msg: to_binary(&Cw721RarityExecuteMsg::Mint(Cw721RarityMintMsg {
-            token_id: item_token_id.clone(), // unique token id
-            owner: sender.clone().to_string(),
-            token_uri: Some(unique_token_uri.clone()), // unique token uri
-            extension,
-        }))?,
-        funds: vec![],
-    };
+    // job is fulfilled, remove it
+    remove_job(deps.storage, &request_id, id)?;
 
-    MYSTERY_BOX.save(deps.storage, &mystery_box)?;
-    PURCHASED_BOXES.save(deps.storage, item_token_id.clone(),&PurchasedBox{ 
+    PURCHASED_BOXES.save(deps.storage, (mystery_box.id, &item_token_id),&PurchasedBox{ 
         is_opened: true,
         open_time: purchased_box.open_time,
         is_received_randomness: true,
+        price: purchased_box.price,
     })?;
 
-    Ok(Response::new().add_message(mint_msg)
+    Ok(Response::new().add_messages(reward_msgs)
                 .add_attribute("action", "receive_hex_randomness")
                 .add_attribute("token_id", item_token_id)
-                .add_attribute("token_uri", unique_token_uri)
+                .add_attributes(attributes)
                 .add_attribute("minter", sender))
 }
 
-fn execute_re_request_randomness(
+/// draw a new item for a re-rolled item
+fn receive_reroll_randomness(
     deps: DepsMut,
-    info: MessageInfo,
-    env: Env,
-    token_id: String
+    item_supplier: Addr,
+    request_id: String,
+    id: u32,
+    sender: Addr,
+    burned_token_id: String,
+    randomness: Vec<i32>,
 ) -> Result<Response, ContractError> {
-    // check if box with id exist
-    if !PURCHASED_BOXES.has(deps.storage, token_id.clone()) {
-        return Err(ContractError::TokenNotRecognized{});
-    }
-    let PurchasedBox{
-        is_opened,
-        open_time,
-        is_received_randomness
-    } = PURCHASED_BOXES.load(deps.storage, token_id.clone())?;
+    // get mystery-box event
+    let mut mystery_box = get_mystery_box(deps.storage)?;
 
-    // only allow to re-request randomness if box was opened but not yet receive randomness
-    if !(is_opened == true && is_received_randomness == false) {
-        return Err(ContractError::InvalidCondition{});
+    // burned item was returned to the event the job was created in, new item is drawn from the same event
+    if mystery_box.id != id {
+        return Err(ContractError::EventNotCurrent{});
     }
 
-    let open_time = open_time.unwrap();
-    let block_time = env.block.time;
+    // check if randomness valid
+    let randomness = parse_randomness(&randomness, 1u32)?;
+    let (random_type, random_index) = randomness[0];
+
+    // reroll is not an opening, pity counters of the opener are neither used nor moved
+    let item_types = mystery_box.rate_distribution.as_ref().map(|distribution| distribution.vec.len()).unwrap_or(0);
+    let mut pity_counters: Vec<u32> = vec![0; item_types];
+    let mut prize_counts = load_prize_counts(deps.storage, &mystery_box)?;
+
+    let mut swaps = PoolSwaps::new(deps.storage, mystery_box.id);
+    let draw = mystery_box.draw(&mut swaps, random_type, random_index, MAX_RANGE_RANDOM as u128, &mut pity_counters, &mut prize_counts)?;
+    swaps.into_changes().save(deps.storage)?;
+
+    let token_uri = draw.token_uri.clone();
+    let reward_msgs = draw_reward_msgs(
+        deps.storage,
+        &mut mystery_box,
+        &item_supplier,
+        &sender,
+        request_id.clone(),
+        draw,
+        random_u128(&[random_type, random_index as u128]),
+    )?;
 
-    // only allow to re-request randomness if box was opened more than 1 hour ago
-    if block_time < open_time.plus_seconds(SECONDS_PER_HOUR) {
-        return Err(ContractError::InvalidCondition{});
-    }
+    MYSTERY_BOX.save(deps.storage, &mystery_box)?;
 
-    // generate request id for receiving randomness
-    let request_id = token_id.clone();
-    
-    // request randomness from aurand contract
-    let random_msg = WasmMsg::Execute {
-        contract_addr: CONFIG.load(deps.storage)?.aurand_address.to_string(),
-        msg: to_binary(&AurandExecuteMsg::RequestIntRandomness { 
-                        request_id: request_id.clone(),
-                        num: NUMBER_OF_RANDOM,
-                        min: MIN_RANGE_RANDOM,
-                        max: MAX_RANGE_RANDOM,
-                    })?,
-        funds: info.funds,
-    };
+    // job is fulfilled, remove it
+    remove_job(deps.storage, &request_id, id)?;
 
-    Ok(Response::new().add_message(random_msg)
-        .add_attribute("action", "request_randomness")
-        .add_attribute("token_id", token_id))
+    // token uri is empty if no item is minted
+    let token_uri = Some(token_uri).filter(|token_uri| !token_uri.is_empty())
+        .map(|token_uri| ("token_uri", token_uri));
+
+    Ok(Response::new().add_messages(reward_msgs)
+        .add_attribute("action", "receive_reroll_randomness")
+        .add_attribute("token_id", request_id)
+        .add_attribute("burned_token_id", burned_token_id)
+        .add_attributes(token_uri)
+        .add_attribute("minter", sender))
 }
 
-fn execute_withdraw(
+/// mint fused item with output chosen by aurand randomness
+fn receive_fusion_randomness(
     deps: DepsMut,
-    env: Env,
+    item_supplier: Addr,
+    request_id: String,
+    id: u32,
+    sender: Addr,
+    outputs: Vec<FusionOutput>,
+    randomness: Vec<i32>,
+) -> Result<Response, ContractError> {
+    // check if randomness valid
+    let randomness = parse_randomness(&randomness, 1u32)?;
+    let (random_output, _) = randomness[0];
+
+    let output = choose_fusion_output(&outputs, random_output, MAX_RANGE_RANDOM as u128)?;
+
+    // fused item gets its token uri from the event the job was created in
+    if get_mystery_box(deps.storage)?.id != id {
+        return Err(ContractError::EventNotCurrent{});
+    }
+
+    // job is fulfilled, remove it
+    remove_job(deps.storage, &request_id, id)?;
+
+    let mint_msg = fusion_mint_msg(deps.storage, &item_supplier, &sender, request_id.clone(), &output.rarity)?;
+
+    Ok(Response::new().add_message(mint_msg)
+        .add_attribute("action", "receive_fusion_randomness")
+        .add_attribute("token_id", request_id)
+        .add_attribute("rarity", output.rarity)
+        .add_attribute("minter", sender))
+}
+
+/// create message to mint fused item
+/// metadata is made like an item drawn from current mystery box event, but no token uri is taken
+fn fusion_mint_msg(
+    storage: &mut dyn Storage,
+    item_supplier: &Addr,
+    owner: &Addr,
+    token_id: String,
+    rarity: &str,
+) -> Result<WasmMsg, ContractError> {
+    let mut mystery_box = get_mystery_box(storage)?;
+
+    let Draw{
+        item_type,
+        purity,
+        image,
+        attributes,
+        ..
+    } = mystery_box.fuse_item(rarity)?;
+
+    // cw721rarity metadata
+    let extension = Some(item_metadata(&mut mystery_box, item_type, purity, image, attributes));
+
+    MYSTERY_BOX.save(storage, &mystery_box)?;
+
+    Ok(WasmMsg::Execute {
+        contract_addr: item_supplier.to_string(),
+        msg: to_json_binary(&Cw721RarityExecuteMsg::Mint(Cw721RarityMintMsg {
+            token_id,
+            owner: owner.to_string(),
+            token_uri: None,
+            extension,
+        }))?,
+        funds: vec![],
+    })
+}
+
+/// create messages to send item and reward of a draw to the opener
+/// if a prize is drawn, one of escrowed prizes of the item_type is transferred instead of minting
+fn draw_reward_msgs(
+    storage: &mut dyn Storage,
+    mystery_box: &mut MysteryBox,
+    item_supplier: &Addr,
+    recipient: &Addr,
+    item_token_id: String,
+    draw: Draw,
+    prize_random: u128,
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    let Draw{
+        item_type,
+        purity,
+        token_index,
+        token_uri,
+        hidden,
+        reward,
+        mint_item,
+        prize,
+        image,
+        attributes,
+    } = draw;
+
+    let mut msgs: Vec<CosmosMsg> = Vec::new();
+
+    if mint_item {
+        if prize {
+            // prize count was taken from PRIZE_COUNTS, so a prize is always found
+            let (prize_index, prize) = match pick_prize(storage, mystery_box.id, &item_type, prize_random)? {
+                Some(prize) => prize,
+                None => return Err(ContractError::CustomError{val: String::from("Prize not found")}),
+            };
+            remove_prize(storage, mystery_box.id, &item_type, prize_index)?;
+
+            msgs.push(WasmMsg::Execute {
+                contract_addr: prize.contract.to_string(),
+                msg: to_json_binary(&Cw721BaseExecuteMsg::TransferNft {
+                    recipient: recipient.to_string(),
+                    token_id: prize.token_id,
+                })?,
+                funds: vec![],
+            }.into());
+        } else {
+            // keep drawn item so it can be re-rolled
+            MINTED_ITEMS.save(storage, item_token_id.clone(), &MintedItem {
+                id: mystery_box.id,
+                item_type: item_type.clone(),
+                token_index,
+            })?;
+
+            // item with placeholder uri gets its real uri on reveal
+            if hidden {
+                UNREVEALED_ITEMS.save(storage, (mystery_box.id, &item_token_id), &token_index)?;
+            }
+
+            // cw721rarity metadata
+            let extension = Some(item_metadata(mystery_box, item_type, purity, image, attributes));
+
+            // create mint message NFT for the sender
+            msgs.push(WasmMsg::Execute {
+                contract_addr: item_supplier.to_string(),
+                msg: to_json_binary(&Cw721RarityExecuteMsg::Mint(Cw721RarityMintMsg {
+                    token_id: item_token_id, // unique token id
+                    owner: recipient.to_string(),
+                    token_uri: Some(token_uri), // unique token uri
+                    extension,
+                }))?,
+                funds: vec![],
+            }.into());
+        }
+    }
+
+    // pay fungible reward of item_type
+    if let Some(reward) = reward {
+        msgs.push(reward_msg(reward, recipient)?);
+    }
+
+    Ok(msgs)
+}
+
+/// metadata of an item minted from mystery box, name and description are made from templates of the event
+/// event id, item type, purity and serial number of the item are added as traits
+fn item_metadata(
+    mystery_box: &mut MysteryBox,
+    item_type: String,
+    purity: Decimal,
+    image: Option<String>,
+    mut attributes: Vec<Trait>,
+) -> Cw721RarityMetadata {
+    mystery_box.minted_item += 1;
+    let serial_number = mystery_box.minted_item.to_string();
+
+    let values = [
+        ("event_name", mystery_box.name.clone()),
+        ("event_id", mystery_box.id.to_string()),
+        ("item_type", item_type.clone()),
+        ("purity", purity.to_string()),
+        ("n", serial_number.clone()),
+    ];
+    let (name, description) = match &mystery_box.item_metadata {
+        Some(template) => (
+            template.name.as_ref().map(|name| render_template(name, &values)),
+            template.description.as_ref().map(|description| render_template(description, &values)),
+        ),
+        None => (None, None),
+    };
+
+    let number_trait = |trait_type: &str, value: String| Trait {
+        display_type: Some(String::from("number")),
+        trait_type: trait_type.to_string(),
+        value,
+    };
+    attributes.extend(vec![
+        number_trait("event_id", mystery_box.id.to_string()),
+        Trait {
+            display_type: None,
+            trait_type: String::from("item_type"),
+            value: item_type.clone(),
+        },
+        number_trait("purity", purity.to_string()),
+        number_trait("serial_number", serial_number),
+    ]);
+
+    let (royalty_payment_address, royalty_percentage) = Royalty::metadata_fields(&mystery_box.royalty);
+    Cw721RarityMetadata {
+        image,
+        description,
+        name,
+        attributes: Some(attributes),
+        rarity: item_type,
+        purity: purity.to_string(),
+        royalty_payment_address,
+        royalty_percentage,
+        ..Cw721RarityMetadata::default()
+    }
+}
+
+/// create message to pay a reward to recipient
+fn reward_msg(reward: Reward, recipient: &Addr) -> Result<CosmosMsg, ContractError> {
+    match reward {
+        Reward::Native { amount } => Ok(BankMsg::Send { 
+            to_address: recipient.to_string(), 
+            amount: vec![amount] 
+        }.into()),
+        Reward::Cw20 { contract_addr, amount } => Ok(WasmMsg::Execute {
+            contract_addr,
+            msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: recipient.to_string(),
+                amount,
+            })?,
+            funds: vec![],
+        }.into()),
+    }
+}
+
+/// amount of a reward asset reserved for rewards of current mystery box
+fn reserved_amount(storage: &dyn Storage, asset: &Reward) -> Result<Uint128, ContractError> {
+    let liabilities = match MYSTERY_BOX.may_load(storage)? {
+        Some(MysteryBox { rate_distribution: Some(rate_distribution), .. }) => rate_distribution.reward_liabilities()?,
+        _ => vec![],
+    };
+
+    Ok(liabilities.iter()
+        .find(|liability| liability.is_same_asset(asset))
+        .map_or(Uint128::zero(), |liability| liability.amount()))
+}
+
+/// balance of a reward asset held by this contract
+fn asset_balance(deps: Deps, env: &Env, asset: &Reward) -> Result<Uint128, ContractError> {
+    match asset {
+        Reward::Native { amount } => Ok(deps.querier.query_balance(
+            env.contract.address.to_string(),
+            amount.denom.clone(),
+        )?.amount),
+        Reward::Cw20 { contract_addr, .. } => {
+            let balance: Cw20BalanceResponse = deps.querier.query_wasm_smart(
+                contract_addr, 
+                &Cw20QueryMsg::Balance { address: env.contract.address.to_string() }
+            )?;
+            Ok(balance.balance)
+        },
+    }
+}
+
+/// load pity counters of an address for each item_type of mystery box
+fn load_pity_counters(
+    storage: &dyn Storage,
+    mystery_box: &MysteryBox,
+    address: &Addr,
+) -> Result<Vec<u32>, ContractError> {
+    let mut pity_counters: Vec<u32> = Vec::new();
+    if let Some(rate_distribution) = &mystery_box.rate_distribution {
+        for item_type in rate_distribution.vec.iter() {
+            let counter = if item_type.pity.is_some() {
+                PITY_COUNTERS.may_load(storage, (mystery_box.id, address, &item_type.name))?.unwrap_or(0)
+            } else {
+                0
+            };
+            pity_counters.push(counter);
+        }
+    }
+
+    Ok(pity_counters)
+}
+
+/// number of escrowed prizes of each item_type of mystery box
+fn load_prize_counts(storage: &dyn Storage, mystery_box: &MysteryBox) -> Result<Vec<u64>, ContractError> {
+    let mut prize_counts: Vec<u64> = Vec::new();
+    if let Some(rate_distribution) = &mystery_box.rate_distribution {
+        for item_type in rate_distribution.vec.iter() {
+            prize_counts.push(PRIZE_COUNTS.may_load(storage, (mystery_box.id, &item_type.name))?.unwrap_or(0));
+        }
+    }
+
+    Ok(prize_counts)
+}
+
+/// save pity counters of an address for item_types with pity
+fn save_pity_counters(
+    storage: &mut dyn Storage,
+    mystery_box: &MysteryBox,
+    address: &Addr,
+    pity_counters: &[u32],
+) -> Result<(), ContractError> {
+    if let Some(rate_distribution) = &mystery_box.rate_distribution {
+        for (item_type, counter) in rate_distribution.vec.iter().zip(pity_counters.iter()) {
+            if item_type.pity.is_some() {
+                PITY_COUNTERS.save(storage, (mystery_box.id, address, &item_type.name), counter)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// check randomness received from aurand contract
+/// it must contain NUMBER_OF_RANDOM numbers for each item, all between MIN_RANGE_RANDOM..MAX_RANGE_RANDOM
+/// return randomness used to choose item_type and randomness used to choose token id of each item
+fn parse_randomness(randomness: &[i32], items_per_box: u32) -> Result<Vec<(u128, usize)>, ContractError> {
+    if randomness.len() != (NUMBER_OF_RANDOM * items_per_box) as usize || 
+    randomness.iter().any(|r| *r < MIN_RANGE_RANDOM || *r > MAX_RANGE_RANDOM) {
+        return Err(ContractError::InvalidRandomness{});
+    }
+
+    Ok(randomness.chunks(NUMBER_OF_RANDOM as usize)
+        .map(|pair| (pair[0] as u128, pair[1] as usize))
+        .collect())
+}
+
+/// token id of the i-th item drawn from a box
+/// if only one item is drawn, it's the same as token id of the box
+fn derive_item_token_id(box_token_id: &str, i: usize, items_per_box: u32) -> String {
+    if items_per_box == 1 {
+        box_token_id.to_string()
+    } else {
+        format!("{}_{}", box_token_id, i)
+    }
+}
+
+fn execute_re_request_randomness(
+    deps: DepsMut,
+    info: MessageInfo,
+    env: Env,
+    token_id: String
+) -> Result<Response, ContractError> {
+    // get mystery-box event
+    let mystery_box = get_mystery_box(deps.storage)?;
+
+    // check if box with id exist
+    if !PURCHASED_BOXES.has(deps.storage, (mystery_box.id, &token_id)) {
+        return Err(ContractError::TokenNotRecognized{});
+    }
+    let PurchasedBox{
+        is_opened,
+        open_time,
+        is_received_randomness,
+        ..
+    } = PURCHASED_BOXES.load(deps.storage, (mystery_box.id, &token_id))?;
+
+    // only allow to re-request randomness if box was opened but not yet receive randomness
+    if !is_opened || is_received_randomness {
+        return Err(ContractError::InvalidCondition{});
+    }
+
+    let open_time = open_time.unwrap();
+    let block_time = env.block.time;
+
+    // only allow to re-request randomness if box was opened more than 1 hour ago
+    if block_time < open_time.plus_seconds(SECONDS_PER_HOUR) {
+        return Err(ContractError::InvalidCondition{});
+    }
+
+    // generate request id for receiving randomness
+    let request_id = token_id.clone();
+
+    // update the time randomness was requested
+    JOBS.update(deps.storage, request_id.clone(), |job| -> Result<Job, ContractError> {
+        match job {
+            Some(job) => Ok(Job{
+                requested_time: block_time,
+                ..job
+            }),
+            None => Err(ContractError::JobNotExist{}),
+        }
+    })?;
+    
+    // request randomness from aurand contract
+    let random_msg = request_randomness_msg(
+        CONFIG.load(deps.storage)?.aurand_address, 
+        request_id, 
+        mystery_box.items_per_box(),
+        info.funds
+    )?;
+
+    Ok(Response::new().add_message(random_msg)
+        .add_attribute("action", "request_randomness")
+        .add_attribute("token_id", token_id))
+}
+
+fn execute_retry_jobs(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    request_ids: Vec<String>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    // check if sender is owner of this contract
+    if config.owner != info.sender {
+        return Err(ContractError::Unauthorized{});
+    }
+
+    if request_ids.is_empty() {
+        return Err(ContractError::InvalidCondition{});
+    }
+
+    // each job is retried once, otherwise its share of funds would be paid twice
+    if request_ids.iter().enumerate().any(|(i, request_id)| request_ids[..i].contains(request_id)) {
+        return Err(ContractError::DuplicateRequestId{});
+    }
+
+    // funds are split evenly between requests
+    let number_of_requests = Uint128::from(request_ids.len() as u128);
+    let funds: Vec<Coin> = info.funds.iter()
+        .map(|coin| Coin {
+            denom: coin.denom.clone(),
+            amount: coin.amount / number_of_requests,
+        })
+        .filter(|coin| !coin.amount.is_zero())
+        .collect();
+
+    // remainder of the split is refunded to sender
+    let remainder: Vec<Coin> = info.funds.iter()
+        .map(|coin| Coin {
+            denom: coin.denom.clone(),
+            amount: coin.amount - coin.amount / number_of_requests * number_of_requests,
+        })
+        .filter(|coin| !coin.amount.is_zero())
+        .collect();
+
+    let mystery_box = get_mystery_box(deps.storage)?;
+    let block_time = env.block.time;
+    let mut random_msgs: Vec<WasmMsg> = Vec::new();
+    for request_id in request_ids.iter() {
+        // only retry jobs which are still waiting for randomness
+        let job = JOBS.update(deps.storage, request_id.clone(), |job| -> Result<Job, ContractError> {
+            match job {
+                Some(job) => Ok(Job{
+                    requested_time: block_time,
+                    ..job
+                }),
+                None => Err(ContractError::JobNotExist{}),
+            }
+        })?;
+
+        random_msgs.push(request_randomness_msg(
+            config.aurand_address.clone(), 
+            request_id.clone(), 
+            job.kind.items(&mystery_box),
+            funds.clone()
+        )?);
+    }
+
+    let mut res = Response::new().add_messages(random_msgs);
+    if !remainder.is_empty() {
+        res = res.add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: remainder,
+        });
+    }
+
+    Ok(res
+        .add_attribute("action", "retry_jobs")
+        .add_attribute("request_ids", request_ids.join(",")))
+}
+
+fn execute_cancel_job(
+    deps: DepsMut,
+    info: MessageInfo,
+    request_id: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    // check if sender is owner of this contract
+    if config.owner != info.sender {
+        return Err(ContractError::Unauthorized{});
+    }
+
+    let Job{id, sender, kind, ..} = match JOBS.may_load(deps.storage, request_id.clone())? {
+        Some(job) => job,
+        None => return Err(ContractError::JobNotExist{}),
+    };
+
+    // sender paid for the job, the payment is refunded because no item will be drawn
+    let refund = match kind {
+        JobKind::OpenBox {} => {
+            // request id of open box job is token id of the burned box
+            let purchased_box = PURCHASED_BOXES.may_load(deps.storage, (id, &request_id))?;
+            PURCHASED_BOXES.remove(deps.storage, (id, &request_id));
+            purchased_box.map(|purchased_box| purchased_box.price)
+        },
+        JobKind::Reroll { .. } => {
+            let mystery_box = match MYSTERY_BOX.may_load(deps.storage)? {
+                Some(mystery_box) if mystery_box.id == id => Some(mystery_box),
+                _ => MYSTERY_BOX_HISTORY.may_load(deps.storage, id)?,
+            };
+            mystery_box.and_then(|mystery_box| mystery_box.reroll_fee)
+        },
+        // fusion inputs are burned, nothing was paid
+        JobKind::Fuse { .. } => None,
+    }.filter(|refund| !refund.amount.is_zero());
+
+    remove_job(deps.storage, &request_id, id)?;
+
+    let mut res = Response::new();
+    if let Some(refund) = &refund {
+        res = res.add_message(BankMsg::Send {
+            to_address: sender.to_string(),
+            amount: vec![refund.clone()],
+        });
+    }
+
+    Ok(res
+        .add_attribute("action", "cancel_job")
+        .add_attribute("request_id", request_id)
+        .add_attribute("refund", refund.map(|refund| refund.to_string()).unwrap_or_else(|| "0".to_string())))
+}
+
+/// save a new job waiting for randomness and count it as pending job of its event
+fn save_job(storage: &mut dyn Storage, request_id: &str, job: &Job) -> Result<(), ContractError> {
+    JOBS.save(storage, request_id.to_string(), job)?;
+    PENDING_JOBS.update(storage, job.id, |count| -> StdResult<u64> {
+        Ok(count.unwrap_or(0) + 1)
+    })?;
+    Ok(())
+}
+
+/// remove a fulfilled job of event with specified id
+fn remove_job(storage: &mut dyn Storage, request_id: &str, id: u32) -> Result<(), ContractError> {
+    JOBS.remove(storage, request_id.to_string());
+    let count = PENDING_JOBS.may_load(storage, id)?.unwrap_or(0).saturating_sub(1);
+    if count == 0 {
+        PENDING_JOBS.remove(storage, id);
+    } else {
+        PENDING_JOBS.save(storage, id, &count)?;
+    }
+    Ok(())
+}
+
+/// create message to request randomness from aurand contract
+/// NUMBER_OF_RANDOM numbers are requested for each item of the box
+fn request_randomness_msg(
+    aurand_address: Addr,
+    request_id: String,
+    items_per_box: u32,
+    funds: Vec<Coin>,
+) -> Result<WasmMsg, ContractError> {
+    Ok(WasmMsg::Execute {
+        contract_addr: aurand_address.to_string(),
+        msg: to_json_binary(&AurandExecuteMsg::RequestIntRandomness { 
+                        request_id,
+                        num: NUMBER_OF_RANDOM * items_per_box,
+                        min: MIN_RANGE_RANDOM,
+                        max: MAX_RANGE_RANDOM,
+                    })?,
+        funds,
+    })
+}
+
+fn execute_withdraw(
+    deps: DepsMut,
+    env: Env,
     info: MessageInfo,
     amount: Coin,
     receiver: String,
@@ -793,7 +1736,9 @@ fn execute_withdraw(
 
     let receiver_addr = optional_addr_validate(deps.api, receiver)?;
 
-    // check if contract sufficient balance
+    // check if contract sufficient balance, excluding amount reserved for rewards
+    let asset = Reward::Native { amount: amount.clone() };
+    let reserved = reserved_amount(deps.storage, &asset)?;
     let contract_balance: StdResult<Coin> = deps.querier.query_balance(
         env.contract.address.to_string(),
         amount.denom.clone(),
@@ -801,7 +1746,7 @@ fn execute_withdraw(
     match contract_balance {
         Ok(balance) => {
             // if current balance smaller than required amount
-            if balance.amount < amount.amount {
+            if balance.amount.saturating_sub(reserved) < amount.amount {
                 return Err(ContractError::InsufficientAmount{});
             }
         }
@@ -823,35 +1768,979 @@ fn execute_withdraw(
             .add_attribute("receiver", receiver_addr.to_string()))
 }
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    // TODO: add query for MarketplaceInfo here
-    match msg {
-        QueryMsg::GetMysteryBoxInformation{id} => to_binary(&query_mystery_box_information(deps, id)?),
-        QueryMsg::GetBoxStatus{token_id} => to_binary(&query_box_status(deps, token_id)?),
-        QueryMsg::GetLinkedAddres{} => to_binary(&query_linked_address(deps)?),
+fn execute_withdraw_cw20(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    contract_addr: String,
+    amount: Uint128,
+    receiver: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    // check if user is owner of contract
+    if config.owner != info.sender {
+        return Err(ContractError::Unauthorized{});
     }
-}
 
-pub fn query_mystery_box_information(deps: Deps, id: Option<u32>) -> StdResult<Option<MysteryBox>> {
-    if id.is_none() {
-        return Ok(MYSTERY_BOX.may_load(deps.storage)?);
-    }else{
-        return Ok(MYSTERY_BOX_HISTORY.may_load(deps.storage, id.unwrap())?);
+    let receiver_addr = optional_addr_validate(deps.api, receiver)?;
+    let contract_addr = optional_addr_validate(deps.api, contract_addr)?;
+
+    // check if contract sufficient balance, excluding amount reserved for rewards
+    let asset = Reward::Cw20 { contract_addr: contract_addr.to_string(), amount };
+    let reserved = reserved_amount(deps.storage, &asset)?;
+    let balance = asset_balance(deps.as_ref(), &env, &asset)
+        .map_err(|_| ContractError::InsufficientAmount{})?;
+    if balance.saturating_sub(reserved) < amount {
+        return Err(ContractError::InsufficientAmount{});
     }
+
+    Ok(Response::new().add_message(reward_msg(asset, &receiver_addr)?)
+            .add_attribute("action", "withdraw_cw20")
+            .add_attribute("contract_addr", contract_addr.to_string())
+            .add_attribute("amount", amount.to_string())
+            .add_attribute("receiver", receiver_addr.to_string()))
 }
 
-pub fn query_box_status(deps: Deps, token_id: String) -> StdResult<Option<PurchasedBox>> {
-    Ok(PURCHASED_BOXES.may_load(deps.storage, token_id)?)
+fn execute_prune_history(
+    deps: DepsMut,
+    info: MessageInfo,
+    event_id: u32,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    // check if user is owner of contract
+    if config.owner != info.sender {
+        return Err(ContractError::Unauthorized{});
+    }
+
+    // purchase records of current mystery box event are still in use
+    if !MYSTERY_BOX_HISTORY.has(deps.storage, event_id) {
+        return Err(ContractError::InvalidCondition{});
+    }
+
+    let limit = limit.unwrap_or(DEFAULT_PRUNE_LIMIT).min(MAX_PRUNE_LIMIT) as usize;
+    let token_ids = PURCHASED_BOXES
+        .prefix(event_id)
+        .keys(deps.storage, None, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<String>>>()?;
+
+    for token_id in token_ids.iter() {
+        PURCHASED_BOXES.remove(deps.storage, (event_id, token_id));
+    }
+
+    Ok(Response::new().add_attribute("action", "prune_history")
+        .add_attribute("event_id", event_id.to_string())
+        .add_attribute("pruned", token_ids.len().to_string()))
 }
 
-pub fn query_linked_address(deps: Deps) -> StdResult<LinkedArress> {
+fn execute_set_box_buyback(
+    deps: DepsMut,
+    info: MessageInfo,
+    buyback: Option<BoxBuyback>,
+) -> Result<Response, ContractError> {
     let config = CONFIG.load(deps.storage)?;
 
-    Ok(LinkedArress { 
-        aurand_address: config.aurand_address, 
-        box_supplier_address: config.box_supplier, 
-        item_supplier_address: config.item_supplier,
-    })
+    // check if user is owner of contract
+    if config.owner != info.sender {
+        return Err(ContractError::Unauthorized{});
+    }
+
+    match buyback {
+        Some(buyback) => {
+            buyback.validate()?;
+            BOX_BUYBACK.save(deps.storage, &buyback)?;
+        },
+        None => BOX_BUYBACK.remove(deps.storage),
+    }
+
+    Ok(Response::new().add_attribute("action", "set_box_buyback"))
 }
 
+fn execute_set_item_buyback(
+    deps: DepsMut,
+    info: MessageInfo,
+    rarity: String,
+    payout: Option<Coin>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    // check if user is owner of contract
+    if config.owner != info.sender {
+        return Err(ContractError::Unauthorized{});
+    }
+
+    match payout {
+        Some(payout) => {
+            if payout.amount.is_zero() {
+                return Err(ContractError::InvalidBuyback{});
+            }
+            ITEM_BUYBACKS.save(deps.storage, &rarity, &payout)?;
+        },
+        None => ITEM_BUYBACKS.remove(deps.storage, &rarity),
+    }
+
+    Ok(Response::new().add_attribute("action", "set_item_buyback")
+        .add_attribute("rarity", rarity))
+}
+
+fn execute_sell_box(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    // check if box supplier is not yet linked
+    if config.box_supplier.is_none(){
+        return Err(ContractError::BoxSupplierNotLinked{});
+    }
+    let box_supplier = config.box_supplier.unwrap();
+
+    let buyback = match BOX_BUYBACK.may_load(deps.storage)? {
+        Some(buyback) => buyback,
+        None => return Err(ContractError::BuybackNotAllowed{}),
+    };
+
+    // get mystery-box event
+    let mut mystery_box = get_mystery_box(deps.storage)?;
+
+    // only unopened boxes of current mystery box event can be sold
+    let purchased_box = match PURCHASED_BOXES.may_load(deps.storage, (mystery_box.id, &token_id))? {
+        Some(purchased_box) => purchased_box,
+        None => return Err(ContractError::TokenNotRecognized{}),
+    };
+    if purchased_box.is_opened {
+        return Err(ContractError::BoxOpened{});
+    }
+
+    // check if sender owns the box
+    let owner: OwnerOfResponse = deps.querier.query_wasm_smart(
+        box_supplier.to_string(),
+        &Cw721QueryMsg::OwnerOf { 
+            token_id: token_id.clone(), 
+            include_expired: Some(false) 
+        },
+    ).map_err(|_| ContractError::TokenNotRecognized{})?;
+    if owner.owner != info.sender {
+        return Err(ContractError::Unauthorized{});
+    }
+    check_burn_approval(deps.as_ref(), &env, &box_supplier, &token_id, &info.sender)?;
+
+    let payout = buyback.payout(&purchased_box.price)?;
+    check_buyback_funds(deps.as_ref(), &env, &payout)?;
+
+    PURCHASED_BOXES.remove(deps.storage, (mystery_box.id, &token_id));
+
+    // box is back in stock while mystery box event is running
+    if !mystery_box.end.is_triggered(&env.block) {
+        mystery_box.max_minted_box += 1;
+        mystery_box.minted_box -= 1;
+        mystery_box.bought_back_box += 1;
+        MYSTERY_BOX.save(deps.storage, &mystery_box)?;
+    }
+
+    let burn_msg = WasmMsg::Execute {
+        contract_addr: box_supplier.to_string(),
+        msg: to_json_binary(&Cw721ExecuteMsg::Burn{
+            token_id: token_id.clone()
+        })?,
+        funds: vec![],
+    };
+
+    let bank_msg = BankMsg::Send { 
+        to_address: info.sender.to_string(), 
+        amount: vec![payout.clone()] 
+    };
+
+    Ok(Response::new().add_message(burn_msg)
+        .add_message(bank_msg)
+        .add_attribute("action", "sell_box")
+        .add_attribute("token_id", token_id)
+        .add_attribute("payout", payout.to_string())
+        .add_attribute("seller", info.sender))
+}
+
+fn execute_sell_item(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    // must link to a cw721 item contract
+    if config.item_supplier.is_none() {
+        return Err(ContractError::ItemSupplierNotLinked{});
+    }
+    let item_supplier = config.item_supplier.unwrap();
+
+    let nft_info: AllNftInfoResponse<Cw721RarityExtension> = deps.querier.query_wasm_smart(
+        item_supplier.to_string(),
+        &Cw721QueryMsg::AllNftInfo { 
+            token_id: token_id.clone(), 
+            include_expired: Some(false) 
+        },
+    ).map_err(|_| ContractError::TokenNotRecognized{})?;
+
+    // check if sender owns the item
+    if nft_info.access.owner != info.sender {
+        return Err(ContractError::Unauthorized{});
+    }
+    check_burn_approval(deps.as_ref(), &env, &item_supplier, &token_id, &info.sender)?;
+
+    // get payout of item's rarity
+    let rarity = nft_info.info.extension.map(|metadata| metadata.rarity).unwrap_or_default();
+    let payout = match ITEM_BUYBACKS.may_load(deps.storage, &rarity)? {
+        Some(payout) => payout,
+        None => return Err(ContractError::BuybackNotAllowed{}),
+    };
+    check_buyback_funds(deps.as_ref(), &env, &payout)?;
+
+    // burned item can not be re-rolled
+    MINTED_ITEMS.remove(deps.storage, token_id.clone());
+
+    let burn_msg = WasmMsg::Execute {
+        contract_addr: item_supplier.to_string(),
+        msg: to_json_binary(&Cw721RarityExecuteMsg::Burn{
+            token_id: token_id.clone()
+        })?,
+        funds: vec![],
+    };
+
+    let bank_msg = BankMsg::Send { 
+        to_address: info.sender.to_string(), 
+        amount: vec![payout.clone()] 
+    };
+
+    Ok(Response::new().add_message(burn_msg)
+        .add_message(bank_msg)
+        .add_attribute("action", "sell_item")
+        .add_attribute("token_id", token_id)
+        .add_attribute("rarity", rarity)
+        .add_attribute("payout", payout.to_string())
+        .add_attribute("seller", info.sender))
+}
+
+/// check if contract can pay a buyback, amount reserved for rewards can not be used
+fn check_buyback_funds(deps: Deps, env: &Env, payout: &Coin) -> Result<(), ContractError> {
+    if payout.amount.is_zero() {
+        return Err(ContractError::BuybackNotAllowed{});
+    }
+
+    let asset = Reward::Native { amount: payout.clone() };
+    let reserved = reserved_amount(deps.storage, &asset)?;
+    let balance = asset_balance(deps, env, &asset)?;
+    if balance.saturating_sub(reserved) < payout.amount {
+        return Err(ContractError::InsufficientAmount{});
+    }
+
+    Ok(())
+}
+
+/// check that owner approves this contract to burn the token, as token approval or as operator
+/// for now, we require never expired approval
+fn check_burn_approval(
+    deps: Deps,
+    env: &Env,
+    collection: &Addr,
+    token_id: &str,
+    owner: &Addr,
+) -> Result<(), ContractError> {
+    let spender = env.contract.address.to_string();
+    let approval_response: StdResult<cw721::ApprovalResponse> = deps.querier.query_wasm_smart(
+        collection.to_string(),
+        &Cw721QueryMsg::Approval {
+            token_id: token_id.to_string(),
+            spender: spender.clone(),
+            include_expired: Some(true),
+        },
+    );
+    let mut expires = approval_response.ok().map(|approval| approval.approval.expires);
+
+    // approval of a single token is not found, look for operator approval of all tokens of owner
+    let mut start_after: Option<String> = None;
+    while expires.is_none() {
+        let operators: cw721::OperatorsResponse = deps.querier.query_wasm_smart(
+            collection.to_string(),
+            &Cw721QueryMsg::AllOperators {
+                owner: owner.to_string(),
+                include_expired: Some(true),
+                start_after: start_after.clone(),
+                limit: None,
+            },
+        )?;
+        if operators.operators.is_empty() {
+            break;
+        }
+        expires = operators.operators.iter()
+            .find(|operator| operator.spender == spender)
+            .map(|operator| operator.expires);
+        start_after = operators.operators.last().map(|operator| operator.spender.clone());
+    }
+
+    // check if approval is never expired
+    match expires {
+        Some(Cw721Expiration::Never {}) => Ok(()),
+        Some(_) => Err(ContractError::Unauthorized {}),
+        None => Err(ContractError::CustomError {
+            val: "Require never expired approval".to_string(),
+        }),
+    }
+}
+
+fn execute_reroll(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    // must link to a cw721 item contract
+    if config.item_supplier.is_none() {
+        return Err(ContractError::ItemSupplierNotLinked{});
+    }
+    let item_supplier = config.item_supplier.unwrap();
+
+    // get mystery-box event
+    let mut mystery_box = get_mystery_box(deps.storage)?;
+
+    // check if mystery box expired
+    let block_time = env.block.time;
+    if mystery_box.end.is_triggered(&env.block) {
+        return Err(ContractError::MysteryBoxEnded{});
+    }
+
+    // check if re-roll is enabled for this event
+    let reroll_fee = match &mystery_box.reroll_fee {
+        Some(reroll_fee) => reroll_fee.clone(),
+        None => return Err(ContractError::RerollNotAllowed{}),
+    };
+
+    // check if item was minted from current event
+    let minted_item = match MINTED_ITEMS.may_load(deps.storage, token_id.clone())? {
+        Some(minted_item) if minted_item.id == mystery_box.id => minted_item,
+        _ => return Err(ContractError::RerollNotAllowed{}),
+    };
+
+    // reward paid with the item can not be taken back, so such item can not be re-rolled
+    let has_reward = mystery_box.rate_distribution.as_ref()
+        .and_then(|rate_distribution| rate_distribution.vec.iter().find(|t| t.name == minted_item.item_type))
+        .is_some_and(|item_type| item_type.reward.is_some());
+    if has_reward {
+        return Err(ContractError::RerollNotAllowed{});
+    }
+
+    // check if sender paid reroll fee
+    let sent_amount = info.funds.iter()
+        .find(|fund| fund.denom == reroll_fee.denom)
+        .map_or(Uint128::zero(), |fund| fund.amount);
+    if sent_amount < reroll_fee.amount {
+        return Err(ContractError::CustomError{
+            val: String::from("Insufficient fee! required ")
+            + &reroll_fee.amount.to_string() + &reroll_fee.denom});
+    }
+
+    // check if sender owns the item
+    let owner: OwnerOfResponse = deps.querier.query_wasm_smart(
+        item_supplier.to_string(),
+        &Cw721QueryMsg::OwnerOf { 
+            token_id: token_id.clone(), 
+            include_expired: Some(false) 
+        },
+    ).map_err(|_| ContractError::TokenNotRecognized{})?;
+    if owner.owner != info.sender {
+        return Err(ContractError::Unauthorized{});
+    }
+    check_burn_approval(deps.as_ref(), &env, &item_supplier, &token_id, &info.sender)?;
+
+    // burned item goes back to mystery box
+    let mut swaps = PoolSwaps::new(deps.storage, mystery_box.id);
+    mystery_box.return_item(&mut swaps, &minted_item.item_type, minted_item.token_index)?;
+    swaps.into_changes().save(deps.storage)?;
+    MYSTERY_BOX.save(deps.storage, &mystery_box)?;
+    MINTED_ITEMS.remove(deps.storage, token_id.clone());
+
+    let burn_msg = WasmMsg::Execute {
+        contract_addr: item_supplier.to_string(),
+        msg: to_json_binary(&Cw721RarityExecuteMsg::Burn{
+            token_id: token_id.clone()
+        })?,
+        funds: vec![],
+    };
+
+    // token id of the new item is generated from contract address, block time and burned token id
+    let request_id = format!("{}_{}", mystery_box.id, make_id(vec![
+        env.contract.address.to_string(),
+        block_time.to_string(),
+        token_id.clone(),
+    ]));
+
+    save_job(deps.storage, &request_id, &Job{
+        id: mystery_box.id,
+        sender: info.sender,
+        created_time: block_time,
+        requested_time: block_time,
+        kind: JobKind::Reroll { burned_token_id: token_id.clone() },
+    })?;
+
+    // remaining funds are used to pay for randomness
+    let funds: Vec<Coin> = info.funds.iter()
+        .map(|fund| if fund.denom == reroll_fee.denom {
+            Coin { denom: fund.denom.clone(), amount: fund.amount - reroll_fee.amount }
+        } else {
+            fund.clone()
+        })
+        .filter(|fund| !fund.amount.is_zero())
+        .collect();
+
+    let random_msg = request_randomness_msg(
+        config.aurand_address, 
+        request_id.clone(), 
+        1u32,
+        funds
+    )?;
+
+    Ok(Response::new().add_message(burn_msg)
+        .add_message(random_msg)
+        .add_attribute("action", "reroll")
+        .add_attribute("burned_token_id", token_id)
+        .add_attribute("item_type", minted_item.item_type)
+        .add_attribute("token_id", request_id))
+}
+
+fn execute_set_fusion_recipe(
+    deps: DepsMut,
+    info: MessageInfo,
+    rarity: String,
+    recipe: Option<FusionRecipe>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    // check if user is owner of contract
+    if config.owner != info.sender {
+        return Err(ContractError::Unauthorized{});
+    }
+
+    match recipe {
+        Some(recipe) => {
+            recipe.validate()?;
+            FUSION_RECIPES.save(deps.storage, &rarity, &recipe)?;
+        },
+        None => FUSION_RECIPES.remove(deps.storage, &rarity),
+    }
+
+    Ok(Response::new().add_attribute("action", "set_fusion_recipe")
+        .add_attribute("rarity", rarity))
+}
+
+fn execute_fuse(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_ids: Vec<String>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    // must link to a cw721 item contract
+    if config.item_supplier.is_none() {
+        return Err(ContractError::ItemSupplierNotLinked{});
+    }
+    let item_supplier = config.item_supplier.unwrap();
+
+    // token ids must not be duplicated
+    let mut sorted_token_ids = token_ids.clone();
+    sorted_token_ids.sort();
+    sorted_token_ids.dedup();
+    if token_ids.is_empty() || sorted_token_ids.len() != token_ids.len() {
+        return Err(ContractError::InvalidCondition{});
+    }
+
+    // all items must be owned by sender and have the same rarity
+    let mut rarity: Option<String> = None;
+    let mut burn_msgs: Vec<WasmMsg> = Vec::new();
+    for token_id in token_ids.iter() {
+        let nft_info: AllNftInfoResponse<Cw721RarityExtension> = deps.querier.query_wasm_smart(
+            item_supplier.to_string(),
+            &Cw721QueryMsg::AllNftInfo { 
+                token_id: token_id.clone(), 
+                include_expired: Some(false) 
+            },
+        ).map_err(|_| ContractError::TokenNotRecognized{})?;
+
+        if nft_info.access.owner != info.sender {
+            return Err(ContractError::Unauthorized{});
+        }
+        check_burn_approval(deps.as_ref(), &env, &item_supplier, token_id, &info.sender)?;
+
+        let item_rarity = nft_info.info.extension.map(|metadata| metadata.rarity).unwrap_or_default();
+        match &rarity {
+            Some(r) if r != &item_rarity => return Err(ContractError::InvalidCondition{}),
+            _ => rarity = Some(item_rarity),
+        }
+
+        burn_msgs.push(WasmMsg::Execute {
+            contract_addr: item_supplier.to_string(),
+            msg: to_json_binary(&Cw721RarityExecuteMsg::Burn{
+                token_id: token_id.clone()
+            })?,
+            funds: vec![],
+        });
+    }
+    let rarity = rarity.unwrap_or_default();
+
+    // number of items must match fusion recipe of the rarity
+    let recipe = match FUSION_RECIPES.may_load(deps.storage, &rarity)? {
+        Some(recipe) => recipe,
+        None => return Err(ContractError::FusionRecipeNotFound{}),
+    };
+    if recipe.input_count as usize != token_ids.len() {
+        return Err(ContractError::InvalidCondition{});
+    }
+
+    // token id of fused item is generated from contract address, block time and burned token ids
+    let token_id = format!("fuse_{}", make_id(vec![
+        env.contract.address.to_string(),
+        env.block.time.to_string(),
+        token_ids.join(","),
+    ]));
+
+    let res = Response::new().add_messages(burn_msgs)
+        .add_attribute("action", "fuse")
+        .add_attribute("rarity", rarity)
+        .add_attribute("token_id", token_id.clone())
+        .add_attribute("burned_token_ids", token_ids.join(","));
+
+    // only one output, mint fused item immediately
+    // fused item takes no token uri, so nothing is chosen at random
+    if recipe.outputs.len() == 1 {
+        let mint_msg = fusion_mint_msg(deps.storage, &item_supplier, &info.sender, token_id, &recipe.outputs[0].rarity)?;
+        return Ok(res.add_message(mint_msg));
+    }
+
+    // otherwise output is chosen by randomness
+    // fused item is made from item types of current event, so job can only fail if randomness is invalid
+    let block_time = env.block.time;
+    let mystery_box = get_mystery_box(deps.storage)?;
+    if mystery_box.rate_distribution.is_none() {
+        return Err(ContractError::MysteryBoxNotUpdated{});
+    }
+    save_job(deps.storage, &token_id, &Job{
+        id: mystery_box.id,
+        sender: info.sender,
+        created_time: block_time,
+        requested_time: block_time,
+        kind: JobKind::Fuse { outputs: recipe.outputs },
+    })?;
+
+    Ok(res.add_message(request_randomness_msg(
+        config.aurand_address, 
+        token_id, 
+        1u32,
+        info.funds
+    )?))
+}
+
+fn execute_set_prize_collection(
+    deps: DepsMut,
+    info: MessageInfo,
+    contract_addr: String,
+    allowed: bool,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    // check if user is owner of contract
+    if config.owner != info.sender {
+        return Err(ContractError::Unauthorized{});
+    }
+
+    let contract_addr = optional_addr_validate(deps.api, contract_addr)?;
+
+    // box NFTs can not be used as prizes
+    if allowed && config.box_supplier == Some(contract_addr.clone()) {
+        return Err(ContractError::PrizeCollectionNotAllowed{});
+    }
+
+    if allowed {
+        PRIZE_COLLECTIONS.save(deps.storage, &contract_addr, &true)?;
+    } else {
+        PRIZE_COLLECTIONS.remove(deps.storage, &contract_addr);
+    }
+
+    Ok(Response::new().add_attribute("action", "set_prize_collection")
+        .add_attribute("contract_addr", contract_addr)
+        .add_attribute("allowed", allowed.to_string()))
+}
+
+fn execute_receive_nft(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: Cw721ReceiveMsg,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    // only owner of contract can deposit prizes
+    if config.owner != msg.sender {
+        return Err(ContractError::Unauthorized{});
+    }
+
+    // sender of ReceiveNft is the cw721 contract, only allowed collections can be used for prizes
+    // otherwise any contract could deposit fake NFTs in the name of owner
+    if config.box_supplier == Some(info.sender.clone()) || !PRIZE_COLLECTIONS.has(deps.storage, &info.sender) {
+        return Err(ContractError::PrizeCollectionNotAllowed{});
+    }
+
+    match from_json(&msg.msg)? {
+        ReceiveNftMsg::DepositPrize { item_type } => {
+            // get mystery-box event
+            let mystery_box = get_mystery_box(deps.storage)?;
+
+            // prizes can only be deposited before mystery box ends
+            if mystery_box.end.is_triggered(&env.block) {
+                return Err(ContractError::MysteryBoxEnded{});
+            }
+
+            // item_type must exist in rate distribution of mystery box
+            match &mystery_box.rate_distribution {
+                Some(rate_distribution) => {
+                    if !rate_distribution.vec.iter().any(|t| t.name == item_type) {
+                        return Err(ContractError::ItemTypeNotFound{});
+                    }
+                },
+                None => return Err(ContractError::MysteryBoxNotUpdated{}),
+            }
+
+            // add prize to the end of item_type's prizes
+            let count = PRIZE_COUNTS.may_load(deps.storage, (mystery_box.id, &item_type))?.unwrap_or(0);
+            PRIZES.save(deps.storage, (mystery_box.id, &item_type, count), &Prize {
+                contract: info.sender.clone(),
+                token_id: msg.token_id.clone(),
+            })?;
+            PRIZE_COUNTS.save(deps.storage, (mystery_box.id, &item_type), &(count + 1))?;
+
+            Ok(Response::new().add_attribute("action", "deposit_prize")
+                .add_attribute("id", mystery_box.id.to_string())
+                .add_attribute("item_type", item_type)
+                .add_attribute("contract", info.sender)
+                .add_attribute("token_id", msg.token_id))
+        },
+    }
+}
+
+fn execute_withdraw_prizes(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: u32,
+    item_type: String,
+    limit: Option<u32>,
+    receiver: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    // check if user is owner of contract
+    if config.owner != info.sender {
+        return Err(ContractError::Unauthorized{});
+    }
+
+    let receiver_addr = optional_addr_validate(deps.api, receiver)?;
+
+    // prizes of current mystery box event can only be withdrawn after it ended
+    let mystery_box = get_mystery_box(deps.storage)?;
+    if mystery_box.id == id && !mystery_box.end.is_triggered(&env.block) {
+        return Err(ContractError::MysteryBoxNotEnded{});
+    }
+
+    // pending open jobs of the event may still be given a prize
+    if PENDING_JOBS.may_load(deps.storage, id)?.unwrap_or(0) > 0 {
+        return Err(ContractError::JobsPending{});
+    }
+
+    // withdraw prizes from the end, so indexes stay continuous
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as u64;
+    let count = PRIZE_COUNTS.may_load(deps.storage, (id, &item_type))?.unwrap_or(0);
+    let remain = count.saturating_sub(limit);
+
+    let mut transfer_msgs: Vec<WasmMsg> = Vec::new();
+    for index in remain..count {
+        let prize = PRIZES.load(deps.storage, (id, &item_type, index))?;
+        PRIZES.remove(deps.storage, (id, &item_type, index));
+
+        transfer_msgs.push(WasmMsg::Execute {
+            contract_addr: prize.contract.to_string(),
+            msg: to_json_binary(&Cw721BaseExecuteMsg::TransferNft {
+                recipient: receiver_addr.to_string(),
+                token_id: prize.token_id,
+            })?,
+            funds: vec![],
+        });
+    }
+    PRIZE_COUNTS.save(deps.storage, (id, &item_type), &remain)?;
+
+    Ok(Response::new().add_messages(transfer_msgs)
+            .add_attribute("action", "withdraw_prizes")
+            .add_attribute("id", id.to_string())
+            .add_attribute("item_type", item_type)
+            .add_attribute("amount", (count - remain).to_string())
+            .add_attribute("receiver", receiver_addr.to_string()))
+}
+
+/// choose an escrowed prize of item_type using random number
+fn pick_prize(
+    storage: &dyn Storage,
+    id: u32,
+    item_type: &str,
+    random_number: u128,
+) -> Result<Option<(u64, Prize)>, ContractError> {
+    let count = PRIZE_COUNTS.may_load(storage, (id, item_type))?.unwrap_or(0);
+    if count == 0 {
+        return Ok(None);
+    }
+
+    let index = (random_number % count as u128) as u64;
+    Ok(Some((index, PRIZES.load(storage, (id, item_type, index))?)))
+}
+
+/// remove escrowed prize at index, the last prize takes its place
+fn remove_prize(
+    storage: &mut dyn Storage,
+    id: u32,
+    item_type: &str,
+    index: u64,
+) -> Result<(), ContractError> {
+    let last_index = PRIZE_COUNTS.load(storage, (id, item_type))? - 1;
+    if index != last_index {
+        let last_prize = PRIZES.load(storage, (id, item_type, last_index))?;
+        PRIZES.save(storage, (id, item_type, index), &last_prize)?;
+    }
+    PRIZES.remove(storage, (id, item_type, last_index));
+    PRIZE_COUNTS.save(storage, (id, item_type), &last_index)?;
+
+    Ok(())
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    // TODO: add query for MarketplaceInfo here
+    match msg {
+        QueryMsg::GetMysteryBoxInformation{id} => to_json_binary(&query_mystery_box_information(deps, id)?),
+        QueryMsg::GetBoxStatus{token_id, id} => to_json_binary(&query_box_status(deps, token_id, id)?),
+        QueryMsg::GetLinkedAddres{} => to_json_binary(&query_linked_address(deps)?),
+        QueryMsg::SimulateOpen{randomness, address} => to_json_binary(&query_simulate_open(deps, randomness, address)?),
+        QueryMsg::ListPendingJobs{start_after, limit} => to_json_binary(&query_pending_jobs(deps, env, start_after, limit)?),
+        QueryMsg::GetPityCounter{address} => to_json_binary(&query_pity_counter(deps, address)?),
+        QueryMsg::ListPrizes{id, item_type, start_after, limit} => to_json_binary(&query_prizes(deps, id, item_type, start_after, limit)?),
+        QueryMsg::ListPrizeCollections{start_after, limit} => to_json_binary(&query_prize_collections(deps, start_after, limit)?),
+        QueryMsg::GetFusionRecipe{rarity} => to_json_binary(&FUSION_RECIPES.may_load(deps.storage, &rarity)?),
+        QueryMsg::GetBoxBuyback{} => to_json_binary(&BOX_BUYBACK.may_load(deps.storage)?),
+        QueryMsg::GetItemBuyback{rarity} => to_json_binary(&ITEM_BUYBACKS.may_load(deps.storage, &rarity)?),
+    }
+}
+
+pub fn query_mystery_box_information(deps: Deps, id: Option<u32>) -> StdResult<Option<MysteryBox>> {
+    match id {
+        None => MYSTERY_BOX.may_load(deps.storage),
+        Some(id) => MYSTERY_BOX_HISTORY.may_load(deps.storage, id),
+    }
+}
+
+pub fn query_box_status(deps: Deps, token_id: String, id: Option<u32>) -> StdResult<Option<PurchasedBox>> {
+    let id = match id {
+        Some(id) => id,
+        None => match MYSTERY_BOX.may_load(deps.storage)? {
+            Some(mystery_box) => mystery_box.id,
+            None => return Ok(None),
+        },
+    };
+
+    PURCHASED_BOXES.may_load(deps.storage, (id, &token_id))
+}
+
+pub fn query_linked_address(deps: Deps) -> StdResult<LinkedArress> {
+    let config = CONFIG.load(deps.storage)?;
+
+    Ok(LinkedArress { 
+        aurand_address: config.aurand_address, 
+        box_supplier_address: config.box_supplier, 
+        item_supplier_address: config.item_supplier,
+    })
+}
+
+
+/// simulate opening a box with given randomness, state of mystery box is not changed
+pub fn query_simulate_open(deps: Deps, randomness: Vec<i32>, address: Option<String>) -> StdResult<SimulateOpenResponse> {
+    let mut mystery_box = get_mystery_box(deps.storage)
+        .map_err(|e| StdError::generic_err(e.to_string()))?;
+
+    let randomness = parse_randomness(&randomness, mystery_box.items_per_box())
+        .map_err(|e| StdError::generic_err(e.to_string()))?;
+
+    // apply pity counters of address if set
+    let mut pity_counters = match address {
+        Some(address) => {
+            let address = deps.api.addr_validate(&address)?;
+            load_pity_counters(deps.storage, &mystery_box, &address)
+                .map_err(|e| StdError::generic_err(e.to_string()))?
+        },
+        None => vec![0; mystery_box.rate_distribution.as_ref().map_or(0, |d| d.vec.len())],
+    };
+    let mut prize_counts = load_prize_counts(deps.storage, &mystery_box)
+        .map_err(|e| StdError::generic_err(e.to_string()))?;
+
+    // changes of uri pools are discarded
+    let mut swaps = PoolSwaps::new(deps.storage, mystery_box.id);
+    let draws = mystery_box.draw_bundle(&mut swaps, &randomness, MAX_RANGE_RANDOM as u128, &mut pity_counters, &mut prize_counts)
+        .map_err(|e| StdError::generic_err(e.to_string()))?;
+
+    let mut items: Vec<SimulatedItem> = Vec::new();
+    for (draw, (random_type, random_index)) in draws.into_iter().zip(randomness) {
+        let Draw{
+            item_type,
+            purity,
+            token_index: _,
+            token_uri,
+            reward,
+            mint_item,
+            prize: is_prize,
+            ..
+        } = draw;
+
+        // escrowed prize would be transferred instead of minting
+        // prize is not removed in simulation, so items of a bundle may show the same prize
+        let prize = pick_prize(
+            deps.storage, 
+            mystery_box.id, 
+            &item_type, 
+            random_u128(&[random_type, random_index as u128])
+        ).map_err(|e| StdError::generic_err(e.to_string()))?
+        .map(|(_, prize)| prize)
+        .filter(|_| is_prize);
+
+        items.push(SimulatedItem {
+            item_type,
+            purity,
+            token_uri,
+            prize,
+            reward,
+            mint_item,
+        });
+    }
+
+    // rates of all item_types after the draw
+    let rates = mystery_box.rate_distribution
+        .map(|rate_distribution| rate_distribution.vec)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|item_type| ItemTypeRate{
+            name: item_type.name,
+            rate: item_type.rate,
+            supply: item_type.supply,
+        })
+        .collect();
+
+    Ok(SimulateOpenResponse {
+        items,
+        rates,
+    })
+}
+
+/// list jobs which are waiting for randomness, ordered by request id
+pub fn query_pending_jobs(
+    deps: Deps, 
+    env: Env, 
+    start_after: Option<String>, 
+    limit: Option<u32>
+) -> StdResult<PendingJobsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let block_time = env.block.time;
+
+    let jobs = JOBS.range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (request_id, job) = item?;
+            Ok(PendingJob {
+                request_id,
+                id: job.id,
+                sender: job.sender,
+                created_time: job.created_time,
+                requested_time: job.requested_time,
+                age: block_time.seconds().saturating_sub(job.created_time.seconds()),
+                kind: job.kind,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(PendingJobsResponse { jobs })
+}
+
+/// pity counters of an address for item_types with pity in current mystery box event
+pub fn query_pity_counter(deps: Deps, address: String) -> StdResult<PityCounterResponse> {
+    let address = deps.api.addr_validate(&address)?;
+
+    let mut counters: Vec<PityCount> = Vec::new();
+    if let Some(mystery_box) = MYSTERY_BOX.may_load(deps.storage)? {
+        if let Some(rate_distribution) = mystery_box.rate_distribution {
+            for item_type in rate_distribution.vec.into_iter() {
+                if let Some(pity) = item_type.pity {
+                    let counter = PITY_COUNTERS.may_load(
+                        deps.storage, 
+                        (mystery_box.id, &address, &item_type.name)
+                    )?.unwrap_or(0);
+
+                    counters.push(PityCount {
+                        item_type: item_type.name,
+                        counter,
+                        pity,
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(PityCounterResponse { counters })
+}
+
+/// escrowed prizes of an item_type in mystery box event with id
+pub fn query_prizes(
+    deps: Deps,
+    id: u32,
+    item_type: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<PrizesResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let count = PRIZE_COUNTS.may_load(deps.storage, (id, &item_type))?.unwrap_or(0);
+    let prizes = PRIZES.prefix((id, &item_type))
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, prize)| prize))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(PrizesResponse { count, prizes })
+}
+
+/// list cw721 contracts allowed to be used for prizes, ordered by address
+pub fn query_prize_collections(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<PrizeCollectionsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start_after = start_after.map(Addr::unchecked);
+    let start = start_after.as_ref().map(Bound::exclusive);
+
+    let collections = PRIZE_COLLECTIONS
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(PrizeCollectionsResponse { collections })
+}
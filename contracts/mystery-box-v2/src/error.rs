@@ -1,7 +1,7 @@
 use cosmwasm_std::StdError;
 use thiserror::Error;
 
-#[derive(Error, Debug)]
+#[derive(Error, Debug, PartialEq)]
 pub enum ContractError {
     #[error("{0}")]
     Std(#[from] StdError),
@@ -77,6 +77,111 @@ pub enum ContractError {
 
     #[error("InvalidCondition")]
     InvalidCondition{},
+
+    #[error("InvalidRateCurve")]
+    InvalidRateCurve{},
+
+    #[error("InvalidDeckSize")]
+    InvalidDeckSize{},
+
+    #[error("InvalidPity")]
+    InvalidPity{},
+
+    #[error("InvalidUriPool")]
+    InvalidUriPool{},
+
+    #[error("ItemTypeNotFound")]
+    ItemTypeNotFound{},
+
+    #[error("MysteryBoxNotEnded")]
+    MysteryBoxNotEnded{},
+
+    #[error("InvalidReward")]
+    InvalidReward{},
+
+    #[error("InsufficientEscrow")]
+    InsufficientEscrow{},
+
+    #[error("InvalidItemsPerBox")]
+    InvalidItemsPerBox{},
+
+    #[error("InvalidFusionRecipe")]
+    InvalidFusionRecipe{},
+
+    #[error("FusionRecipeNotFound")]
+    FusionRecipeNotFound{},
+
+    #[error("RerollNotAllowed")]
+    RerollNotAllowed{},
+
+    #[error("InvalidBuyback")]
+    InvalidBuyback{},
+
+    #[error("BuybackNotAllowed")]
+    BuybackNotAllowed{},
+
+    #[error("InvalidRoyalty")]
+    InvalidRoyalty{},
+    
+    #[error("InvalidName")]
+    InvalidName{},
+
+    #[error("DuplicateItemType")]
+    DuplicateItemType{},
+
+    #[error("InvalidRate")]
+    InvalidRate{},
+
+    #[error("InvalidTotalRate")]
+    InvalidTotalRate{},
+
+    #[error("InvalidItemTypeSupply")]
+    InvalidItemTypeSupply{},
+
+    #[error("InvalidDateFormat")]
+    InvalidDateFormat{},
+
+    #[error("InvalidTimeRange")]
+    InvalidTimeRange{},
+
+    #[error("InvalidTotalSupply")]
+    InvalidTotalSupply{},
+
+    #[error("InvalidBoxSupply")]
+    InvalidBoxSupply{},
+
+    #[error("InsufficientUriSupply")]
+    InsufficientUriSupply{},
+
+    #[error("InvalidPrice")]
+    InvalidPrice{},
+
+    #[error("FieldLocked: {field}")]
+    FieldLocked { field: String },
+
+    #[error("InvalidProvenance")]
+    InvalidProvenance{},
+
+    #[error("RevealNotAllowed")]
+    RevealNotAllowed{},
+
+    #[error("MysteryBoxNotRevealed")]
+    MysteryBoxNotRevealed{},
+
+    #[error("DuplicateRequestId")]
+    DuplicateRequestId{},
+
+    #[error("JobsPending")]
+    JobsPending{},
+
+    #[error("EventNotCurrent")]
+    EventNotCurrent{},
+
+    #[error("PrizeCollectionNotAllowed")]
+    PrizeCollectionNotAllowed{},
+
+    #[error("ItemsFrozen")]
+    ItemsFrozen{},
     
     #[error("Custom Error val: {val:?}")]
     CustomError { val: String },
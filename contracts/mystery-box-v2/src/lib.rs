@@ -1,7 +1,11 @@
 pub mod contract;
 mod error;
-mod utils;
+pub mod utils;
+mod validation;
 pub mod msg;
 pub mod state;
 
 pub use crate::error::ContractError;
+
+#[cfg(test)]
+mod testing;
@@ -1,6 +1,8 @@
 use cosmwasm_schema::{cw_serde,QueryResponses};
-use cosmwasm_std::{Coin,Decimal, Addr};
-use crate::state::{MysteryBox, PurchasedBox};
+use cosmwasm_std::{Coin,Decimal, Addr, Timestamp, Uint128};
+use cw721::Cw721ReceiveMsg;
+use cw721_rarity::Trait;
+use crate::state::{MysteryBox, PurchasedBox, RateCurve, DrawMode, Prize, Reward, FusionRecipe, JobKind, BoxBuyback, MetadataTemplate};
 
 #[cw_serde]
 pub struct InstantiateMsg {
@@ -46,10 +48,22 @@ pub enum ExecuteMsg {
         box_info: BoxInfo
     },
 
-    /// update prefix uri for mystery box
+    /// update mystery box event, only fields which are set are changed
     UpdateMysteryBox {
+        box_update: BoxUpdate
+    },
+
+    /// set prefix uri of mystery box in delayed reveal mode, hex sha256 of prefix_uri must be provenance_hash
+    /// items minted with placeholder uri are updated a batch at a time, call again until all are revealed
+    Reveal {
         prefix_uri: String,
-        rate_distribution: RateDistributionMsg
+        provenance_hash: String,
+    },
+
+    /// freeze metadata of an item, or of all items if token_id is not set
+    /// metadata of items can not be updated anymore, not even by reveal
+    FreezeItemMetadata {
+        token_id: Option<String>,
     },
 
     // update source of randomness
@@ -68,11 +82,105 @@ pub enum ExecuteMsg {
         token_id: String,
     },
 
-    /// withdraw coin
+    /// re-request randomness for stuck jobs, only owner of contract
+    /// funds are split evenly between requests
+    RetryJobs {
+        request_ids: Vec<String>,
+    },
+
+    /// cancel a stuck job which can not be fulfilled, only owner of contract
+    /// price of opened box or reroll fee is refunded to sender of the job
+    CancelJob {
+        request_id: String,
+    },
+
+    /// withdraw coin, amount reserved for rewards of current mystery box can not be withdrawn
     Withdraw{
         amount: Coin,
         receiver: String,
     },
+
+    /// remove up to limit purchase records of an old mystery box event, only owner of contract
+    PruneHistory {
+        event_id: u32,
+        limit: Option<u32>,
+    },
+
+    /// set price at which contract buys back unopened boxes, disable box buyback if not set
+    SetBoxBuyback {
+        buyback: Option<BoxBuyback>,
+    },
+
+    /// set payout for selling items of a rarity back to contract, disable it if not set
+    SetItemBuyback {
+        rarity: String,
+        payout: Option<Coin>,
+    },
+
+    /// sell an unopened box of current mystery box event back to contract, the box is burned
+    /// this contract must be approved to burn the box
+    SellBox {
+        token_id: String,
+    },
+
+    /// sell an item back to contract at payout of its rarity, the item is burned
+    /// this contract must be approved to burn the item
+    SellItem {
+        token_id: String,
+    },
+
+    /// pay reroll fee to burn an item minted from current mystery box event and draw again
+    /// this contract must be approved to burn the item
+    /// pity counters of sender are not used or changed by the new draw
+    Reroll {
+        token_id: String,
+    },
+
+    /// burn items of the same rarity and mint one item following fusion recipe of the rarity
+    /// this contract must be approved to burn the items
+    Fuse {
+        token_ids: Vec<String>,
+    },
+
+    /// set fusion recipe for items of a rarity, remove the recipe if not set
+    SetFusionRecipe {
+        rarity: String,
+        recipe: Option<FusionRecipe>,
+    },
+
+    /// allow or disallow a cw721 contract to be used for prizes
+    SetPrizeCollection {
+        contract_addr: String,
+        allowed: bool,
+    },
+
+    /// receive NFT from an allowed prize collection, used by owner to deposit prizes
+    ReceiveNft(Cw721ReceiveMsg),
+
+    /// withdraw cw20 token, amount reserved for rewards of current mystery box can not be withdrawn
+    WithdrawCw20 {
+        contract_addr: String,
+        amount: Uint128,
+        receiver: String,
+    },
+
+    /// withdraw undistributed prizes of an item type once mystery box event has ended
+    WithdrawPrizes {
+        id: u32,
+        item_type: String,
+        limit: Option<u32>,
+        receiver: String,
+    },
+}
+
+/// message sent along with NFT in ReceiveNft
+#[cw_serde]
+pub enum ReceiveNftMsg {
+    /// deposit NFT as a prize of item type of current mystery box event
+    /// when a box is opened and this item type is drawn, an escrowed prize is transferred instead of minting
+    DepositPrize {
+        item_type: String,
+    },
 }
 
 #[cw_serde]
@@ -81,9 +189,9 @@ pub struct BoxInfo {
 
     pub description: String, // some information about mystery box event
 
-    pub start_time: String, // utc time format "YY-MM-DD hh:mm:ssZ"
+    pub start: EventTime, // event starts when this time or block height is reached
 
-    pub end_time: String, // it's required that start_time < end_time
+    pub end: EventTime, // if start and end are both times or both heights, it's required that start < end
 
     pub total_supply: u64, // number of unique uri
 
@@ -97,7 +205,58 @@ pub struct BoxInfo {
     pub max_minted_box: Option<u64>,
 
 		// price of one box
-    pub price: Coin
+    pub price: Coin,
+
+    // fee to burn an item minted from this event and draw again
+    // if not set, items can not be re-rolled
+    pub reroll_fee: Option<Coin>,
+
+    // cw2981 royalty set on boxes and items minted from this event
+    // both must be set to enable royalties, royalty_percentage is between 0 and 100
+    pub royalty_payment_address: Option<String>,
+    pub royalty_percentage: Option<u64>,
+
+    // templates of name and description of minted items (e.g '{event_name} #{n}')
+    pub item_metadata: Option<MetadataTemplate>,
+
+    // image uri of box NFTs
+    pub box_image: Option<String>,
+
+    // delayed reveal: items are minted with placeholder_uri until prefix uri is revealed
+    // provenance_hash is hex sha256 of the prefix uri, both must be set to enable delayed reveal
+    pub placeholder_uri: Option<String>,
+    pub provenance_hash: Option<String>,
+}
+
+/// changes of a mystery box event
+/// name, description, start, end, price and max_minted_box are locked once the first box is sold
+/// prefix_uri and rate_distribution are locked once the first box is opened
+#[cw_serde]
+#[derive(Default)]
+pub struct BoxUpdate {
+    pub name: Option<String>,
+
+    pub description: Option<String>,
+
+    pub start: Option<EventTime>,
+
+    pub end: Option<EventTime>,
+
+    pub price: Option<Coin>,
+
+    pub max_minted_box: Option<u64>, // in deck mode rate distribution must be updated too, deck size depends on it
+
+    pub prefix_uri: Option<String>, // both prefix_uri and rate_distribution are required to open boxes
+
+    pub rate_distribution: Option<RateDistributionMsg>,
+}
+
+/// time of a mystery box event, stored as cw_utils::Scheduled
+#[cw_serde]
+pub enum EventTime {
+    AtHeight(u64), // block height
+    AtTime(Timestamp), // nanoseconds since unix epoch
+    Rfc3339(String), // e.g. "2023-01-01T00:00:00Z", converted to AtTime
 }
 
 #[cw_serde]
@@ -113,6 +272,29 @@ pub struct ItemTypeMsg {
     pub slip_rate: u32, 
   
     pub supply: u32, // maximum number of item that can be minted
+
+    // if set to N, a wallet which opened N-1 boxes in a row without getting an item of this type or rarer
+    // is guaranteed to get this type on the Nth open
+    pub pity: Option<u32>,
+
+    // uri pool of this item type, both must be set or not set
+    // if set, uri of items of this type are made from prefix_uri and 0..total_supply
+    // and replacement is applied to this pool, the item type can not be drawn when its pool runs out
+    // if not set, the item type uses prefix_uri and total_supply of mystery box
+    pub prefix_uri: Option<String>,
+    pub total_supply: Option<u64>,
+
+    // native coin or cw20 paid to opener when this item type is drawn (e.g 'jackpot: 1000 AURA')
+    // contract must hold enough to pay reward for the whole supply when rate distribution is set
+    pub reward: Option<Reward>,
+
+    // if set to false, only reward is paid and no item is minted
+    // if not set, it's will be true
+    pub mint_item: Option<bool>,
+
+    // image and traits set on metadata of minted items of this type
+    pub image: Option<String>,
+    pub attributes: Option<Vec<Trait>>,
 }
 
 #[cw_serde]
@@ -123,7 +305,23 @@ pub struct RateDistributionMsg {
     // if not set, it's will be 'common'
     // it's rate equal to one minus the sum of the rates of all of the above and not apply rate_modifier
     // its supply is almost limitless (u64::MAX)
-    pub default_type: Option<String>
+    pub default_type: Option<String>,
+
+    // curve used to reduce rate of item types when their supply decreases
+    // if not set, it's will be sigmoid with e = 1.5, exponent = 2, max_exponent = 116
+    pub rate_curve: Option<RateCurve>,
+
+    // if set to 'deck', supply of each item type is its exact count and rate is ignored
+    // default type gets the remaining slots, so max_minted_box must be set
+    // if not set, it's will be 'rate'
+    pub mode: Option<DrawMode>,
+
+    // number of items drawn when a box is opened (1..=10), in deck mode deck size is number of boxes * items_per_box
+    // if not set, it's will be 1
+    pub items_per_box: Option<u32>,
+
+    // if set, at least one item of each box is this type or rarer
+    pub guaranteed_type: Option<String>,
 }
 
 #[cw_serde]
@@ -142,11 +340,45 @@ pub enum QueryMsg {
     #[returns(Option<MysteryBox>)]
     GetMysteryBoxInformation {id: Option<u32>},
 
+    /// status of a box bought in mystery box event with id, current event if not set
     #[returns(Option<PurchasedBox>)]
-    GetBoxStatus {token_id: String},
+    GetBoxStatus {token_id: String, id: Option<u32>},
     
     #[returns(LinkedArress)]
     GetLinkedAddres {},
+
+    /// preview the outcome of opening a box with given aurand randomness
+    /// if address is set, pity counters of the address are applied
+    #[returns(SimulateOpenResponse)]
+    SimulateOpen {randomness: Vec<i32>, address: Option<String>},
+
+    /// list jobs which are waiting for randomness
+    #[returns(PendingJobsResponse)]
+    ListPendingJobs {start_after: Option<String>, limit: Option<u32>},
+
+    /// pity counters of a wallet in current mystery box event
+    #[returns(PityCounterResponse)]
+    GetPityCounter {address: String},
+
+    /// escrowed prizes of an item type in mystery box event with id
+    #[returns(PrizesResponse)]
+    ListPrizes {id: u32, item_type: String, start_after: Option<u64>, limit: Option<u32>},
+
+    /// cw721 contracts allowed to be used for prizes
+    #[returns(PrizeCollectionsResponse)]
+    ListPrizeCollections {start_after: Option<String>, limit: Option<u32>},
+
+    /// fusion recipe for items of a rarity
+    #[returns(Option<FusionRecipe>)]
+    GetFusionRecipe {rarity: String},
+
+    /// price at which contract buys back unopened boxes
+    #[returns(Option<BoxBuyback>)]
+    GetBoxBuyback {},
+
+    /// payout for selling an item of a rarity back to contract
+    #[returns(Option<Coin>)]
+    GetItemBuyback {rarity: String},
 }
 
 
@@ -155,4 +387,66 @@ pub struct LinkedArress {
     pub aurand_address: Addr,
     pub box_supplier_address: Option<Addr>,
     pub item_supplier_address: Option<Addr>,
-}
\ No newline at end of file
+}
+
+#[cw_serde]
+pub struct ItemTypeRate {
+    pub name: String,
+    pub rate: Decimal,
+    pub supply: u32,
+}
+
+#[cw_serde]
+pub struct SimulatedItem {
+    pub item_type: String, // name of item type would be minted
+    pub purity: Decimal,
+    pub token_uri: String,
+    pub prize: Option<Prize>, // escrowed prize would be transferred instead of minting
+    pub reward: Option<Reward>, // fungible reward would be paid
+    pub mint_item: bool, // if false, no item would be minted
+}
+
+#[cw_serde]
+pub struct SimulateOpenResponse {
+    pub items: Vec<SimulatedItem>, // items would be drawn from the box
+    pub rates: Vec<ItemTypeRate>, // rate of each item type after the draw
+}
+
+#[cw_serde]
+pub struct PendingJob {
+    pub request_id: String,
+    pub id: u32, // id of mystery box event the job draws from
+    pub sender: Addr,
+    pub created_time: Timestamp,
+    pub requested_time: Timestamp,
+    pub age: u64, // seconds since the job was created
+    pub kind: JobKind,
+}
+
+#[cw_serde]
+pub struct PendingJobsResponse {
+    pub jobs: Vec<PendingJob>,
+}
+
+#[cw_serde]
+pub struct PityCount {
+    pub item_type: String,
+    pub counter: u32, // consecutive opens without getting this type or rarer
+    pub pity: u32, // open on which this type is guaranteed
+}
+
+#[cw_serde]
+pub struct PityCounterResponse {
+    pub counters: Vec<PityCount>,
+}
+
+#[cw_serde]
+pub struct PrizesResponse {
+    pub count: u64,
+    pub prizes: Vec<Prize>,
+}
+
+#[cw_serde]
+pub struct PrizeCollectionsResponse {
+    pub collections: Vec<Addr>,
+}
@@ -1,17 +1,115 @@
 use std::str::FromStr;
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Addr, Timestamp, Decimal, Coin};
+use std::collections::BTreeMap;
+use cosmwasm_std::{Addr, Timestamp, Decimal, Coin, Uint128, Storage, StdResult};
 use cw_storage_plus::{Item, Map};
+use cw_utils::Scheduled;
+use cw721_rarity::Trait;
 
 use crate::msg::RateDistributionMsg;
+use crate::utils::random_u128;
+use crate::validation::validate_rate_distribution;
 use crate::ContractError;
 
 #[cw_serde]
 pub struct Job {
+    pub id: u32, // id of mystery box event the job draws from
     pub sender: Addr,
+    pub created_time: Timestamp, // time the job was created
+    pub requested_time: Timestamp, // time randomness was last requested
+    pub kind: JobKind,
 }
+
+/// what randomness of a job is used for
+#[cw_serde]
+pub enum JobKind {
+    /// open a purchased box, request id is token id of the box
+    OpenBox {},
+
+    /// fuse items into one item of an output chosen by randomness, request id is token id of the new item
+    Fuse {
+        outputs: Vec<FusionOutput>,
+    },
+
+    /// draw again from mystery box after an item was burned, request id is token id of the new item
+    Reroll {
+        burned_token_id: String,
+    },
+}
+
+impl JobKind {
+    /// number of items drawn when randomness is received
+    pub fn items(&self, mystery_box: &MysteryBox) -> u32 {
+        match self {
+            JobKind::OpenBox {} => mystery_box.items_per_box(),
+            JobKind::Fuse { .. } | JobKind::Reroll { .. } => 1u32,
+        }
+    }
+}
+
+/// possible result of fusing items
+/// fused item gets metadata of item_type named rarity of current mystery box event
+/// no token uri is taken from uri pools of the event, so fusion never uses up uri of boxes
+#[cw_serde]
+pub struct FusionOutput {
+    pub rarity: String,
+    pub rate: Decimal, // chance of getting this output, rates of all outputs sum to 1
+}
+
+/// recipe to fuse items of the same rarity
+#[cw_serde]
+pub struct FusionRecipe {
+    pub input_count: u32, // number of items burned
+    pub outputs: Vec<FusionOutput>,
+}
+
+impl FusionRecipe {
+    /// check if recipe needs at least 2 items and rates of outputs sum to 1
+    pub fn validate(&self) -> Result<(), ContractError> {
+        if self.input_count < 2 || self.outputs.is_empty() {
+            return Err(ContractError::InvalidFusionRecipe{});
+        }
+
+        let mut total_rate = Decimal::zero();
+        for output in self.outputs.iter() {
+            if output.rate.is_zero() {
+                return Err(ContractError::InvalidFusionRecipe{});
+            }
+            total_rate = total_rate.checked_add(output.rate)
+                .map_err(|_| ContractError::InvalidFusionRecipe{})?;
+        }
+
+        if total_rate != Decimal::one() {
+            return Err(ContractError::InvalidFusionRecipe{});
+        }
+
+        Ok(())
+    }
+}
+
+/// choose a fusion output using random number in range 0..max_range
+pub fn choose_fusion_output(outputs: &[FusionOutput], random_number: u128, max_range: u128) -> Result<FusionOutput, ContractError> {
+    let mut upper_bound = Decimal::zero();
+    for output in outputs.iter() {
+        upper_bound = upper_bound.checked_add(output.rate)
+            .map_err(|_| ContractError::DecimalOperationFail{})?;
+
+        if Decimal::from_ratio(random_number, max_range) < upper_bound {
+            return Ok(output.clone());
+        }
+    }
+
+    // random_number == max_range falls to the last output
+    outputs.last().cloned().ok_or(ContractError::InvalidFusionRecipe{})
+}
+
+/// fusion recipes keyed by rarity of input items
+pub const FUSION_RECIPES: Map<&str, FusionRecipe> = Map::new("fusion recipes");
 pub const JOBS: Map<String, Job> = Map::new("jobs");
 
+/// number of jobs waiting for randomness, keyed by mystery box event id
+pub const PENDING_JOBS: Map<u32, u64> = Map::new("pending jobs");
+
 #[cw_serde]
 pub struct Config {
     pub owner: Addr,
@@ -29,24 +127,195 @@ pub struct ItemType {
     pub slip_rate: u32,
     pub supply: u32,
     max_supply: u32,
+    pub pity: Option<u32>,
+    pub pool: Option<UriPool>, // if not set, item_type uses shared uri pool of mystery box
+    pub reward: Option<Reward>, // fungible reward paid to opener when item_type is drawn
+    pub mint_item: bool, // if false, only reward is paid
+    pub image: Option<String>, // image of minted items
+    pub attributes: Vec<Trait>, // traits added to metadata of minted items
 } 
 
+/// fungible reward paid when an item_type is drawn
+#[cw_serde]
+pub enum Reward {
+    Native {
+        amount: Coin,
+    },
+    Cw20 {
+        contract_addr: String,
+        amount: Uint128,
+    },
+}
+
+impl Reward {
+    pub fn amount(&self) -> Uint128 {
+        match self {
+            Reward::Native { amount } => amount.amount,
+            Reward::Cw20 { amount, .. } => *amount,
+        }
+    }
+
+    /// same reward asset with different amount
+    fn with_amount(&self, new_amount: Uint128) -> Reward {
+        match self {
+            Reward::Native { amount } => Reward::Native { 
+                amount: Coin { denom: amount.denom.clone(), amount: new_amount } 
+            },
+            Reward::Cw20 { contract_addr, .. } => Reward::Cw20 { 
+                contract_addr: contract_addr.clone(), 
+                amount: new_amount 
+            },
+        }
+    }
+
+    /// check if two rewards are paid with same denom or cw20 contract
+    pub fn is_same_asset(&self, other: &Reward) -> bool {
+        match (self, other) {
+            (Reward::Native { amount: a }, Reward::Native { amount: b }) => a.denom == b.denom,
+            (Reward::Cw20 { contract_addr: a, .. }, Reward::Cw20 { contract_addr: b, .. }) => a == b,
+            _ => false,
+        }
+    }
+}
+
+/// pool of token uri owned by an item_type
+#[cw_serde]
+pub struct UriPool {
+    pub prefix_uri: String,
+    pub total_supply: u64,
+    pub remaining: u64, // number of token ids left in the pool
+}
+
+impl UriPool {
+    fn new(prefix_uri: Option<String>, total_supply: Option<u64>) -> Result<Option<UriPool>, ContractError> {
+        match (prefix_uri, total_supply) {
+            (None, None) => Ok(None),
+            (Some(prefix_uri), Some(total_supply)) if total_supply > 0 => Ok(Some(UriPool {
+                prefix_uri,
+                total_supply,
+                remaining: total_supply,
+            })),
+            _ => Err(ContractError::InvalidUriPool{}),
+        }
+    }
+}
+
+/// token ids of a uri pool are kept as a lazy Fisher-Yates permutation of 0..total_supply
+/// slot i holds token id i unless it was swapped, so only swapped slots are stored
+pub const POOL_SWAPS: Map<(u32, &str, u64), u64> = Map::new("pool swaps");
+
+/// pool key of shared uri pool of mystery box, uri pool of an item_type uses its name
+pub const SHARED_POOL: &str = "";
+
+/// view of POOL_SWAPS of a mystery box event
+/// reads go to storage, changes are kept in memory until they are saved
+pub struct PoolSwaps<'a> {
+    storage: &'a dyn Storage,
+    id: u32,
+    changes: BTreeMap<(String, u64), u64>,
+}
+
+impl<'a> PoolSwaps<'a> {
+    pub fn new(storage: &'a dyn Storage, id: u32) -> PoolSwaps<'a> {
+        PoolSwaps { storage, id, changes: BTreeMap::new() }
+    }
+
+    /// token id at a slot of a pool
+    pub fn get(&self, pool: &str, slot: u64) -> StdResult<u64> {
+        match self.changes.get(&(pool.to_string(), slot)) {
+            Some(token_id) => Ok(*token_id),
+            None => Ok(POOL_SWAPS.may_load(self.storage, (self.id, pool, slot))?.unwrap_or(slot)),
+        }
+    }
+
+    fn set(&mut self, pool: &str, slot: u64, token_id: u64) {
+        self.changes.insert((pool.to_string(), slot), token_id);
+    }
+
+    /// choose a token id from the remaining slots of a pool using random index
+    /// if replacement == true, the chosen slot is swapped with the last remaining slot and removed
+    pub fn take(&mut self, pool: &str, remaining: &mut u64, random_index: usize, replacement: bool) -> Result<u64, ContractError> {
+        if *remaining == 0 {
+            return Err(ContractError::SoldOut{});
+        }
+
+        let slot = random_index as u64 % *remaining;
+        let token_id = self.get(pool, slot)?;
+
+        if replacement {
+            let last = *remaining - 1;
+            let last_token_id = self.get(pool, last)?;
+            self.set(pool, slot, last_token_id);
+            self.set(pool, last, last);
+            *remaining = last;
+        }
+
+        Ok(token_id)
+    }
+
+    /// put a token id taken from a pool back to its remaining slots
+    pub fn put_back(&mut self, pool: &str, remaining: &mut u64, token_id: u64) {
+        self.set(pool, *remaining, token_id);
+        *remaining += 1;
+    }
+
+    /// release storage and keep changes to be saved
+    pub fn into_changes(self) -> PoolChanges {
+        PoolChanges { id: self.id, changes: self.changes }
+    }
+}
+
+/// changes of POOL_SWAPS made by draws
+pub struct PoolChanges {
+    id: u32,
+    changes: BTreeMap<(String, u64), u64>,
+}
+
+impl PoolChanges {
+    pub fn save(self, storage: &mut dyn Storage) -> StdResult<()> {
+        for ((pool, slot), token_id) in self.changes.into_iter() {
+            if token_id == slot {
+                POOL_SWAPS.remove(storage, (self.id, &pool, slot));
+            } else {
+                POOL_SWAPS.save(storage, (self.id, &pool, slot), &token_id)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// name of default item_type if not set
+pub const DEFAULT_ITEM_TYPE: &str = "common";
+
 impl ItemType {
     fn default(name: Option<String>) -> ItemType {
         // default item type 
         ItemType { 
-            name: if name.is_some() {
-                name.unwrap()
-            } else {
-                String::from("common")
-            }, 
+            name: name.unwrap_or_else(|| String::from(DEFAULT_ITEM_TYPE)), 
             rate: Decimal::zero(), 
             max_rate: Decimal::one(), 
             slip_rate: 0u32, 
             supply: u32::MAX,
             max_supply: u32::MAX,
+            pity: None,
+            pool: None,
+            reward: None,
+            mint_item: true,
+            image: None,
+            attributes: vec![],
         }
     }
+
+    /// uri pool of item_type ran out, item_type can not be drawn until a token id is put back
+    /// supply is kept as is, so the pool can be refilled without losing track of it
+    pub fn is_pool_exhausted(&self) -> bool {
+        matches!(&self.pool, Some(pool) if pool.remaining == 0)
+    }
+
+    /// number of items of item_type can be drawn now
+    pub fn drawable_supply(&self) -> u32 {
+        if self.is_pool_exhausted() { 0 } else { self.supply }
+    }
 }
 
 
@@ -56,109 +325,245 @@ const RATE_MODIFY_EXPONENT: u32 = 2u32;
 // E ^ (MAX_EXPONENT + 1) case multiplication overflow
 const MAX_EXPONENT: u32 = 116u32;
 
-/// Calculate rate modify for a type of rarity using equation:
-///     
-///    let rate_modifier = (1 / (1 + e ^-(n / slip_rate))) ^ RATE_MODIFY_EXPONENT 
-///
-///  
-/// 
-/// e: random number (1.5)
-/// 
-/// n: number of rarity's supply
-/// 
-/// slip_rate: the magnitude of the difference between rate reductions when applying rate_modifier
-/// 
-/// RATE_MODIFY_EXPONENT: increase rate modify impact
-fn rate_modifier(n: u32, slip_rate: u32) -> Result<Decimal, ContractError> {
-    // if n (total_supply) is 0, rate will be zero
-    if n == 0 {
-        return Ok(Decimal::zero());
+/// calculate x ^ n, return error instead of panic if the result overflows
+/// (Decimal::checked_pow panics when the last multiplication overflows)
+fn checked_pow(mut x: Decimal, mut n: u32) -> Result<Decimal, ContractError> {
+    let mut y = Decimal::one();
+    while n > 0 {
+        if n % 2 == 1 {
+            y = y.checked_mul(x).map_err(|_| ContractError::DecimalOperationFail{})?;
+        }
+        n /= 2;
+        if n > 0 {
+            x = x.checked_mul(x).map_err(|_| ContractError::DecimalOperationFail{})?;
+        }
     }
-    
-    // if slip rate is 0, not apply rate_modifier
-    if slip_rate == 0 {
-        return Ok(Decimal::one());
+
+    Ok(y)
+}
+
+/// curve used to reduce rate of an item type when its supply decreases
+#[cw_serde]
+pub enum RateCurve {
+    /// rate_modifier = (1 / (1 + e ^-(n / slip_rate))) ^ exponent
+    /// 
+    /// e: random number (recommend 1.5 - 3.0)
+    /// 
+    /// exponent: increase rate modify impact
+    /// 
+    /// max_exponent: upper bound of n / slip_rate, e ^ max_exponent must not overflow
+    Sigmoid {
+        e: Decimal,
+        exponent: u32,
+        max_exponent: u32,
+    },
+
+    /// rate_modifier = n / max_supply
+    Linear {},
+
+    /// rate_modifier = base ^ ((max_supply - n) / slip_rate)
+    /// 
+    /// base: 0 < base < 1, the closer to 0 the faster rate decreases
+    Exponential {
+        base: Decimal,
+    },
+
+    /// rate_modifier = 1, rate is never reduced until supply is 0
+    Constant {},
+}
+
+impl Default for RateCurve {
+    fn default() -> Self {
+        RateCurve::Sigmoid { 
+            e: Decimal::from_str(E).unwrap(), 
+            exponent: RATE_MODIFY_EXPONENT, 
+            max_exponent: MAX_EXPONENT,
+        }
     }
+}
 
-    // n_div = n / slip_rate
-    let n_div = if n < slip_rate {
-        0
-    } else {
-        let d = n / slip_rate;
+impl RateCurve {
+    /// check if parameters of curve are valid
+    pub fn validate(&self) -> Result<(), ContractError> {
+        let one = Decimal::one();
+        match self {
+            RateCurve::Sigmoid { e, exponent, max_exponent } => {
+                // e must be greater than 1 and e ^ max_exponent must not overflow
+                if *e <= one || *exponent == 0 || checked_pow(*e, *max_exponent).is_err() {
+                    return Err(ContractError::InvalidRateCurve{});
+                }
+            },
+            RateCurve::Exponential { base } => {
+                if base.is_zero() || *base >= one {
+                    return Err(ContractError::InvalidRateCurve{});
+                }
+            },
+            RateCurve::Linear {} | RateCurve::Constant {} => {},
+        }
 
-        if d > MAX_EXPONENT {
-            MAX_EXPONENT
-        }else {
-            d - 1
+        Ok(())
+    }
+
+    /// Calculate rate modify for a type of rarity
+    /// 
+    /// n: number of rarity's supply
+    /// 
+    /// max_supply: number of rarity's supply when mystery box was created
+    /// 
+    /// slip_rate: the magnitude of the difference between rate reductions when applying rate_modifier
+    fn rate_modifier(&self, n: u32, max_supply: u32, slip_rate: u32) -> Result<Decimal, ContractError> {
+        // if n (total_supply) is 0, rate will be zero
+        if n == 0 {
+            return Ok(Decimal::zero());
+        }
+        
+        // if slip rate is 0, not apply rate_modifier
+        if slip_rate == 0 {
+            return Ok(Decimal::one());
         }
-    };
-    
-    // epow = e ^ n_div
-    let mut epow: Decimal = Decimal::from_str(E).unwrap();
-    epow = epow.pow(n_div);
-    
-    // l = 1 + 1 / epow
-    let one = Decimal::one();
-    let l = one.checked_div(epow)
-            .map_err(|_| ContractError::DecimalOperationFail{})?
-            .checked_add(one)
-            .map_err(|_| ContractError::DecimalOperationFail{})?;
 
-    // m = (1 / l) ^ RATE_MODIFY_EXPONENT
-    let m = one.checked_div(l)
-        .map_err(|_| ContractError::DecimalOperationFail{})?
-        .pow(RATE_MODIFY_EXPONENT);
+        let one = Decimal::one();
+        match self {
+            RateCurve::Sigmoid { e, exponent, max_exponent } => {
+                // n_div = n / slip_rate
+                let n_div = if n < slip_rate {
+                    0
+                } else {
+                    let d = n / slip_rate;
+
+                    if d > *max_exponent {
+                        *max_exponent
+                    }else {
+                        d - 1
+                    }
+                };
+                
+                // epow = e ^ n_div
+                let epow = checked_pow(*e, n_div)?;
+                
+                // l = 1 + 1 / epow
+                let l = one.checked_div(epow)
+                        .map_err(|_| ContractError::DecimalOperationFail{})?
+                        .checked_add(one)
+                        .map_err(|_| ContractError::DecimalOperationFail{})?;
+
+                // m = (1 / l) ^ exponent
+                let m = one.checked_div(l)
+                    .map_err(|_| ContractError::DecimalOperationFail{})?
+                    .pow(*exponent);
+
+                Ok(m)
+            },
+            RateCurve::Linear {} => {
+                Ok(Decimal::from_ratio(n.min(max_supply), max_supply))
+            },
+            RateCurve::Exponential { base } => {
+                let n_div = max_supply.saturating_sub(n) / slip_rate;
+                Ok(base.pow(n_div))
+            },
+            RateCurve::Constant {} => Ok(one),
+        }
+    }
+}
 
-    Ok(m)
+/// how item type is chosen when a box is opened
+#[cw_serde]
+#[derive(Default)]
+pub enum DrawMode {
+    /// item type is chosen by its rate, rate decreases with supply following rate curve
+    #[default]
+    Rate,
+
+    /// each item type has an exact count, items are drawn without replacement from remaining counts
+    Deck,
 }
 
+/// maximum number of items can be drawn from one box
+pub const MAX_ITEMS_PER_BOX: u32 = 10u32;
 
 #[cw_serde]
 pub struct RateDistribution {
     pub vec: Vec<ItemType>,
+    pub rate_curve: RateCurve,
+    pub mode: DrawMode,
+    pub items_per_box: u32, // number of items drawn when a box is opened
+    pub guaranteed_type: Option<String>, // at least one item of each box is this type or rarer
 }
 
 impl RateDistribution {
     /// init rate distribution
-    pub fn new(init_rate_distribution: RateDistributionMsg) -> Result<RateDistribution,ContractError> {
-        let one = Decimal::one();
-        let zero = Decimal::zero();
+    /// box_supply is the total number of boxes of mystery box event, it's used as deck size in deck mode
+    pub fn new(init_rate_distribution: RateDistributionMsg, box_supply: u64) -> Result<RateDistribution,ContractError> {
+        validate_rate_distribution(&init_rate_distribution)?;
+
+        // if not set, one item is drawn from a box
+        let items_per_box = init_rate_distribution.items_per_box.unwrap_or(1u32);
+        if items_per_box == 0 || items_per_box > MAX_ITEMS_PER_BOX {
+            return Err(ContractError::InvalidItemsPerBox{});
+        }
+        let guaranteed_type = init_rate_distribution.guaranteed_type.clone();
+
+        let mut rate_distribution = if init_rate_distribution.mode.clone().unwrap_or_default() == DrawMode::Deck {
+            // each item of each box needs a slot in the deck
+            RateDistribution::new_deck(init_rate_distribution, box_supply.saturating_mul(items_per_box as u64))?
+        } else {
+            RateDistribution::new_rate(init_rate_distribution)?
+        };
+
+        // guaranteed item type must exist in distribution
+        if let Some(name) = &guaranteed_type {
+            if !rate_distribution.vec.iter().any(|item_type| &item_type.name == name) {
+                return Err(ContractError::ItemTypeNotFound{});
+            }
+        }
+        rate_distribution.items_per_box = items_per_box;
+        rate_distribution.guaranteed_type = guaranteed_type;
+
+        Ok(rate_distribution)
+    }
+
+    /// init distribution where item type is chosen by its rate
+    fn new_rate(init_rate_distribution: RateDistributionMsg) -> Result<RateDistribution,ContractError> {
+        // if not set, use sigmoid curve with default parameters
+        let rate_curve = init_rate_distribution.rate_curve.unwrap_or_default();
+        rate_curve.validate()?;
+
         let mut rate_distribution: RateDistribution = RateDistribution {
-            vec: Vec::new()
+            vec: Vec::new(),
+            rate_curve,
+            mode: DrawMode::Rate,
+            items_per_box: 1u32,
+            guaranteed_type: None,
         };
-        
-        // total rate of all item type
-        let mut total_rate = zero;
+
         for item_msg in init_rate_distribution.vec.iter() {
 
-            // check if 0 < rate < 1
-            if item_msg.rate >= one || item_msg.rate <= zero {
-                return Err(ContractError::CustomError {
-                    val: format!("Rate of item {} error !!! must 0 < rate < 1",item_msg.name) 
-                });
+            // pity of 0 never triggers
+            if item_msg.pity == Some(0) {
+                return Err(ContractError::InvalidPity{});
             }
-            
+
+            // reward must not be zero, item_type without item must have reward
+            if item_msg.reward.as_ref().map_or(item_msg.mint_item == Some(false), |r| r.amount().is_zero()) {
+                return Err(ContractError::InvalidReward{});
+            }
+
             let item: ItemType = ItemType { 
                 name: item_msg.name.clone(), 
                 rate: item_msg.rate, 
                 max_rate: item_msg.rate, 
                 slip_rate: item_msg.slip_rate, 
                 supply: item_msg.supply,
-                max_supply: item_msg.supply
+                max_supply: item_msg.supply,
+                pity: item_msg.pity,
+                pool: UriPool::new(item_msg.prefix_uri.clone(), item_msg.total_supply)?,
+                reward: item_msg.reward.clone(),
+                mint_item: item_msg.mint_item.unwrap_or(true),
+                image: item_msg.image.clone(),
+                attributes: item_msg.attributes.clone().unwrap_or_default(),
             };
 
             rate_distribution.vec.push(item);
-
-            // calculate new total_rate
-            total_rate = total_rate.checked_add(item_msg.rate)
-                .map_err(|_| ContractError::DecimalOperationFail{})?;
-        }
-
-        // check if total_rate greater than 1
-        if total_rate > one {
-            return Err(ContractError::CustomError { 
-                val: String::from("total rate greater than 1") 
-            })
         }
 
         // add default item_type to distribution
@@ -170,9 +575,84 @@ impl RateDistribution {
         Ok(rate_distribution)
     }
 
+    /// init deck distribution
+    /// supply of each item type is its exact count, default item type gets remaining slots of the deck
+    /// deck_size is number of items can be drawn from all boxes
+    fn new_deck(init_rate_distribution: RateDistributionMsg, deck_size: u64) -> Result<RateDistribution,ContractError> {
+        // every box must map to a slot, so number of boxes must be limited and fit in u32 supply
+        if deck_size == 0 || deck_size > u32::MAX as u64 {
+            return Err(ContractError::InvalidDeckSize{});
+        }
+
+        let mut rate_distribution: RateDistribution = RateDistribution {
+            vec: Vec::new(),
+            rate_curve: init_rate_distribution.rate_curve.unwrap_or_default(),
+            mode: DrawMode::Deck,
+            items_per_box: 1u32,
+            guaranteed_type: None,
+        };
+        rate_distribution.rate_curve.validate()?;
+
+        // total count of all item type
+        let mut total_count = 0u64;
+        for item_msg in init_rate_distribution.vec.iter() {
+            if item_msg.supply == 0 {
+                return Err(ContractError::InvalidDeckSize{});
+            }
+
+            // pity of 0 never triggers
+            if item_msg.pity == Some(0) {
+                return Err(ContractError::InvalidPity{});
+            }
+
+            // reward must not be zero, item_type without item must have reward
+            if item_msg.reward.as_ref().map_or(item_msg.mint_item == Some(false), |r| r.amount().is_zero()) {
+                return Err(ContractError::InvalidReward{});
+            }
+
+            let rate = Decimal::from_ratio(item_msg.supply, deck_size);
+            rate_distribution.vec.push(ItemType { 
+                name: item_msg.name.clone(), 
+                rate, 
+                max_rate: rate, 
+                slip_rate: item_msg.slip_rate, 
+                supply: item_msg.supply,
+                max_supply: item_msg.supply,
+                pity: item_msg.pity,
+                pool: UriPool::new(item_msg.prefix_uri.clone(), item_msg.total_supply)?,
+                reward: item_msg.reward.clone(),
+                mint_item: item_msg.mint_item.unwrap_or(true),
+                image: item_msg.image.clone(),
+                attributes: item_msg.attributes.clone().unwrap_or_default(),
+            });
+
+            total_count += item_msg.supply as u64;
+        }
+
+        // total count must not greater than deck size
+        if total_count > deck_size {
+            return Err(ContractError::InvalidDeckSize{});
+        }
+
+        // default item_type fills the rest of the deck
+        let default_count = (deck_size - total_count) as u32;
+        let default_rate = Decimal::from_ratio(default_count, deck_size);
+        let mut default_item_type = ItemType::default(init_rate_distribution.default_type);
+        default_item_type.rate = default_rate;
+        default_item_type.max_rate = default_rate;
+        default_item_type.supply = default_count;
+        default_item_type.max_supply = default_count;
+        rate_distribution.vec.push(default_item_type);
+
+        // sort distribution by item_type's count
+        rate_distribution.sort_item_type();
+
+        Ok(rate_distribution)
+    }
+
     /// sort item type by max rate
     fn sort_item_type(&mut self) {
-        self.vec.sort_by(|a, b| a.max_rate.cmp(&b.max_rate));
+        self.vec.sort_by_key(|a| a.max_rate);
     }
 
     /// get item type using random number and max_range number
@@ -188,7 +668,9 @@ impl RateDistribution {
             
             // because of 0 < item_type.rate < 1 and total rate <= 1, below operation will never fail 
             // calculate lower_bound of this item type
-            let range = max_range_decimal * item_type.rate;
+            // item_type with exhausted uri pool is skipped
+            let rate = if item_type.is_pool_exhausted() { Decimal::zero() } else { item_type.rate };
+            let range = max_range_decimal * rate;
             let lower_bound = current_upper_bound - range.to_uint_floor().u128(); 
             
             // if random number in range lower_bound..current_upper_bound return current index
@@ -201,16 +683,92 @@ impl RateDistribution {
         }
 
         // if not find any item_type, return default item_type 
-        if let Some(_) = self.vec.last() {
+        if !self.vec.is_empty() {
             return Ok(self.vec.len() - 1);
         }
 
+        // unreachable, default item type is always the last of vec
         Err(ContractError::PriceInsufficient{})
     }
 
+    /// get item type in deck mode using random number
+    /// each remaining slot of the deck has the same chance to be drawn
+    pub fn get_deck_index(&self, random_number: u128) -> Result<usize, ContractError>{
+        let remaining: u128 = self.vec.iter().map(|item_type| item_type.drawable_supply() as u128).sum();
+        if remaining == 0 {
+            return Err(ContractError::SoldOut{});
+        }
+
+        // find the item_type owning the chosen slot
+        let mut slot = random_number % remaining;
+        for (index, item_type) in self.vec.iter().enumerate() {
+            if slot < item_type.drawable_supply() as u128 {
+                return Ok(index);
+            }
+            slot -= item_type.drawable_supply() as u128;
+        }
+
+        Err(ContractError::SoldOut{})
+    }
+
+    /// maximum amount of each reward asset can be paid, assuming all remaining supply is drawn
+    pub fn reward_liabilities(&self) -> Result<Vec<Reward>, ContractError> {
+        let mut liabilities: Vec<Reward> = Vec::new();
+        for item_type in self.vec.iter() {
+            if let Some(reward) = &item_type.reward {
+                let amount = reward.amount().checked_mul(Uint128::from(item_type.supply))
+                    .map_err(|_| ContractError::InsufficientEscrow{})?;
+
+                match liabilities.iter_mut().find(|l| l.is_same_asset(reward)) {
+                    Some(liability) => {
+                        let total = liability.amount().checked_add(amount)
+                            .map_err(|_| ContractError::InsufficientEscrow{})?;
+                        *liability = liability.with_amount(total);
+                    },
+                    None => liabilities.push(reward.with_amount(amount)),
+                }
+            }
+        }
+
+        Ok(liabilities)
+    }
+
+    /// apply pity to drawn item_type at specified index
+    /// pity_counters[i] is number of consecutive opens without getting item_type i or rarer (lower index)
+    /// if this is the Nth open of an item_type with pity N, the rarest of such item_types is forced
+    /// counters are updated with the final item_type, which is returned
+    pub fn apply_pity(&self, index: usize, pity_counters: &mut [u32]) -> usize {
+        let forced = self.vec.iter().enumerate().find(|(i, item_type)| {
+            match item_type.pity {
+                Some(pity) => *i < index && item_type.drawable_supply() > 0 
+                    && pity_counters[*i].saturating_add(1) >= pity,
+                None => false,
+            }
+        });
+
+        let index = match forced {
+            Some((i, _)) => i,
+            None => index,
+        };
+
+        // reset counters of item_types at or below the final one, increase the others
+        for (i, counter) in pity_counters.iter_mut().enumerate() {
+            if self.vec[i].pity.is_none() {
+                continue;
+            }
+            *counter = if index <= i { 0 } else { counter.saturating_add(1) };
+        }
+
+        index
+    }
+
     /// update item_type rate and supply at specified index 
     pub fn update_item_type(&mut self, index: usize) -> Result<(),ContractError>{
-        let mut item_type = &mut self.vec[index];
+        if self.mode == DrawMode::Deck {
+            return self.update_deck(index);
+        }
+
+        let item_type = &mut self.vec[index];
     
         if item_type.supply <= 1u32 {
             // if item_type's supply equal 0 after updated
@@ -220,7 +778,8 @@ impl RateDistribution {
         }else {
             let remain_supply = item_type.supply - 1u32;
             // calculate new rate for item_type using rate_modifier
-            item_type.rate = item_type.max_rate.checked_mul(rate_modifier(remain_supply, item_type.slip_rate)?)
+            let modifier = self.rate_curve.rate_modifier(remain_supply, item_type.max_supply, item_type.slip_rate)?;
+            item_type.rate = item_type.max_rate.checked_mul(modifier)
                 .map_err(|_| ContractError::DecimalOperationFail{})?;
             // update new supply
             item_type.supply = remain_supply;
@@ -229,6 +788,57 @@ impl RateDistribution {
         Ok(())
     }
     
+    /// inverse of update_item_type, give back one supply to item_type at specified index
+    pub fn restore_item_type(&mut self, index: usize) -> Result<(),ContractError>{
+        let item_type = &mut self.vec[index];
+        if item_type.supply >= item_type.max_supply {
+            return Err(ContractError::InvalidCondition{});
+        }
+        item_type.supply += 1;
+
+        if self.mode == DrawMode::Deck {
+            self.update_deck_rates();
+            return Ok(());
+        }
+
+        let item_type = &mut self.vec[index];
+        if item_type.supply == item_type.max_supply {
+            // item_type is back to its initial rate
+            item_type.rate = item_type.max_rate;
+        } else {
+            // calculate rate for restored supply using rate_modifier
+            let modifier = self.rate_curve.rate_modifier(item_type.supply, item_type.max_supply, item_type.slip_rate)?;
+            item_type.rate = item_type.max_rate.checked_mul(modifier)
+                .map_err(|_| ContractError::DecimalOperationFail{})?;
+        }
+
+        Ok(())
+    }
+
+    /// remove one slot of item_type at specified index from the deck
+    /// rate of each item_type is its share of remaining slots
+    fn update_deck(&mut self, index: usize) -> Result<(),ContractError>{
+        if self.vec[index].supply == 0 {
+            return Err(ContractError::SoldOut{});
+        }
+        self.vec[index].supply -= 1;
+        self.update_deck_rates();
+
+        Ok(())
+    }
+
+    // rate of each item_type in deck is its share of remaining slots
+    fn update_deck_rates(&mut self) {
+        let remaining: u64 = self.vec.iter().map(|item_type| item_type.supply as u64).sum();
+        for item_type in self.vec.iter_mut() {
+            item_type.rate = if remaining == 0 {
+                Decimal::zero()
+            } else {
+                Decimal::from_ratio(item_type.supply, remaining)
+            };
+        }
+    }
+    
     /// calculate current purity of item_type at specified index 
     ///     purity = (h - c) / (h - l) (0..1)
     /// 
@@ -244,9 +854,14 @@ impl RateDistribution {
             return Ok(Decimal::zero());
         }
 
-        let max_rate_modifier = rate_modifier(item_type.max_supply, item_type.slip_rate)?;
-        let min_rate_modifier = rate_modifier(1u32, item_type.slip_rate)?;
-        let current_rate_modifier = rate_modifier(item_type.supply, item_type.slip_rate)?;
+        let max_rate_modifier = self.rate_curve.rate_modifier(item_type.max_supply, item_type.max_supply, item_type.slip_rate)?;
+        let min_rate_modifier = self.rate_curve.rate_modifier(1u32, item_type.max_supply, item_type.slip_rate)?;
+        let current_rate_modifier = self.rate_curve.rate_modifier(item_type.supply, item_type.max_supply, item_type.slip_rate)?;
+
+        // rate of item_type never changes, purity is always 0
+        if max_rate_modifier <= min_rate_modifier {
+            return Ok(Decimal::zero());
+        }
 
         let purity = (max_rate_modifier - current_rate_modifier) / (max_rate_modifier - min_rate_modifier);
         Ok(purity) 
@@ -258,24 +873,296 @@ pub struct MysteryBox {
     pub id: u32,
     pub name: String,
     pub description: String,
-    pub start_time: Timestamp,
-    pub end_time: Timestamp,
+    pub start: Scheduled,
+    pub end: Scheduled,
     pub rate_distribution: Option<RateDistribution>,
     pub prefix_uri: Option<String>,
-    pub tokens_id: Vec<u64>,
     pub total_supply: u64,
+    pub remaining_tokens: u64, // number of token ids left in shared uri pool
     pub max_minted_box: u64,
+    pub minted_box: u64, // number of boxes sold
+    pub bought_back_box: u64, // number of boxes sold back, minted_box + bought_back_box never decreases
     pub replacement: bool,
     pub price: Coin,
+    pub reroll_fee: Option<Coin>, // if not set, items can not be re-rolled
+    pub royalty: Option<Royalty>, // royalty set on every NFT minted from this event
+    pub item_metadata: Option<MetadataTemplate>,
+    pub box_image: Option<String>,
+    pub minted_item: u64, // number of items minted, used as serial number of items
     pub created_time: Timestamp,
+    pub has_sold: bool, // true once the first box is sold, locks sale fields of the event
+    pub has_opened: bool, // true once the first box is opened, locks prefix_uri and rate_distribution
+    pub placeholder_uri: Option<String>, // uri of items drawn from shared uri pool before reveal
+    pub provenance_hash: Option<String>, // hex sha256 of prefix_uri committed for delayed reveal
+}
+
+/// templates of name and description of minted items
+/// placeholders: {event_name}, {event_id}, {item_type}, {purity}, {n} (serial number)
+#[cw_serde]
+pub struct MetadataTemplate {
+    pub name: Option<String>,
+    pub description: Option<String>,
+}
+
+/// cw2981 royalty of minted NFTs
+#[cw_serde]
+pub struct Royalty {
+    pub payment_address: Addr,
+    pub percentage: u64, // 0 - 100
+}
+
+impl Royalty {
+    /// royalty_payment_address and royalty_percentage of minted NFT metadata
+    pub fn metadata_fields(royalty: &Option<Royalty>) -> (Option<String>, Option<u64>) {
+        match royalty {
+            Some(royalty) => (Some(royalty.payment_address.to_string()), Some(royalty.percentage)),
+            None => (None, None),
+        }
+    }
 }
 
 impl  MysteryBox {
-    pub fn remove_token_id(&mut self, index: usize) {
-        self.tokens_id.swap_remove(index);
+    /// draw an item from mystery box using aurand randomness
+    /// random_type is used to choose item_type, random_index is used to choose token id
+    /// pity_counters of the opener (one per item_type) are applied and updated
+    /// prize_counts are numbers of escrowed prizes (one per item_type), a drawn prize is counted out
+    /// rate distribution and uri pools of mystery box are updated as if the item was minted
+    pub fn draw(
+        &mut self, 
+        swaps: &mut PoolSwaps,
+        random_type: u128, 
+        random_index: usize, 
+        max_range: u128,
+        pity_counters: &mut [u32],
+        prize_counts: &mut [u64],
+    ) -> Result<Draw, ContractError> {
+        Ok(self.draw_item(swaps, (random_type, random_index), max_range, pity_counters, prize_counts, None)?.1)
+    }
+
+    /// number of items drawn when a box is opened
+    pub fn items_per_box(&self) -> u32 {
+        self.rate_distribution.as_ref().map_or(1u32, |rate_distribution| rate_distribution.items_per_box)
+    }
+
+    /// draw all items of a box, each pair of randomness is used to draw one item
+    /// if no item is guaranteed type or rarer, the last item is forced to be guaranteed type
+    pub fn draw_bundle(
+        &mut self, 
+        swaps: &mut PoolSwaps,
+        randomness: &[(u128, usize)], 
+        max_range: u128,
+        pity_counters: &mut [u32],
+        prize_counts: &mut [u64],
+    ) -> Result<Vec<Draw>, ContractError> {
+        let guaranteed = match &self.rate_distribution {
+            Some(RateDistribution { vec, guaranteed_type: Some(name), .. }) => {
+                vec.iter().position(|item_type| &item_type.name == name)
+            },
+            _ => None,
+        };
+
+        let mut draws: Vec<Draw> = Vec::new();
+        let mut guarantee_met = false;
+        for (i, (random_type, random_index)) in randomness.iter().enumerate() {
+            let force = if i + 1 == randomness.len() && !guarantee_met { guaranteed } else { None };
+
+            let (index, draw) = self.draw_item(swaps, (*random_type, *random_index), max_range, pity_counters, prize_counts, force)?;
+            if guaranteed.is_some_and(|g| index <= g) {
+                guarantee_met = true;
+            }
+            draws.push(draw);
+        }
+
+        Ok(draws)
+    }
+
+    /// draw an item, if guaranteed is set and drawn item_type is not guaranteed item_type or rarer 
+    /// guaranteed item_type is forced
+    /// return index of drawn item_type and the draw
+    fn draw_item(
+        &mut self, 
+        swaps: &mut PoolSwaps,
+        (random_type, random_index): (u128, usize),
+        max_range: u128,
+        pity_counters: &mut [u32],
+        prize_counts: &mut [u64],
+        guaranteed: Option<usize>,
+    ) -> Result<(usize, Draw), ContractError> {
+        let mut rate_distribution = match self.rate_distribution.clone() {
+            Some(rate_distribution) => rate_distribution,
+            None => return Err(ContractError::MysteryBoxNotUpdated{}),
+        };
+
+        // get index of item_type based on aurand randomness
+        let index = match rate_distribution.mode {
+            DrawMode::Rate => rate_distribution.get_item_type_index(random_type, max_range)?,
+            // deck can be larger than max_range, so use both random numbers to choose a slot
+            DrawMode::Deck => rate_distribution.get_deck_index(
+                random_u128(&[random_type, random_index as u128])
+            )?,
+        };
+
+        // force guaranteed item_type if drawn item_type is more common
+        let index = match guaranteed {
+            Some(g) if index > g && rate_distribution.vec[g].drawable_supply() > 0 => g,
+            _ => index,
+        };
+
+        // force item_type if opener reached its pity
+        let index = rate_distribution.apply_pity(index, pity_counters);
+
+        // get current purity of item_type at specified index
+        let purity = rate_distribution.purity(index)?;
+
+        // update supply and rate for item_type at specifed index
+        rate_distribution.update_item_type(index)?;
+
+        // escrowed prize of item_type is sent instead of minting an item
+        let prize = rate_distribution.vec[index].mint_item && prize_counts.get(index).is_some_and(|count| *count > 0);
+        if prize {
+            prize_counts[index] -= 1;
+        }
+
+        // no item is minted for reward only item_type or prize, so no token uri is used
+        let (token_index, token_uri, hidden) = if rate_distribution.vec[index].mint_item && !prize {
+            self.take_token_uri(swaps, &mut rate_distribution, Some(index), random_index)?
+        } else {
+            (0, String::new(), false)
+        };
+
+        let ItemType { name: item_type, reward, mint_item, image, attributes, .. } = rate_distribution.vec[index].clone();
+
+        // update mystery-box rate distribution
+        self.rate_distribution = Some(rate_distribution);
+
+        Ok((index, Draw {
+            item_type,
+            purity,
+            token_index,
+            token_uri,
+            hidden,
+            reward,
+            mint_item,
+            prize,
+            image,
+            attributes,
+        }))
+    }
+
+    /// make a fused item of the given rarity with image and attributes of item_type named rarity
+    /// purity is the current purity of the item_type, supply of the item_type is not changed
+    /// fused item has no token uri, uri pools are only used by items drawn from boxes
+    pub fn fuse_item(&self, rarity: &str) -> Result<Draw, ContractError> {
+        let rate_distribution = match &self.rate_distribution {
+            Some(rate_distribution) => rate_distribution,
+            None => return Err(ContractError::MysteryBoxNotUpdated{}),
+        };
+
+        let index = rate_distribution.vec.iter().position(|t| t.name == rarity);
+        let (purity, image, attributes) = match index {
+            Some(index) => {
+                let item_type = &rate_distribution.vec[index];
+                (rate_distribution.purity(index)?, item_type.image.clone(), item_type.attributes.clone())
+            },
+            None => (Decimal::zero(), None, vec![]),
+        };
+
+        Ok(Draw {
+            item_type: rarity.to_string(),
+            purity,
+            token_index: 0,
+            token_uri: String::new(),
+            hidden: false,
+            reward: None,
+            mint_item: true,
+            prize: false,
+            image,
+            attributes,
+        })
+    }
+
+    /// take a token uri from uri pool of item_type at specified index, or from shared uri pool of mystery box
+    /// if replacement == true, selected uri is removed from its pool to make all minted Item NFTs unique
+    /// return token index, token uri and if token uri is a placeholder
+    fn take_token_uri(
+        &mut self,
+        swaps: &mut PoolSwaps,
+        rate_distribution: &mut RateDistribution,
+        index: Option<usize>,
+        random_index: usize,
+    ) -> Result<(u64, String, bool), ContractError> {
+        let replacement = self.replacement;
+        let item_type = index.map(|index| &mut rate_distribution.vec[index]);
+        match item_type {
+            // item_type has its own uri pool
+            // once pool runs out, item_type is skipped by next draws, see ItemType::is_pool_exhausted
+            Some(ItemType { name, pool: Some(pool), .. }) => {
+                let token_index = swaps.take(name, &mut pool.remaining, random_index, replacement)?;
+
+                // token uri made by combining prefix_uri and token_id
+                Ok((token_index, format!("{}{}", pool.prefix_uri, token_index), false))
+            },
+            // shared uri pool of mystery box
+            // before reveal, item gets placeholder uri and its real uri is set by reveal
+            _ => {
+                let (uri, hidden) = match (&self.prefix_uri, &self.placeholder_uri) {
+                    (Some(prefix_uri), _) => (prefix_uri.clone(), false),
+                    (None, Some(placeholder_uri)) => (placeholder_uri.clone(), true),
+                    (None, None) => return Err(ContractError::MysteryBoxNotUpdated{}),
+                };
+
+                let token_index = swaps.take(SHARED_POOL, &mut self.remaining_tokens, random_index, replacement)?;
+                let token_uri = if hidden { uri } else { format!("{}{}", uri, token_index) };
+                Ok((token_index, token_uri, hidden))
+            },
+        }
+    }
+
+    /// give back an item drawn from mystery box, inverse of draw
+    /// supply of its item_type is restored and its token id is returned to the uri pool
+    pub fn return_item(&mut self, swaps: &mut PoolSwaps, item_type: &str, token_index: u64) -> Result<(), ContractError> {
+        let mut rate_distribution = match self.rate_distribution.clone() {
+            Some(rate_distribution) => rate_distribution,
+            None => return Err(ContractError::MysteryBoxNotUpdated{}),
+        };
+
+        let index = match rate_distribution.vec.iter().position(|t| t.name == item_type) {
+            Some(index) => index,
+            None => return Err(ContractError::ItemTypeNotFound{}),
+        };
+
+        rate_distribution.restore_item_type(index)?;
+
+        // if replacement == false or no item was minted, token id was never removed from its pool
+        if self.replacement && rate_distribution.vec[index].mint_item {
+            let item_type = &mut rate_distribution.vec[index];
+            match &mut item_type.pool {
+                Some(pool) => swaps.put_back(&item_type.name, &mut pool.remaining, token_index),
+                None => swaps.put_back(SHARED_POOL, &mut self.remaining_tokens, token_index),
+            }
+        }
+
+        self.rate_distribution = Some(rate_distribution);
+
+        Ok(())
     }
 }
 
+/// outcome of drawing an item from mystery box
+#[cw_serde]
+pub struct Draw {
+    pub item_type: String,
+    pub purity: Decimal,
+    pub token_index: u64,
+    pub token_uri: String, // empty if no item is minted
+    pub hidden: bool, // token_uri is placeholder uri until mystery box is revealed
+    pub reward: Option<Reward>,
+    pub mint_item: bool,
+    pub prize: bool, // escrowed prize of item_type is sent instead of minting
+    pub image: Option<String>,
+    pub attributes: Vec<Trait>,
+}
+
 pub const MYSTERY_BOX: Item<MysteryBox> = Item::new("mystery box");
 
 pub const MYSTERY_BOX_HISTORY: Map<u32, MysteryBox> = Map::new("mystery box history"); 
@@ -285,13 +1172,110 @@ pub struct PurchasedBox {
     pub is_opened: bool,
     pub open_time: Option<Timestamp>,
     pub is_received_randomness: bool,
+    pub price: Coin, // price paid for the box
+}
+
+/// purchased boxes keyed by (id of mystery box event, token id of box)
+pub const PURCHASED_BOXES: Map<(u32, &str), PurchasedBox> = Map::new("purchased boxes");
+
+/// price at which contract buys back an unopened box
+#[cw_serde]
+pub enum BoxBuyback {
+    /// fixed price in denom of box price, capped at price paid for the box
+    Fixed {
+        price: Coin,
+    },
+
+    /// percentage of price paid for the box, 0 < percentage <= 1
+    Percentage {
+        percentage: Decimal,
+    },
+}
+
+impl BoxBuyback {
+    pub fn validate(&self) -> Result<(), ContractError> {
+        match self {
+            BoxBuyback::Fixed { price } if price.amount.is_zero() => Err(ContractError::InvalidBuyback{}),
+            BoxBuyback::Percentage { percentage } if percentage.is_zero() || *percentage > Decimal::one() => {
+                Err(ContractError::InvalidBuyback{})
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// amount paid to seller of a box bought with purchase_price
+    /// fixed price must be paid in denom of purchase_price and is capped at purchase_price,
+    /// so selling a box never makes a profit
+    pub fn payout(&self, purchase_price: &Coin) -> Result<Coin, ContractError> {
+        match self {
+            BoxBuyback::Fixed { price } => {
+                if price.denom != purchase_price.denom {
+                    return Err(ContractError::InvalidBuyback{});
+                }
+                Ok(Coin {
+                    denom: price.denom.clone(),
+                    amount: price.amount.min(purchase_price.amount),
+                })
+            },
+            BoxBuyback::Percentage { percentage } => Ok(Coin {
+                denom: purchase_price.denom.clone(),
+                amount: purchase_price.amount.mul_floor(*percentage),
+            }),
+        }
+    }
+}
+
+pub const BOX_BUYBACK: Item<BoxBuyback> = Item::new("box buyback");
+
+/// payout for selling an item back to contract, keyed by rarity
+pub const ITEM_BUYBACKS: Map<&str, Coin> = Map::new("item buybacks");
+
+/// item minted from a mystery box, kept so the item can be re-rolled
+#[cw_serde]
+pub struct MintedItem {
+    pub id: u32, // id of mystery box event
+    pub item_type: String,
+    pub token_index: u64,
+}
+
+pub const MINTED_ITEMS: Map<String, MintedItem> = Map::new("minted items");
+
+/// token index of items minted with placeholder uri, keyed by mystery box id and token id
+pub const UNREVEALED_ITEMS: Map<(u32, &str), u64> = Map::new("unrevealed items");
+
+/// true once metadata of all items is frozen, items can not be revealed anymore
+pub const ITEMS_FROZEN: Item<bool> = Item::new("items frozen");
+
+/// NFT deposited by owner as a prize of an item_type
+#[cw_serde]
+pub struct Prize {
+    pub contract: Addr, // cw721 contract of the NFT
+    pub token_id: String,
 }
 
-pub const PURCHASED_BOXES: Map<String, PurchasedBox> = Map::new("purchased boxes");
+/// escrowed prizes, keyed by (mystery box id, item_type name, index)
+/// indexes of an item_type are always 0..PRIZE_COUNTS
+pub const PRIZES: Map<(u32, &str, u64), Prize> = Map::new("prizes");
+
+/// cw721 contracts allowed to be used for prizes
+pub const PRIZE_COLLECTIONS: Map<&Addr, bool> = Map::new("prize collections");
+
+/// number of escrowed prizes, keyed by (mystery box id, item_type name)
+pub const PRIZE_COUNTS: Map<(u32, &str), u64> = Map::new("prize counts");
+
+/// consecutive opens without getting an item_type or rarer, keyed by (mystery box id, wallet, item_type name)
+pub const PITY_COUNTERS: Map<(u32, &Addr, &str), u32> = Map::new("pity counters");
 
 #[cfg(test)]
 mod unit_tests {
     use super::*;
+    use cosmwasm_std::testing::MockStorage;
+    use crate::utils::SplitMix64;
+
+    // token ids left in a pool
+    fn pool_tokens(swaps: &PoolSwaps, pool: &str, remaining: u64) -> Vec<u64> {
+        (0..remaining).map(|slot| swaps.get(pool, slot).unwrap()).collect()
+    }
 
     #[test]
     fn test() {
@@ -301,312 +1285,710 @@ mod unit_tests {
         print!("{:?}",b);
     }
 
-    /* #[test]
-    fn test_sort_rarity() {
-        let mut vecs: Vec<Rarity> = Vec::new();
-        
-        vecs.push(Rarity{
-            name: "limited".to_string(),
-            rate: Decimal::from_str("0.09").unwrap(),
-            supply: 990u32,
-            slip_rate: 1,
-        });
-        
-        vecs.push(Rarity{
-            name: "common".to_string(),
-            rate: Decimal::from_str("0.90").unwrap(),
-            supply: 9000u32,
-            slip_rate: 0,
-        });
+    fn new_mystery_box(replacement: bool) -> MysteryBox {
+        MysteryBox {
+            id: 0,
+            name: String::from("event"),
+            description: String::from("description"),
+            start: Scheduled::AtTime(Timestamp::from_seconds(0)),
+            end: Scheduled::AtTime(Timestamp::from_seconds(100)),
+            rate_distribution: Some(RateDistribution::new(RateDistributionMsg {
+                vec: vec![crate::msg::ItemTypeMsg {
+                    name: String::from("rare"),
+                    rate: Decimal::from_str("0.1").unwrap(),
+                    slip_rate: 1,
+                    supply: 10,
+                    pity: None,
+                    prefix_uri: None,
+                    total_supply: None,
+                    reward: None,
+                    mint_item: None,
+                    image: None,
+                    attributes: None,
+                }],
+                default_type: None,
+                rate_curve: None,
+                mode: None,
+                items_per_box: None,
+                guaranteed_type: None,
+            }, 5).unwrap()),
+            prefix_uri: Some(String::from("ipfs://prefix/")),
+            total_supply: 5,
+            remaining_tokens: 5,
+            max_minted_box: 5,
+            minted_box: 0,
+            bought_back_box: 0,
+            replacement,
+            price: Coin::new(1, "uaura"),
+            reroll_fee: None,
+            royalty: None,
+            item_metadata: None,
+            box_image: None,
+            minted_item: 0,
+            created_time: Timestamp::from_seconds(0),
+            has_sold: false,
+            has_opened: false,
+            placeholder_uri: None,
+            provenance_hash: None,
+        }
+    }
 
-        vecs.push(Rarity{
-            name: "rare".to_string(),
-            rate: Decimal::from_str("0.01").unwrap(),
-            supply: 10u32,
-            slip_rate: 1,
-        });
+    #[test]
+    fn draw_success() {
+        let storage = MockStorage::new();
+        let mut swaps = PoolSwaps::new(&storage, 0);
+        let mut mystery_box = new_mystery_box(true);
+
+        // 9950 is in range of 'rare' (9000..10000)
+        let draw = mystery_box.draw(&mut swaps, 9950, 7, 10000, &mut [0, 0], &mut [0, 0]).unwrap();
+        assert_eq!(draw.item_type, String::from("rare"));
+        assert_eq!(draw.token_index, 2);
+        assert_eq!(draw.token_uri, String::from("ipfs://prefix/2"));
+        assert_eq!(pool_tokens(&swaps, SHARED_POOL, mystery_box.remaining_tokens), vec![0, 1, 4, 3]);
+        assert_eq!(mystery_box.rate_distribution.unwrap().vec[0].supply, 9);
+
+        // without replacement, tokens id are kept
+        let mut swaps = PoolSwaps::new(&storage, 0);
+        let mut mystery_box = new_mystery_box(false);
+        let draw = mystery_box.draw(&mut swaps, 0, 0, 10000, &mut [0, 0], &mut [0, 0]).unwrap();
+        assert_eq!(draw.item_type, String::from("common"));
+        assert_eq!(mystery_box.remaining_tokens, 5);
+    }
 
-        let mut dist: RarityDistribution = RarityDistribution {
-            vecs
-        };
+    #[test]
+    fn reward_only_draw_keeps_uri() {
+        let storage = MockStorage::new();
+        let mut swaps = PoolSwaps::new(&storage, 0);
+        let mut mystery_box = new_mystery_box(true);
+        let mut rate_distribution = mystery_box.rate_distribution.clone().unwrap();
+        rate_distribution.vec[0].reward = Some(Reward::Native { amount: Coin::new(10, "uaura") });
+        rate_distribution.vec[0].mint_item = false;
+        mystery_box.rate_distribution = Some(rate_distribution);
+
+        // no item is minted, so no token id is taken from shared pool
+        let draw = mystery_box.draw(&mut swaps, 9950, 7, 10000, &mut [0, 0], &mut [0, 0]).unwrap();
+        assert_eq!(draw.item_type, String::from("rare"));
+        assert!(!draw.mint_item);
+        assert_eq!(mystery_box.remaining_tokens, 5);
+
+        // a prefix uri is not needed either
+        mystery_box.prefix_uri = None;
+        mystery_box.draw(&mut swaps, 9950, 7, 10000, &mut [0, 0], &mut [0, 0]).unwrap();
+        assert_eq!(mystery_box.remaining_tokens, 5);
+    }
 
-        assert_eq!(dist.vecs, vec![
-            Rarity{
-                name: "limited".to_string(),
-                rate: Decimal::from_str("0.09").unwrap(),
-                supply: 990u32,
-                slip_rate: 1,
-            },Rarity{
-                name: "common".to_string(),
-                rate: Decimal::from_str("0.90").unwrap(),
-                supply: 9000u32,
-                slip_rate: 0,
-            },Rarity{
-                name: "rare".to_string(),
-                rate: Decimal::from_str("0.01").unwrap(),
-                supply: 10u32,
-                slip_rate: 1,
-            }
-        ]);
+    #[test]
+    fn prize_draw_keeps_uri() {
+        let storage = MockStorage::new();
+        let mut swaps = PoolSwaps::new(&storage, 0);
+        let mut mystery_box = new_mystery_box(true);
+        mystery_box.remaining_tokens = 0;
+
+        // shared pool is used up
+        let err = mystery_box.clone().draw(&mut swaps, 9950, 7, 10000, &mut [0, 0], &mut [0, 0]).unwrap_err();
+        assert_eq!(err, ContractError::SoldOut{});
+
+        // escrowed prize is drawn without a token uri
+        let mut prize_counts = [1, 0];
+        let draw = mystery_box.draw(&mut swaps, 9950, 7, 10000, &mut [0, 0], &mut prize_counts).unwrap();
+        assert_eq!(draw.item_type, String::from("rare"));
+        assert!(draw.prize);
+        assert_eq!(draw.token_uri, String::new());
+        assert_eq!(prize_counts, [0, 0]);
+        assert_eq!(mystery_box.rate_distribution.unwrap().vec[0].supply, 9);
+    }
 
-        dist.sort_rarity();
+    #[test]
+    fn draw_with_placeholder_uri() {
+        let storage = MockStorage::new();
+        let mut swaps = PoolSwaps::new(&storage, 0);
+        let mut mystery_box = new_mystery_box(true);
+        mystery_box.prefix_uri = None;
+
+        // neither prefix uri nor placeholder uri is set
+        let err = mystery_box.clone().draw(&mut swaps, 9950, 7, 10000, &mut [0, 0], &mut [0, 0]).unwrap_err();
+        assert_eq!(err, ContractError::MysteryBoxNotUpdated{});
+
+        // token index is drawn as usual, only uri is hidden
+        mystery_box.placeholder_uri = Some(String::from("ipfs://placeholder"));
+        let draw = mystery_box.draw(&mut swaps, 9950, 7, 10000, &mut [0, 0], &mut [0, 0]).unwrap();
+        assert_eq!(draw.token_index, 2);
+        assert_eq!(draw.token_uri, String::from("ipfs://placeholder"));
+        assert!(draw.hidden);
+    }
 
-        assert_eq!(dist.vecs, vec![
-            Rarity{
-                name: "rare".to_string(),
-                rate: Decimal::from_str("0.01").unwrap(),
-                supply: 10u32,
-                slip_rate: 1,
-            },Rarity{
-                name: "limited".to_string(),
-                rate: Decimal::from_str("0.09").unwrap(),
-                supply: 990u32,
-                slip_rate: 1,
-            },Rarity{
-                name: "common".to_string(),
-                rate: Decimal::from_str("0.90").unwrap(),
-                supply: 9000u32,
+    #[test]
+    fn rate_curve_validate() {
+        assert!(RateCurve::default().validate().is_ok());
+        assert!(RateCurve::Linear{}.validate().is_ok());
+        assert!(RateCurve::Constant{}.validate().is_ok());
+        assert!(RateCurve::Exponential{ base: Decimal::from_str("0.9").unwrap() }.validate().is_ok());
+
+        // e ^ max_exponent overflow
+        assert!(RateCurve::Sigmoid{ 
+            e: Decimal::from_str(E).unwrap(), 
+            exponent: RATE_MODIFY_EXPONENT, 
+            max_exponent: MAX_EXPONENT + 1 
+        }.validate().is_err());
+
+        // e must be greater than 1
+        assert!(RateCurve::Sigmoid{ e: Decimal::one(), exponent: 2, max_exponent: 10 }.validate().is_err());
+
+        // base must be in range 0..1
+        assert!(RateCurve::Exponential{ base: Decimal::one() }.validate().is_err());
+        assert!(RateCurve::Exponential{ base: Decimal::zero() }.validate().is_err());
+    }
+
+    #[test]
+    fn rate_curve_modifier() {
+        let linear = RateCurve::Linear{};
+        assert_eq!(linear.rate_modifier(5, 10, 1).unwrap(), Decimal::from_str("0.5").unwrap());
+        assert_eq!(linear.rate_modifier(0, 10, 1).unwrap(), Decimal::zero());
+
+        let exponential = RateCurve::Exponential{ base: Decimal::from_str("0.5").unwrap() };
+        assert_eq!(exponential.rate_modifier(10, 10, 1).unwrap(), Decimal::one());
+        assert_eq!(exponential.rate_modifier(8, 10, 1).unwrap(), Decimal::from_str("0.25").unwrap());
+        assert_eq!(exponential.rate_modifier(8, 10, 2).unwrap(), Decimal::from_str("0.5").unwrap());
+
+        let constant = RateCurve::Constant{};
+        assert_eq!(constant.rate_modifier(1, 10, 1).unwrap(), Decimal::one());
+
+        // sigmoid modifier increases with supply
+        let sigmoid = RateCurve::default();
+        assert!(sigmoid.rate_modifier(1, 100, 1).unwrap() < sigmoid.rate_modifier(50, 100, 1).unwrap());
+        assert_eq!(sigmoid.rate_modifier(10, 100, 0).unwrap(), Decimal::one());
+    }
+
+    #[test]
+    fn deck_draws_exact_counts() {
+        let mut rate_distribution = RateDistribution::new(RateDistributionMsg {
+            vec: vec![crate::msg::ItemTypeMsg {
+                name: String::from("legendary"),
+                rate: Decimal::zero(),
                 slip_rate: 0,
+                supply: 2,
+                pity: None,
+                prefix_uri: None,
+                total_supply: None,
+                reward: None,
+                mint_item: None,
+                image: None,
+                attributes: None,
+            }],
+            default_type: None,
+            rate_curve: None,
+            mode: Some(DrawMode::Deck),
+            items_per_box: None,
+            guaranteed_type: None,
+        }, 10).unwrap();
+
+        assert_eq!(rate_distribution.vec[0].name, String::from("legendary"));
+        assert_eq!(rate_distribution.vec[0].rate, Decimal::from_ratio(2u32, 10u32));
+        assert_eq!(rate_distribution.vec[1].supply, 8);
+
+        // draw the whole deck, every slot is used exactly once
+        let mut legendary = 0;
+        for i in 0..10u128 {
+            let index = rate_distribution.get_deck_index(random_u128(&[i])).unwrap();
+            if index == 0 {
+                legendary += 1;
             }
-        ]);
-
-        assert_eq!(dist.check_rate().unwrap(), true);
+            rate_distribution.update_item_type(index).unwrap();
+        }
+        assert_eq!(legendary, 2);
+        assert!(rate_distribution.vec.iter().all(|item_type| item_type.supply == 0));
 
-        assert_eq!(dist.get_rarity(Uint256::from_u128(1000u128), Uint256::from_u128(10000u128)).unwrap(), (2, Rarity{
-            name: "common".to_string(),
-            rate: Decimal::from_str("0.90").unwrap(),
-            supply: 9000u32,
-            slip_rate: 0,
-        }));
+        match rate_distribution.get_deck_index(0).unwrap_err() {
+            ContractError::SoldOut{} => {},
+            _ => panic!(),
+        }
     }
 
     #[test]
-    fn test_check_rate_true() {
-        let mut vecs: Vec<Rarity> = Vec::new();
-        
-        vecs.push(Rarity{
-            name: "limited".to_string(),
-            rate: Decimal::from_str("0.09").unwrap(),
-            supply: 990u32,
-            slip_rate: 1,
-        });
-        
-        vecs.push(Rarity{
-            name: "common".to_string(),
-            rate: Decimal::from_str("0.90").unwrap(),
-            supply: 9000u32,
-            slip_rate: 0,
-        });
+    fn deck_fail_with_invalid_size() {
+        let msg = RateDistributionMsg {
+            vec: vec![crate::msg::ItemTypeMsg {
+                name: String::from("legendary"),
+                rate: Decimal::zero(),
+                slip_rate: 0,
+                supply: 11,
+                pity: None,
+                prefix_uri: None,
+                total_supply: None,
+                reward: None,
+                mint_item: None,
+                image: None,
+                attributes: None,
+            }],
+            default_type: None,
+            rate_curve: None,
+            mode: Some(DrawMode::Deck),
+            items_per_box: None,
+            guaranteed_type: None,
+        };
 
-        vecs.push(Rarity{
-            name: "rare".to_string(),
-            rate: Decimal::from_str("0.01").unwrap(),
-            supply: 10u32,
-            slip_rate: 1,
-        });
+        // counts greater than deck size
+        match RateDistribution::new(msg.clone(), 10).unwrap_err() {
+            ContractError::InvalidDeckSize{} => {},
+            _ => panic!(),
+        }
 
-        let dist: RarityDistribution = RarityDistribution {
-            vecs
-        };
+        // unlimited number of boxes
+        match RateDistribution::new(msg, u64::MAX).unwrap_err() {
+            ContractError::InvalidDeckSize{} => {},
+            _ => panic!(),
+        }
+    }
 
-        assert_eq!(dist.check_rate().unwrap(), true);
+    #[test]
+    fn pity_forces_item_type() {
+        let rate_distribution = RateDistribution::new(RateDistributionMsg {
+            vec: vec![crate::msg::ItemTypeMsg {
+                name: String::from("legendary"),
+                rate: Decimal::from_str("0.01").unwrap(),
+                slip_rate: 0,
+                supply: 10,
+                pity: Some(3),
+                prefix_uri: None,
+                total_supply: None,
+                reward: None,
+                mint_item: None,
+                image: None,
+                attributes: None,
+            },crate::msg::ItemTypeMsg {
+                name: String::from("rare"),
+                rate: Decimal::from_str("0.1").unwrap(),
+                slip_rate: 0,
+                supply: 10,
+                pity: None,
+                prefix_uri: None,
+                total_supply: None,
+                reward: None,
+                mint_item: None,
+                image: None,
+                attributes: None,
+            }],
+            default_type: None,
+            rate_curve: None,
+            mode: None,
+            items_per_box: None,
+            guaranteed_type: None,
+        }, 100).unwrap();
+
+        // two unlucky opens increase the counter
+        let mut counters = [0u32, 0, 0];
+        assert_eq!(rate_distribution.apply_pity(2, &mut counters), 2);
+        assert_eq!(rate_distribution.apply_pity(1, &mut counters), 1);
+        assert_eq!(counters, [2, 0, 0]);
+
+        // third open is forced to legendary and counter is reset
+        assert_eq!(rate_distribution.apply_pity(2, &mut counters), 0);
+        assert_eq!(counters, [0, 0, 0]);
     }
 
     #[test]
-    fn test_check_rate_false() {
-        let mut vecs: Vec<Rarity> = Vec::new();
-        
-        vecs.push(Rarity{
-            name: "limited".to_string(),
+    fn draw_from_item_type_pool() {
+        let mut mystery_box = new_mystery_box(true);
+        mystery_box.rate_distribution = Some(RateDistribution::new(RateDistributionMsg {
+            vec: vec![crate::msg::ItemTypeMsg {
+                name: String::from("rare"),
+                rate: Decimal::from_str("0.1").unwrap(),
+                slip_rate: 0,
+                supply: 10,
+                pity: None,
+                prefix_uri: Some(String::from("ipfs://rare/")),
+                total_supply: Some(2),
+                reward: None,
+                mint_item: None,
+                image: None,
+                attributes: None,
+            }],
+            default_type: None,
+            rate_curve: None,
+            mode: None,
+            items_per_box: None,
+            guaranteed_type: None,
+        }, 5).unwrap());
+
+        let storage = MockStorage::new();
+        let mut swaps = PoolSwaps::new(&storage, 0);
+        let draw = mystery_box.draw(&mut swaps, 9950, 3, 10000, &mut [0, 0], &mut [0, 0]).unwrap();
+        assert_eq!(draw.token_uri, String::from("ipfs://rare/1"));
+        // shared pool is not used
+        assert_eq!(mystery_box.remaining_tokens, 5);
+
+        // pool runs out, supply of item_type is kept
+        let draw = mystery_box.draw(&mut swaps, 9950, 3, 10000, &mut [0, 0], &mut [0, 0]).unwrap();
+        assert_eq!(draw.token_uri, String::from("ipfs://rare/0"));
+        let rare = mystery_box.rate_distribution.clone().unwrap().vec[0].clone();
+        assert!(rare.is_pool_exhausted());
+        assert_eq!(rare.supply, 8);
+        assert_eq!(rare.drawable_supply(), 0);
+
+        // next draw falls to default item_type
+        let draw = mystery_box.draw(&mut swaps, 9950, 3, 10000, &mut [0, 0], &mut [0, 0]).unwrap();
+        assert_eq!(draw.item_type, String::from("common"));
+        assert_eq!(draw.token_uri, String::from("ipfs://prefix/3"));
+
+        // returned item refills the pool and restores supply from where it was
+        mystery_box.return_item(&mut swaps, "rare", 0).unwrap();
+        let rare = mystery_box.rate_distribution.clone().unwrap().vec[0].clone();
+        assert!(!rare.is_pool_exhausted());
+        assert_eq!(rare.supply, 9);
+        let draw = mystery_box.draw(&mut swaps, 9950, 3, 10000, &mut [0, 0], &mut [0, 0]).unwrap();
+        assert_eq!(draw.token_uri, String::from("ipfs://rare/0"));
+
+        // fused item takes no token uri and does not change supply, even if pool of its item_type runs out
+        let draw = mystery_box.fuse_item("rare").unwrap();
+        assert_eq!(draw.token_uri, String::new());
+        assert_eq!(mystery_box.rate_distribution.clone().unwrap().vec[0].supply, 8);
+        assert!(mystery_box.rate_distribution.clone().unwrap().vec[0].is_pool_exhausted());
+
+        // rarity which is not an item_type has no purity
+        let draw = mystery_box.fuse_item("epic").unwrap();
+        assert_eq!(draw.purity, Decimal::zero());
+    }
+
+    #[test]
+    fn reward_liabilities_merge_same_asset() {
+        let item_type = |name: &str, supply: u32, reward: Reward| crate::msg::ItemTypeMsg {
+            name: String::from(name),
             rate: Decimal::from_str("0.1").unwrap(),
-            supply: 990u32,
-            slip_rate: 1,
-        });
-        
-        vecs.push(Rarity{
-            name: "common".to_string(),
-            rate: Decimal::from_str("0.90").unwrap(),
-            supply: 9000u32,
             slip_rate: 0,
-        });
-
-        vecs.push(Rarity{
-            name: "rare".to_string(),
-            rate: Decimal::from_str("0.01").unwrap(),
-            supply: 10u32,
-            slip_rate: 1,
-        });
-
-        let dist: RarityDistribution = RarityDistribution {
-            vecs
+            supply,
+            pity: None,
+            prefix_uri: None,
+            total_supply: None,
+            reward: Some(reward),
+            mint_item: Some(false),
+            image: None,
+            attributes: None,
         };
 
-        assert_eq!(dist.check_rate().unwrap(), false);
+        let rate_distribution = RateDistribution::new(RateDistributionMsg {
+            vec: vec![
+                item_type("jackpot", 1, Reward::Native { amount: Coin::new(1000, "uaura") }),
+                item_type("small", 10, Reward::Native { amount: Coin::new(10, "uaura") }),
+                item_type("token", 5, Reward::Cw20 { contract_addr: String::from("cw20"), amount: Uint128::new(7) }),
+            ],
+            default_type: None,
+            rate_curve: None,
+            mode: None,
+            items_per_box: None,
+            guaranteed_type: None,
+        }, 100).unwrap();
+
+        let liabilities = rate_distribution.reward_liabilities().unwrap();
+        assert_eq!(liabilities.len(), 2);
+        assert!(liabilities.contains(&Reward::Native { amount: Coin::new(1100, "uaura") }));
+        assert!(liabilities.contains(&Reward::Cw20 { contract_addr: String::from("cw20"), amount: Uint128::new(35) }));
     }
 
     #[test]
-    fn test_total_supply() {
-        let mut vecs: Vec<Rarity> = Vec::new();
-        
-        vecs.push(Rarity{
-            name: "limited".to_string(),
-            rate: Decimal::from_str("0.1").unwrap(),
-            supply: 990u32,
-            slip_rate: 1,
-        });
-        
-        vecs.push(Rarity{
-            name: "common".to_string(),
-            rate: Decimal::from_str("0.90").unwrap(),
-            supply: 9000u32,
-            slip_rate: 0,
-        });
+    fn draw_bundle_with_guaranteed_type() {
+        let mut mystery_box = new_mystery_box(false);
+        let mut rate_distribution = mystery_box.rate_distribution.clone().unwrap();
+        rate_distribution.items_per_box = 3;
+        rate_distribution.guaranteed_type = Some(String::from("rare"));
+        mystery_box.rate_distribution = Some(rate_distribution);
+
+        let storage = MockStorage::new();
+        let mut swaps = PoolSwaps::new(&storage, 0);
+
+        // all random numbers land on 'common', last item is forced to 'rare'
+        let draws = mystery_box.draw_bundle(&mut swaps, &[(0, 0), (0, 1), (0, 2)], 10000, &mut [0, 0], &mut [0, 0]).unwrap();
+        let types: Vec<String> = draws.into_iter().map(|draw| draw.item_type).collect();
+        assert_eq!(types, vec![String::from("common"), String::from("common"), String::from("rare")]);
+
+        // guarantee met by first item, last item is not forced
+        let draws = mystery_box.draw_bundle(&mut swaps, &[(9950, 0), (0, 1), (0, 2)], 10000, &mut [0, 0], &mut [0, 0]).unwrap();
+        let types: Vec<String> = draws.into_iter().map(|draw| draw.item_type).collect();
+        assert_eq!(types, vec![String::from("rare"), String::from("common"), String::from("common")]);
+    }
 
-        vecs.push(Rarity{
-            name: "rare".to_string(),
-            rate: Decimal::from_str("0.01").unwrap(),
-            supply: 10u32,
-            slip_rate: 1,
-        });
+    #[test]
+    fn return_item_restores_supply() {
+        let storage = MockStorage::new();
+        let mut swaps = PoolSwaps::new(&storage, 0);
+        let mut mystery_box = new_mystery_box(true);
+        let before = mystery_box.clone();
 
-        let dist: RarityDistribution = RarityDistribution {
-            vecs
-        };
+        let draw = mystery_box.draw(&mut swaps, 9950, 7, 10000, &mut [0, 0], &mut [0, 0]).unwrap();
+        assert_eq!(draw.item_type, String::from("rare"));
+
+        mystery_box.return_item(&mut swaps, &draw.item_type, draw.token_index).unwrap();
+
+        let rate_distribution = mystery_box.rate_distribution.clone().unwrap();
+        let before_distribution = before.rate_distribution.clone().unwrap();
+        assert_eq!(rate_distribution.vec, before_distribution.vec);
 
-        assert_eq!(dist.total_supply(), 10000u64);
+        // returned token id is back in the pool
+        let mut tokens_id = pool_tokens(&swaps, SHARED_POOL, mystery_box.remaining_tokens);
+        tokens_id.sort();
+        assert_eq!(tokens_id, vec![0, 1, 2, 3, 4]);
+
+        // supply can not be restored above max supply
+        assert!(mystery_box.return_item(&mut swaps, &draw.item_type, draw.token_index).is_err());
     }
 
     #[test]
-    fn test_total_supply_success_with_big_supply() {
-        let mut vecs: Vec<Rarity> = Vec::new();
-        
-        vecs.push(Rarity{
-            name: "limited".to_string(),
-            rate: Decimal::from_str("0.09").unwrap(),
-            supply: u32::MAX,
-            slip_rate: 1,
-        });
-        
-        vecs.push(Rarity{
-            name: "common".to_string(),
-            rate: Decimal::from_str("0.90").unwrap(),
-            supply: u32::MAX,
-            slip_rate: 0,
-        });
-
-        vecs.push(Rarity{
-            name: "rare".to_string(),
-            rate: Decimal::from_str("0.01").unwrap(),
-            supply: u32::MAX,
-            slip_rate: 1,
-        });
+    fn pool_swaps_take_each_token_once() {
+        let mut storage = MockStorage::new();
+        let mut remaining = 100u64;
+        let mut taken: Vec<u64> = Vec::new();
+
+        // each take is saved to storage like a separate transaction
+        for i in 0..100usize {
+            let mut swaps = PoolSwaps::new(&storage, 0);
+            taken.push(swaps.take("rare", &mut remaining, i * 7919, true).unwrap());
+            swaps.into_changes().save(&mut storage).unwrap();
+        }
 
-        let dist: RarityDistribution = RarityDistribution {
-            vecs
-        };
+        taken.sort();
+        assert_eq!(taken, (0..100).collect::<Vec<u64>>());
+        assert!(PoolSwaps::new(&storage, 0).take("rare", &mut remaining, 0, true).is_err());
 
-        assert_eq!(dist.total_supply(), 12_884_901_885);
+        // all slots are back to identity, nothing is left in storage
+        assert_eq!(POOL_SWAPS.range(&storage, None, None, cosmwasm_std::Order::Ascending).count(), 0);
     }
 
     #[test]
-    fn test_get_rarity() {
-        let mut vecs: Vec<Rarity> = Vec::new();
-        
-        vecs.push(Rarity{
-            name: "limited".to_string(),
-            rate: Decimal::from_str("0.09").unwrap(),
-            supply: 990u32,
-            slip_rate: 1,
-        });
-        
-        vecs.push(Rarity{
-            name: "common".to_string(),
-            rate: Decimal::from_str("0.90").unwrap(),
-            supply: 9000u32,
-            slip_rate: 0,
-        });
+    fn box_buyback_payout() {
+        let purchase_price = Coin::new(150, "uaura");
+
+        let fixed = BoxBuyback::Fixed { price: Coin::new(100, "uaura") };
+        assert!(fixed.validate().is_ok());
+        assert_eq!(fixed.payout(&purchase_price).unwrap(), Coin::new(100, "uaura"));
+
+        let percentage = BoxBuyback::Percentage { percentage: Decimal::from_str("0.5").unwrap() };
+        assert!(percentage.validate().is_ok());
+        assert_eq!(percentage.payout(&purchase_price).unwrap(), Coin::new(75, "uaura"));
+
+        // contract never pays more than the box price
+        let fixed = BoxBuyback::Fixed { price: Coin::new(200, "uaura") };
+        assert_eq!(fixed.payout(&purchase_price).unwrap(), Coin::new(150, "uaura"));
+        let fixed = BoxBuyback::Fixed { price: Coin::new(100, "uatom") };
+        assert!(fixed.payout(&purchase_price).is_err());
+        assert!(BoxBuyback::Percentage { percentage: Decimal::from_str("1.1").unwrap() }.validate().is_err());
+        assert!(BoxBuyback::Fixed { price: Coin::new(0, "uaura") }.validate().is_err());
+    }
 
-        vecs.push(Rarity{
-            name: "rare".to_string(),
-            rate: Decimal::from_str("0.01").unwrap(),
-            supply: 10u32,
-            slip_rate: 1,
-        });
+    #[test]
+    fn fusion_recipe() {
+        let output = |rarity: &str, rate: &str| FusionOutput {
+            rarity: String::from(rarity),
+            rate: Decimal::from_str(rate).unwrap(),
+        };
 
-        let mut dist: RarityDistribution = RarityDistribution {
-            vecs
+        let recipe = FusionRecipe {
+            input_count: 3,
+            outputs: vec![output("rare", "0.75"), output("legendary", "0.25")],
         };
+        assert!(recipe.validate().is_ok());
 
-        dist.sort_rarity();
+        assert_eq!(choose_fusion_output(&recipe.outputs, 0, 10000).unwrap().rarity, String::from("rare"));
+        assert_eq!(choose_fusion_output(&recipe.outputs, 7499, 10000).unwrap().rarity, String::from("rare"));
+        assert_eq!(choose_fusion_output(&recipe.outputs, 7500, 10000).unwrap().rarity, String::from("legendary"));
+        assert_eq!(choose_fusion_output(&recipe.outputs, 10000, 10000).unwrap().rarity, String::from("legendary"));
 
-        let rarity = dist.get_rarity(Uint256::from_u128(1u128), Uint256::from_u128(10000u128)).unwrap();
-        assert_eq!(rarity, (2,Rarity{
-            name: "common".to_string(),
-            rate: Decimal::from_str("0.90").unwrap(),
-            supply: 9000u32,
-            slip_rate: 0,
-        }));
+        // rates must sum to 1
+        assert!(FusionRecipe { input_count: 3, outputs: vec![output("rare", "0.5")] }.validate().is_err());
+        // at least 2 items are fused
+        assert!(FusionRecipe { input_count: 1, outputs: vec![output("rare", "1")] }.validate().is_err());
+    }
 
-        let rarity = dist.get_rarity(Uint256::from_u128(9000u128), Uint256::from_u128(10000u128)).unwrap();
-        assert_eq!(rarity, (1,Rarity{
-            name: "limited".to_string(),
-            rate: Decimal::from_str("0.09").unwrap(),
-            supply: 990u32,
-            slip_rate: 1,
-        }));
+    const MAX_RANGE: u128 = 10000u128;
+
+    fn item_type_msg(name: &str, rate: Decimal, supply: u32, slip_rate: u32) -> crate::msg::ItemTypeMsg {
+        crate::msg::ItemTypeMsg {
+            name: String::from(name),
+            rate,
+            slip_rate,
+            supply,
+            pity: None,
+            prefix_uri: None,
+            total_supply: None,
+            reward: None,
+            mint_item: None,
+            image: None,
+            attributes: None,
+        }
+    }
 
-        let rarity = dist.get_rarity(Uint256::from_u128(9900u128), Uint256::from_u128(10000u128)).unwrap();
-        assert_eq!(rarity, (0,Rarity{
-            name: "rare".to_string(),
-            rate: Decimal::from_str("0.01").unwrap(),
-            supply: 10u32,
-            slip_rate: 1,
-        }));
+    fn rate_distribution(vec: Vec<crate::msg::ItemTypeMsg>, rate_curve: RateCurve) -> RateDistribution {
+        RateDistribution::new(RateDistributionMsg {
+            vec,
+            default_type: None,
+            rate_curve: Some(rate_curve),
+            mode: None,
+            items_per_box: None,
+            guaranteed_type: None,
+        }, 0).unwrap()
+    }
 
+    // number of random values in 0..=MAX_RANGE choosing each item_type, default item_type gets the rest
+    fn range_widths(rate_distribution: &RateDistribution) -> Vec<u128> {
+        let max_range = Decimal::from_ratio(MAX_RANGE, 1u128);
+        let mut widths: Vec<u128> = rate_distribution.vec[..rate_distribution.vec.len() - 1].iter()
+            .map(|item_type| (max_range * item_type.rate).to_uint_floor().u128())
+            .collect();
+        widths.push(MAX_RANGE + 1 - widths.iter().sum::<u128>());
+        widths
     }
 
     #[test]
-    fn test_update_rarity() {
-        let mut vecs: Vec<Rarity> = Vec::new();
-        
-        vecs.push(Rarity{
-            name: "limited".to_string(),
-            rate: Decimal::from_str("0.09").unwrap(),
-            supply: 990u32,
-            slip_rate: 1,
-        });
-        
-        vecs.push(Rarity{
-            name: "common".to_string(),
-            rate: Decimal::from_str("0.90").unwrap(),
-            supply: 9000u32,
-            slip_rate: 0,
-        });
+    fn item_type_ranges_match_rates() {
+        let rate_distribution = rate_distribution(vec![
+            item_type_msg("legendary", Decimal::from_str("0.05").unwrap(), 10, 1),
+            item_type_msg("epic", Decimal::from_str("0.15").unwrap(), 10, 1),
+            item_type_msg("rare", Decimal::from_str("0.3").unwrap(), 10, 1),
+        ], RateCurve::Constant{});
+
+        // every random value chooses exactly one item_type
+        let mut counts = vec![0u128; rate_distribution.vec.len()];
+        for random in 0..=MAX_RANGE {
+            counts[rate_distribution.get_item_type_index(random, MAX_RANGE).unwrap()] += 1;
+        }
 
-        vecs.push(Rarity{
-            name: "rare".to_string(),
-            rate: Decimal::from_str("0.01").unwrap(),
-            supply: 10u32,
-            slip_rate: 1,
-        });
+        // default item_type absorbs the remainder, including MAX_RANGE itself
+        assert_eq!(counts, vec![500, 1500, 3000, 5001]);
+        assert_eq!(counts, range_widths(&rate_distribution));
+    }
 
-        let mut dist: RarityDistribution = RarityDistribution {
-            vecs
-        };
+    #[test]
+    fn monte_carlo_frequencies_match_constant_rates() {
+        let mut rate_distribution = rate_distribution(vec![
+            item_type_msg("legendary", Decimal::from_str("0.01").unwrap(), 1_000_000, 1),
+            item_type_msg("epic", Decimal::from_str("0.09").unwrap(), 1_000_000, 1),
+            item_type_msg("rare", Decimal::from_str("0.25").unwrap(), 1_000_000, 1),
+        ], RateCurve::Constant{});
+        let probabilities: Vec<f64> = range_widths(&rate_distribution).iter()
+            .map(|width| *width as f64 / (MAX_RANGE + 1) as f64)
+            .collect();
+
+        let draws = 20_000u32;
+        let mut rng = SplitMix64(42);
+        let mut counts = vec![0u32; rate_distribution.vec.len()];
+        for _ in 0..draws {
+            let index = rate_distribution.get_item_type_index(rng.range(MAX_RANGE as u64) as u128, MAX_RANGE).unwrap();
+            rate_distribution.update_item_type(index).unwrap();
+            counts[index] += 1;
+        }
 
-        dist.update_rarity(0, 100).unwrap();
+        // each observed frequency is within 4 standard deviations of its rate
+        for (count, p) in counts.iter().zip(probabilities.iter()) {
+            let expected = draws as f64 * p;
+            let sigma = (draws as f64 * p * (1.0 - p)).sqrt();
+            assert!((*count as f64 - expected).abs() <= 4.0 * sigma, "count {} expected {}", count, expected);
+        }
+    }
 
-        assert_eq!(dist.vecs[0].supply, 890);
-        assert_eq!(dist.vecs[0].rate, Decimal::from_str("0.09").unwrap());
+    #[test]
+    fn monte_carlo_frequencies_follow_decreasing_rates() {
+        for seed in 0..5u64 {
+            let mut rate_distribution = rate_distribution(vec![
+                item_type_msg("legendary", Decimal::from_str("0.005").unwrap(), 5, 1),
+                item_type_msg("epic", Decimal::from_str("0.02").unwrap(), 30, 1),
+                item_type_msg("rare", Decimal::from_str("0.1").unwrap(), 200, 5),
+            ], RateCurve::default());
+            let len = rate_distribution.vec.len();
+
+            // rates change after every draw, so compare counts with the sum of probabilities at each draw
+            let mut rng = SplitMix64(seed);
+            let mut counts = vec![0u32; len];
+            let mut expected = vec![0f64; len];
+            let mut variance = vec![0f64; len];
+            for _ in 0..10_000 {
+                for (i, width) in range_widths(&rate_distribution).iter().enumerate() {
+                    let p = *width as f64 / (MAX_RANGE + 1) as f64;
+                    expected[i] += p;
+                    variance[i] += p * (1.0 - p);
+                }
+
+                let index = rate_distribution.get_item_type_index(rng.range(MAX_RANGE as u64) as u128, MAX_RANGE).unwrap();
+                rate_distribution.update_item_type(index).unwrap();
+                counts[index] += 1;
+            }
 
-        dist.update_rarity(0, 885).unwrap();
+            for i in 0..len {
+                assert!(
+                    (counts[i] as f64 - expected[i]).abs() <= 4.0 * variance[i].sqrt() + 1.0,
+                    "seed {} item_type {} count {} expected {}", seed, i, counts[i], expected[i]
+                );
+            }
 
-        assert_eq!(dist.vecs[0].supply, 5);
-        assert!(dist.vecs[0].rate < Decimal::from_str("0.09").unwrap());
+            // supply limits number of drawn items
+            for (item_type, count) in rate_distribution.vec[..len - 1].iter().zip(counts.iter()) {
+                assert_eq!(item_type.supply + count, item_type.max_supply);
+            }
+        }
+    }
 
-        dist.update_rarity(0, 5).unwrap();
+    #[test]
+    fn random_distributions_keep_invariants() {
+        let mut rng = SplitMix64(2023);
+        for _ in 0..200 {
+            // rates are multiples of 0.0001 and sum to at most 1
+            let number_of_types = 1 + rng.range(4) as usize;
+            let mut budget = MAX_RANGE as u64 - 1;
+            let mut vec = Vec::new();
+            for i in 0..number_of_types {
+                if budget == 0 {
+                    break;
+                }
+                let units = 1 + rng.range(budget.min(MAX_RANGE as u64 / 2) - 1);
+                budget -= units;
+                vec.push(item_type_msg(
+                    &format!("type{}", i),
+                    Decimal::from_ratio(units, MAX_RANGE),
+                    1 + rng.range(49) as u32,
+                    rng.range(5) as u32,
+                ));
+            }
+            let rate_curve = match rng.range(3) {
+                0 => RateCurve::default(),
+                1 => RateCurve::Linear{},
+                2 => RateCurve::Exponential{ base: Decimal::from_ratio(50 + rng.range(49), 100u64) },
+                _ => RateCurve::Constant{},
+            };
+            let mut rate_distribution = rate_distribution(vec, rate_curve);
+            let len = rate_distribution.vec.len();
+            assert_eq!(rate_distribution.vec[len - 1].name, String::from("common"));
+
+            let mut counts = vec![0u32; len];
+            for _ in 0..300 {
+                let random = rng.range(MAX_RANGE as u64) as u128;
+                let index = rate_distribution.get_item_type_index(random, MAX_RANGE).unwrap();
+
+                // sold out item_type is never drawn
+                if index < len - 1 {
+                    assert!(rate_distribution.vec[index].supply > 0);
+                }
+
+                let purity = rate_distribution.purity(index).unwrap();
+                assert!(purity <= Decimal::one());
+
+                rate_distribution.update_item_type(index).unwrap();
+                counts[index] += 1;
+
+                let mut total_rate = Decimal::zero();
+                for (item_type, count) in rate_distribution.vec[..len - 1].iter().zip(counts.iter()) {
+                    assert_eq!(item_type.supply + count, item_type.max_supply);
+                    assert!(item_type.rate <= item_type.max_rate);
+                    if item_type.supply == 0 {
+                        assert_eq!(item_type.rate, Decimal::zero());
+                    }
+                    total_rate += item_type.rate;
+                }
+                assert!(total_rate <= Decimal::one());
+            }
 
-        assert_eq!(dist.vecs[0].supply, 0);
-        assert!(dist.vecs[0].rate == Decimal::zero());
-    } */
-}
\ No newline at end of file
+            // once other item_types are sold out, default item_type absorbs every draw
+            if rate_distribution.vec[..len - 1].iter().all(|item_type| item_type.supply == 0) {
+                for random in [0, MAX_RANGE / 2, MAX_RANGE] {
+                    assert_eq!(rate_distribution.get_item_type_index(random, MAX_RANGE).unwrap(), len - 1);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{
+    to_json_binary, Addr, Binary, Deps, DepsMut, Empty, Env, MessageInfo, Order, Response,
+    StdError, StdResult, WasmMsg,
+};
+use cw_multi_test::{Contract, ContractWrapper};
+use cw_storage_plus::Map;
+
+use crate::msg::ExecuteMsg as MysteryBoxExecuteMsg;
+
+/// randomness request recorded by mock aurand
+#[cw_serde]
+pub struct Request {
+    pub request_id: String,
+    pub requester: Addr,
+    pub num: u32,
+}
+
+const REQUESTS: Map<&str, Request> = Map::new("requests");
+
+#[cw_serde]
+pub enum ExecuteMsg {
+    /// same message as aurand, the request is recorded until it is fulfilled
+    RequestIntRandomness {
+        request_id: String,
+        num: u32,
+        min: i32,
+        max: i32,
+    },
+
+    /// send randomness of a recorded request back to its requester
+    Fulfill {
+        request_id: String,
+        randomness: Vec<i32>,
+    },
+}
+
+#[cw_serde]
+pub enum QueryMsg {
+    /// requests which are waiting for randomness
+    Requests {},
+}
+
+fn instantiate(_deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Empty) -> StdResult<Response> {
+    Ok(Response::new())
+}
+
+fn execute(deps: DepsMut, _env: Env, info: MessageInfo, msg: ExecuteMsg) -> StdResult<Response> {
+    match msg {
+        ExecuteMsg::RequestIntRandomness { request_id, num, .. } => {
+            REQUESTS.save(deps.storage, &request_id, &Request {
+                request_id: request_id.clone(),
+                requester: info.sender,
+                num,
+            })?;
+            Ok(Response::new())
+        },
+        ExecuteMsg::Fulfill { request_id, randomness } => {
+            let request = REQUESTS.may_load(deps.storage, &request_id)?
+                .ok_or_else(|| StdError::generic_err("request not found"))?;
+            REQUESTS.remove(deps.storage, &request_id);
+
+            Ok(Response::new().add_message(WasmMsg::Execute {
+                contract_addr: request.requester.to_string(),
+                msg: to_json_binary(&MysteryBoxExecuteMsg::ReceiveHexRandomness {
+                    request_id,
+                    randomness,
+                })?,
+                funds: vec![],
+            }))
+        },
+    }
+}
+
+fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Requests {} => to_json_binary(&REQUESTS
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, request)| request))
+            .collect::<StdResult<Vec<Request>>>()?),
+    }
+}
+
+pub fn contract() -> Box<dyn Contract<Empty>> {
+    Box::new(ContractWrapper::new(execute, instantiate, query))
+}
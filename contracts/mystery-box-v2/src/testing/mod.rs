@@ -0,0 +1,3 @@
+mod mock_aurand;
+mod suite;
+mod tests;
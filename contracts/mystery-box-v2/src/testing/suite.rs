@@ -0,0 +1,414 @@
+use anyhow::Result as AnyResult;
+use cosmwasm_std::{
+    coin, coins, to_json_binary, Addr, Binary, Coin, Decimal, Deps, DepsMut, Empty, Env,
+    MessageInfo, Response, StdResult, Timestamp, Uint128,
+};
+use cw721::{AllNftInfoResponse, Cw721ExecuteMsg, Cw721QueryMsg, Expiration, OwnerOfResponse};
+use cw_multi_test::{App, AppBuilder, AppResponse, Contract, ContractWrapper, Executor};
+use serde::de::DeserializeOwned;
+
+use crate::contract::{execute, instantiate, query, reply};
+use crate::msg::{
+    BoxInfo, BoxUpdate, EventTime, ExecuteMsg, InstantiateMsg, ItemTypeMsg, LinkedArress, QueryMsg, RateDistributionMsg,
+};
+use crate::state::{MysteryBox, PurchasedBox};
+use crate::ContractError;
+
+use super::mock_aurand::{self, Request};
+
+pub const OWNER: &str = "owner";
+pub const USER: &str = "user";
+pub const OTHER: &str = "other";
+
+pub const DENOM: &str = "uaura";
+pub const INITIAL_BALANCE: u128 = 1_000_000u128;
+pub const PRICE: u128 = 100u128;
+
+/// 2023-01-01 00:00:00Z
+pub const START: u64 = 1_672_531_200u64;
+/// 2023-01-31 00:00:00Z
+pub const END: u64 = 1_675_123_200u64;
+
+pub const PREFIX_URI: &str = "ipfs://items/";
+/// hex sha256 of PREFIX_URI
+pub const PROVENANCE_HASH: &str = "b5b37ecb8595b252aad157b190a4f2f9f9cb31fbf83be966930a2a55010264ca";
+pub const PLACEHOLDER_URI: &str = "ipfs://placeholder";
+
+/// randomness drawing "rare" of default rate distribution
+pub const RARE: [i32; 2] = [9950, 0];
+/// randomness drawing "common" of default rate distribution
+pub const COMMON: [i32; 2] = [0, 0];
+
+fn box_nft_instantiate(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: cw721_box::InstantiateMsg,
+) -> StdResult<Response> {
+    cw721_box::Cw721MetadataContract::default().instantiate(deps, env, info, msg)
+}
+
+fn box_nft_execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: cw721_box::ExecuteMsg,
+) -> Result<Response, cw721_box::ContractError> {
+    if let cw721_box::ExecuteMsg::Mint(mint_msg) = &msg {
+        cw721_box::validate_royalty(&mint_msg.extension)?;
+    }
+
+    cw721_box::Cw721MetadataContract::default().execute(deps, env, info, msg)
+}
+
+fn box_nft_query(deps: Deps, env: Env, msg: cw721_box::QueryMsg) -> StdResult<Binary> {
+    cw721_box::Cw721MetadataContract::default().query(deps, env, msg)
+}
+
+fn item_nft_instantiate(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: cw721_rarity::InstantiateMsg,
+) -> StdResult<Response> {
+    cw721_rarity::Cw721MetadataContract::default().instantiate(deps, env, info, msg)
+}
+
+fn item_nft_execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: cw721_rarity::ExecuteMsg,
+) -> Result<Response, cw721_rarity::ContractError> {
+    match msg {
+        cw721_rarity::ExecuteMsg::Extension { msg } => match msg {
+            cw721_rarity::MetadataMsg::UpdateMetadata { token_id, token_uri, extension } => {
+                cw721_rarity::execute::update_metadata(deps, info, token_id, token_uri, extension)
+            },
+            cw721_rarity::MetadataMsg::FreezeMetadata { token_id } => {
+                cw721_rarity::execute::freeze_metadata(deps, info, token_id)
+            },
+        },
+        _ => {
+            if let cw721_rarity::ExecuteMsg::Mint(mint_msg) = &msg {
+                cw721_rarity::validate_royalty(&mint_msg.extension)?;
+            }
+
+            cw721_rarity::Cw721MetadataContract::default().execute(deps, env, info, msg)
+        },
+    }
+}
+
+fn item_nft_query(deps: Deps, env: Env, msg: cw721_rarity::QueryMsg) -> StdResult<Binary> {
+    match msg {
+        cw721_rarity::QueryMsg::Extension { msg } => match msg {
+            cw721_rarity::RarityQueryMsg::Royalty(cw721_rarity::Cw2981QueryMsg::RoyaltyInfo { token_id, sale_price }) => {
+                to_json_binary(&cw721_rarity::query::query_royalties_info(deps, token_id, sale_price)?)
+            },
+            cw721_rarity::RarityQueryMsg::Royalty(cw721_rarity::Cw2981QueryMsg::CheckRoyalties {}) => {
+                to_json_binary(&cw721_rarity::query::check_royalties(deps)?)
+            },
+            cw721_rarity::RarityQueryMsg::Metadata(cw721_rarity::MetadataQueryMsg::MetadataFrozen { token_id }) => {
+                to_json_binary(&cw721_rarity::query::query_metadata_frozen(deps, token_id)?)
+            },
+        },
+        _ => cw721_rarity::Cw721MetadataContract::default().query(deps, env, msg),
+    }
+}
+
+fn box_nft_contract() -> Box<dyn Contract<Empty>> {
+    Box::new(ContractWrapper::new(box_nft_execute, box_nft_instantiate, box_nft_query))
+}
+
+fn item_nft_contract() -> Box<dyn Contract<Empty>> {
+    Box::new(ContractWrapper::new(item_nft_execute, item_nft_instantiate, item_nft_query))
+}
+
+fn mystery_box_contract() -> Box<dyn Contract<Empty>> {
+    Box::new(ContractWrapper::new(execute, instantiate, query).with_reply(reply))
+}
+
+/// default mystery box event, running from START to END
+pub fn box_info() -> BoxInfo {
+    BoxInfo {
+        name: String::from("Genesis"),
+        description: String::from("first mystery box event"),
+        start: EventTime::AtTime(Timestamp::from_seconds(START)),
+        end: EventTime::AtTime(Timestamp::from_seconds(END)),
+        total_supply: 100,
+        replacement: false,
+        max_minted_box: Some(100),
+        price: coin(PRICE, DENOM),
+        reroll_fee: None,
+        royalty_payment_address: None,
+        royalty_percentage: None,
+        item_metadata: None,
+        box_image: None,
+        placeholder_uri: None,
+        provenance_hash: None,
+    }
+}
+
+pub fn item_type(name: &str, rate: &str, supply: u32) -> ItemTypeMsg {
+    ItemTypeMsg {
+        name: String::from(name),
+        rate: rate.parse::<Decimal>().unwrap(),
+        slip_rate: 1,
+        supply,
+        pity: None,
+        prefix_uri: None,
+        total_supply: None,
+        reward: None,
+        mint_item: None,
+        image: None,
+        attributes: None,
+    }
+}
+
+/// "rare" item type with rate 0.1 and default "common" item type
+pub fn rate_distribution() -> RateDistributionMsg {
+    RateDistributionMsg {
+        vec: vec![item_type("rare", "0.1", 10)],
+        default_type: None,
+        rate_curve: None,
+        mode: None,
+        items_per_box: None,
+        guaranteed_type: None,
+    }
+}
+
+/// error returned by mystery box contract
+pub fn contract_error(err: anyhow::Error) -> ContractError {
+    err.downcast::<ContractError>().unwrap()
+}
+
+/// mystery box contract linked to its cw721 contracts and a mock aurand
+pub struct Suite {
+    pub app: App,
+    pub contract: Addr,
+    pub aurand: Addr,
+    pub box_supplier: Addr,
+    pub item_supplier: Addr,
+    box_nft_code_id: u64,
+}
+
+impl Suite {
+    pub fn new() -> Self {
+        let mut app = AppBuilder::new().build(|router, _, storage| {
+            for addr in [OWNER, USER, OTHER] {
+                router.bank
+                    .init_balance(storage, &Addr::unchecked(addr), coins(INITIAL_BALANCE, DENOM))
+                    .unwrap();
+            }
+        });
+        app.update_block(|block| block.time = Timestamp::from_seconds(START + 1));
+
+        let aurand_code_id = app.store_code(mock_aurand::contract());
+        let box_nft_code_id = app.store_code(box_nft_contract());
+        let item_nft_code_id = app.store_code(item_nft_contract());
+        let mystery_box_code_id = app.store_code(mystery_box_contract());
+
+        let aurand = app.instantiate_contract(
+            aurand_code_id, Addr::unchecked(OWNER), &Empty {}, &[], "aurand", None,
+        ).unwrap();
+
+        let contract = app.instantiate_contract(
+            mystery_box_code_id,
+            Addr::unchecked(OWNER),
+            &InstantiateMsg {
+                aurand_address: aurand.to_string(),
+                owner: String::from(OWNER),
+                box_supplier_code_id: box_nft_code_id,
+                box_supplier_name: String::from("Mystery Box"),
+                box_supplier_symbol: String::from("BOX"),
+                item_supplier_code_id: item_nft_code_id,
+                item_supplier_name: String::from("Mystery Item"),
+                item_supplier_symbol: String::from("ITEM"),
+            },
+            &[],
+            "mystery box",
+            None,
+        ).unwrap();
+
+        let linked: LinkedArress = app.wrap()
+            .query_wasm_smart(contract.clone(), &QueryMsg::GetLinkedAddres {})
+            .unwrap();
+
+        Suite {
+            app,
+            contract,
+            aurand,
+            box_supplier: linked.box_supplier_address.unwrap(),
+            item_supplier: linked.item_supplier_address.unwrap(),
+            box_nft_code_id,
+        }
+    }
+
+    /// suite with default event created and updated
+    pub fn with_event() -> Self {
+        let mut suite = Suite::new();
+        suite.create(OWNER, box_info()).unwrap();
+        suite.update(OWNER, rate_distribution()).unwrap();
+        suite
+    }
+
+    pub fn execute(&mut self, sender: &str, msg: ExecuteMsg, funds: &[Coin]) -> AnyResult<AppResponse> {
+        self.app.execute_contract(Addr::unchecked(sender), self.contract.clone(), &msg, funds)
+    }
+
+    pub fn query<T: DeserializeOwned>(&self, msg: QueryMsg) -> T {
+        self.app.wrap().query_wasm_smart(self.contract.clone(), &msg).unwrap()
+    }
+
+    pub fn create(&mut self, sender: &str, box_info: BoxInfo) -> AnyResult<AppResponse> {
+        self.execute(sender, ExecuteMsg::CreateMysteryBox { box_info }, &[])
+    }
+
+    pub fn update(&mut self, sender: &str, rate_distribution: RateDistributionMsg) -> AnyResult<AppResponse> {
+        self.update_box(sender, BoxUpdate {
+            prefix_uri: Some(String::from(PREFIX_URI)),
+            rate_distribution: Some(rate_distribution),
+            ..BoxUpdate::default()
+        })
+    }
+
+    pub fn update_box(&mut self, sender: &str, box_update: BoxUpdate) -> AnyResult<AppResponse> {
+        self.execute(sender, ExecuteMsg::UpdateMysteryBox { box_update }, &[])
+    }
+
+    /// buy a box at PRICE, return token id of the box
+    pub fn mint(&mut self, sender: &str) -> AnyResult<String> {
+        let res = self.execute(sender, ExecuteMsg::MintBox {}, &coins(PRICE, DENOM))?;
+        Ok(wasm_attribute(&res, "token_id"))
+    }
+
+    /// approve mystery box contract to burn a box
+    pub fn approve_box(&mut self, sender: &str, token_id: &str, expires: Option<Expiration>) -> AnyResult<AppResponse> {
+        self.app.execute_contract(
+            Addr::unchecked(sender),
+            self.box_supplier.clone(),
+            &Cw721ExecuteMsg::Approve {
+                spender: self.contract.to_string(),
+                token_id: String::from(token_id),
+                expires,
+            },
+            &[],
+        )
+    }
+
+    /// approve mystery box contract to burn all items of sender
+    pub fn approve_items(&mut self, sender: &str) -> AnyResult<AppResponse> {
+        self.app.execute_contract(
+            Addr::unchecked(sender),
+            self.item_supplier.clone(),
+            &Cw721ExecuteMsg::ApproveAll {
+                operator: self.contract.to_string(),
+                expires: None,
+            },
+            &[],
+        )
+    }
+
+    pub fn open(&mut self, sender: &str, token_id: &str) -> AnyResult<AppResponse> {
+        self.execute(sender, ExecuteMsg::OpenBox { token_id: String::from(token_id) }, &[])
+    }
+
+    /// buy, approve and open a box, return token id of the box
+    pub fn mint_and_open(&mut self, sender: &str) -> AnyResult<String> {
+        let token_id = self.mint(sender)?;
+        self.approve_box(sender, &token_id, None)?;
+        self.open(sender, &token_id)?;
+        Ok(token_id)
+    }
+
+    /// mock aurand answers a recorded request
+    pub fn fulfill(&mut self, request_id: &str, randomness: &[i32]) -> AnyResult<AppResponse> {
+        self.app.execute_contract(
+            Addr::unchecked(OWNER),
+            self.aurand.clone(),
+            &mock_aurand::ExecuteMsg::Fulfill {
+                request_id: String::from(request_id),
+                randomness: randomness.to_vec(),
+            },
+            &[],
+        )
+    }
+
+    /// randomness requests waiting in mock aurand
+    pub fn requests(&self) -> Vec<Request> {
+        self.app.wrap()
+            .query_wasm_smart(self.aurand.clone(), &mock_aurand::QueryMsg::Requests {})
+            .unwrap()
+    }
+
+    pub fn mystery_box(&self) -> MysteryBox {
+        self.query::<Option<MysteryBox>>(QueryMsg::GetMysteryBoxInformation { id: None }).unwrap()
+    }
+
+    pub fn box_status(&self, token_id: &str) -> Option<PurchasedBox> {
+        self.query(QueryMsg::GetBoxStatus { token_id: String::from(token_id), id: None })
+    }
+
+    pub fn item(&self, token_id: &str) -> AllNftInfoResponse<cw721_rarity::Extension> {
+        self.app.wrap().query_wasm_smart(self.item_supplier.clone(), &Cw721QueryMsg::AllNftInfo {
+            token_id: String::from(token_id),
+            include_expired: None,
+        }).unwrap()
+    }
+
+    pub fn box_owner(&self, token_id: &str) -> StdResult<OwnerOfResponse> {
+        self.app.wrap().query_wasm_smart(self.box_supplier.clone(), &Cw721QueryMsg::OwnerOf {
+            token_id: String::from(token_id),
+            include_expired: None,
+        })
+    }
+
+    pub fn item_exists(&self, token_id: &str) -> bool {
+        self.app.wrap().query_wasm_smart::<OwnerOfResponse>(self.item_supplier.clone(), &Cw721QueryMsg::OwnerOf {
+            token_id: String::from(token_id),
+            include_expired: None,
+        }).is_ok()
+    }
+
+    pub fn balance(&self, addr: &str) -> Uint128 {
+        self.app.wrap().query_balance(addr, DENOM).unwrap().amount
+    }
+
+    /// instantiate another cw721 contract whose minter is OWNER
+    pub fn instantiate_nft(&mut self) -> Addr {
+        self.app.instantiate_contract(
+            self.box_nft_code_id,
+            Addr::unchecked(OWNER),
+            &cw721_box::InstantiateMsg {
+                name: String::from("Prize"),
+                symbol: String::from("PRIZE"),
+                minter: String::from(OWNER),
+            },
+            &[],
+            "prize",
+            None,
+        ).unwrap()
+    }
+
+    pub fn set_time(&mut self, seconds: u64) {
+        self.app.update_block(|block| block.time = Timestamp::from_seconds(seconds));
+    }
+
+    pub fn set_height(&mut self, height: u64) {
+        self.app.update_block(|block| block.height = height);
+    }
+
+    pub fn advance(&mut self, seconds: u64) {
+        self.app.update_block(|block| block.time = block.time.plus_seconds(seconds));
+    }
+}
+
+/// value of attribute of the first wasm event having it
+pub fn wasm_attribute(res: &AppResponse, key: &str) -> String {
+    res.events.iter()
+        .filter(|event| event.ty == "wasm")
+        .flat_map(|event| event.attributes.iter())
+        .find(|attr| attr.key == key)
+        .map(|attr| attr.value.clone())
+        .unwrap()
+}
@@ -0,0 +1,1650 @@
+use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+use cosmwasm_std::{coin, coins, to_json_binary, Addr, Decimal, Timestamp, Uint128};
+use cw721::{Cw721ExecuteMsg, Expiration};
+use cw_multi_test::Executor;
+use cw_utils::Scheduled;
+
+use crate::contract::{execute, instantiate};
+use crate::msg::{
+    BoxUpdate, EventTime, ExecuteMsg, InstantiateMsg, LinkedArress, PendingJobsResponse, PityCounterResponse, PrizeCollectionsResponse, PrizesResponse, QueryMsg,
+    ReceiveNftMsg, SimulateOpenResponse,
+};
+use crate::state::{
+    BoxBuyback, DrawMode, FusionOutput, FusionRecipe, JobKind, MetadataTemplate, MysteryBox,
+    PurchasedBox, RateCurve, Reward,
+};
+use crate::ContractError;
+
+use super::suite::{
+    box_info, contract_error, item_type, rate_distribution, wasm_attribute, Suite, COMMON, DENOM,
+    END, INITIAL_BALANCE, OTHER, OWNER, PLACEHOLDER_URI, PREFIX_URI, PRICE, PROVENANCE_HASH, RARE,
+    START, USER,
+};
+
+#[test]
+fn instantiate_links_suppliers() {
+    let suite = Suite::new();
+
+    let linked: LinkedArress = suite.query(QueryMsg::GetLinkedAddres {});
+    assert_eq!(linked.aurand_address, suite.aurand);
+    assert_eq!(linked.box_supplier_address, Some(suite.box_supplier.clone()));
+    assert_eq!(linked.item_supplier_address, Some(suite.item_supplier.clone()));
+    assert_ne!(suite.box_supplier, suite.item_supplier);
+
+    // no mystery box event yet
+    let mystery_box: Option<MysteryBox> = suite.query(QueryMsg::GetMysteryBoxInformation { id: None });
+    assert_eq!(mystery_box, None);
+}
+
+#[test]
+fn update_config() {
+    let mut suite = Suite::new();
+
+    let err = suite.execute(USER, ExecuteMsg::UpdateConfig { aurand_address: String::from(OTHER) }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::Unauthorized {});
+
+    suite.execute(OWNER, ExecuteMsg::UpdateConfig { aurand_address: String::from(OTHER) }, &[]).unwrap();
+    let linked: LinkedArress = suite.query(QueryMsg::GetLinkedAddres {});
+    assert_eq!(linked.aurand_address, Addr::unchecked(OTHER));
+}
+
+#[test]
+fn create_mystery_box() {
+    let mut suite = Suite::new();
+
+    // only owner can create mystery box event
+    let err = suite.create(USER, box_info()).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::Unauthorized {});
+
+    // end time is past
+    let mut info = box_info();
+    info.start = EventTime::Rfc3339(String::from("2022-12-01T00:00:00Z"));
+    info.end = EventTime::Rfc3339(String::from("2022-12-31T00:00:00Z"));
+    let err = suite.create(OWNER, info).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidTime {});
+
+    let mut info = box_info();
+    info.start = EventTime::Rfc3339(String::from("2023-02-01T00:00:00Z"));
+    let err = suite.create(OWNER, info).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidTimeRange {});
+
+    // unique uri can not cover all boxes
+    let mut info = box_info();
+    info.replacement = true;
+    info.max_minted_box = Some(101);
+    let err = suite.create(OWNER, info).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InsufficientUriSupply {});
+
+    let mut info = box_info();
+    info.total_supply = 0;
+    let err = suite.create(OWNER, info).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidTotalSupply {});
+
+    suite.create(OWNER, box_info()).unwrap();
+    let mystery_box = suite.mystery_box();
+    assert_eq!(mystery_box.id, 0);
+    assert_eq!(mystery_box.start, Scheduled::AtTime(Timestamp::from_seconds(START)));
+    assert_eq!(mystery_box.end, Scheduled::AtTime(Timestamp::from_seconds(END)));
+    assert_eq!(mystery_box.max_minted_box, 100);
+    assert_eq!(mystery_box.rate_distribution, None);
+
+    // event is running
+    let err = suite.create(OWNER, box_info()).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::MysteryBoxInOperation {});
+
+    // old event is moved to history once it ended
+    suite.set_time(END + 1);
+    let mut info = box_info();
+    info.name = String::from("Second");
+    info.end = EventTime::Rfc3339(String::from("2023-03-01T00:00:00Z"));
+    suite.create(OWNER, info).unwrap();
+    assert_eq!(suite.mystery_box().id, 1);
+    let old: Option<MysteryBox> = suite.query(QueryMsg::GetMysteryBoxInformation { id: Some(0) });
+    assert_eq!(old.unwrap().name, "Genesis");
+}
+
+#[test]
+fn create_mystery_box_invalid_royalty() {
+    let mut suite = Suite::new();
+
+    let mut info = box_info();
+    info.royalty_payment_address = Some(String::from(OWNER));
+    info.royalty_percentage = Some(101);
+    let err = suite.create(OWNER, info).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidRoyalty {});
+
+    // payment address and percentage must be set together
+    let mut info = box_info();
+    info.royalty_percentage = Some(10);
+    let err = suite.create(OWNER, info).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidRoyalty {});
+
+    let mut info = box_info();
+    info.royalty_payment_address = Some(String::from("INVALID"));
+    info.royalty_percentage = Some(10);
+    let err = suite.create(OWNER, info).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidAddress {});
+}
+
+#[test]
+fn suppliers_not_linked() {
+    // cw721 contracts are linked by replies of instantiate, which are not run with mock dependencies
+    let mut deps = mock_dependencies();
+    instantiate(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), InstantiateMsg {
+        aurand_address: String::from("aurand"),
+        owner: String::from(OWNER),
+        box_supplier_code_id: 1,
+        box_supplier_name: String::from("Mystery Box"),
+        box_supplier_symbol: String::from("BOX"),
+        item_supplier_code_id: 2,
+        item_supplier_name: String::from("Mystery Item"),
+        item_supplier_symbol: String::from("ITEM"),
+    }).unwrap();
+
+    let err = execute(deps.as_mut(), mock_env(), mock_info(USER, &[]), ExecuteMsg::MintBox {}).unwrap_err();
+    assert_eq!(err, ContractError::BoxSupplierNotLinked {});
+    let err = execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), ExecuteMsg::FreezeItemMetadata { token_id: None }).unwrap_err();
+    assert_eq!(err, ContractError::ItemSupplierNotLinked {});
+}
+
+#[test]
+fn update_mystery_box() {
+    let mut suite = Suite::new();
+
+    let err = suite.update(OWNER, rate_distribution()).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::MysteryBoxNotInitialized {});
+
+    suite.create(OWNER, box_info()).unwrap();
+
+    let err = suite.update(USER, rate_distribution()).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::Unauthorized {});
+
+    suite.update(OWNER, rate_distribution()).unwrap();
+    let mystery_box = suite.mystery_box();
+    assert_eq!(mystery_box.prefix_uri, Some(String::from(PREFIX_URI)));
+    let rate_distribution_names: Vec<String> = mystery_box.rate_distribution.unwrap().vec
+        .into_iter()
+        .map(|item_type| item_type.name)
+        .collect();
+    assert_eq!(rate_distribution_names, vec!["rare", "common"]);
+
+    // rate distribution can be changed until the first box is opened
+    let mut distribution = rate_distribution();
+    distribution.vec.push(item_type("epic", "0.01", 1));
+    suite.update(OWNER, distribution).unwrap();
+    assert_eq!(suite.mystery_box().rate_distribution.unwrap().vec.len(), 3);
+}
+
+#[test]
+fn update_mystery_box_fields() {
+    let mut suite = Suite::new();
+    suite.create(OWNER, box_info()).unwrap();
+
+    let res = suite.update_box(OWNER, BoxUpdate {
+        name: Some(String::from("Renamed")),
+        end: Some(EventTime::AtHeight(100_000)),
+        price: Some(coin(PRICE * 2, DENOM)),
+        max_minted_box: Some(50),
+        ..BoxUpdate::default()
+    }).unwrap();
+    let changes: Vec<(String, String, String)> = res.events.iter()
+        .filter(|event| event.ty == "wasm-mystery_box_change")
+        .map(|event| (event.attributes[1].value.clone(), event.attributes[2].value.clone(), event.attributes[3].value.clone()))
+        .collect();
+    assert_eq!(changes, vec![
+        (String::from("name"), String::from("Genesis"), String::from("Renamed")),
+        (String::from("end"), format!("scheduled time: {}", Timestamp::from_seconds(END)), String::from("scheduled height: 100000")),
+        (String::from("price"), format!("{}{}", PRICE, DENOM), format!("{}{}", PRICE * 2, DENOM)),
+        (String::from("max_minted_box"), String::from("100"), String::from("50")),
+    ]);
+
+    let mystery_box = suite.mystery_box();
+    assert_eq!(mystery_box.name, "Renamed");
+    assert_eq!(mystery_box.description, "first mystery box event");
+    assert_eq!(mystery_box.start, Scheduled::AtTime(Timestamp::from_seconds(START)));
+    assert_eq!(mystery_box.end, Scheduled::AtHeight(100_000));
+    assert_eq!(mystery_box.price, coin(PRICE * 2, DENOM));
+    assert_eq!(mystery_box.max_minted_box, 50);
+
+    // new values are validated like a new event
+    let err = suite.update_box(OWNER, BoxUpdate { name: Some(String::new()), ..BoxUpdate::default() }).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidName {});
+
+    let err = suite.update_box(OWNER, BoxUpdate {
+        start: Some(EventTime::AtHeight(200_000)),
+        ..BoxUpdate::default()
+    }).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidTimeRange {});
+
+    let err = suite.update_box(OWNER, BoxUpdate {
+        end: Some(EventTime::AtTime(Timestamp::from_seconds(START + 1))),
+        ..BoxUpdate::default()
+    }).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidTime {});
+
+    let err = suite.update_box(OWNER, BoxUpdate { price: Some(coin(0, DENOM)), ..BoxUpdate::default() }).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidPrice {});
+
+    let err = suite.update_box(OWNER, BoxUpdate { max_minted_box: Some(0), ..BoxUpdate::default() }).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidBoxSupply {});
+}
+
+#[test]
+fn update_mystery_box_locked_after_sale() {
+    let mut suite = Suite::new();
+    suite.create(OWNER, box_info()).unwrap();
+    suite.update(OWNER, rate_distribution()).unwrap();
+
+    let token_id = suite.mint(USER).unwrap();
+    for (field, box_update) in [
+        ("name", BoxUpdate { name: Some(String::from("Renamed")), ..BoxUpdate::default() }),
+        ("description", BoxUpdate { description: Some(String::new()), ..BoxUpdate::default() }),
+        ("start", BoxUpdate { start: Some(EventTime::AtHeight(1)), ..BoxUpdate::default() }),
+        ("end", BoxUpdate { end: Some(EventTime::AtHeight(100_000)), ..BoxUpdate::default() }),
+        ("price", BoxUpdate { price: Some(coin(1, DENOM)), ..BoxUpdate::default() }),
+        ("max_minted_box", BoxUpdate { max_minted_box: Some(10), ..BoxUpdate::default() }),
+    ] {
+        let err = suite.update_box(OWNER, box_update).unwrap_err();
+        assert_eq!(contract_error(err), ContractError::FieldLocked { field: String::from(field) });
+    }
+
+    // boxes are sold, but none is opened yet
+    suite.update_box(OWNER, BoxUpdate {
+        prefix_uri: Some(String::from("ipfs://other/")),
+        ..BoxUpdate::default()
+    }).unwrap();
+    let err = suite.update_box(OWNER, BoxUpdate {
+        rate_distribution: Some(rate_distribution()),
+        ..BoxUpdate::default()
+    }).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::FieldLocked { field: String::from("rate_distribution") });
+
+    suite.approve_box(USER, &token_id, None).unwrap();
+    suite.open(USER, &token_id).unwrap();
+    let err = suite.update(OWNER, rate_distribution()).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::FieldLocked { field: String::from("prefix_uri") });
+    let err = suite.update_box(OWNER, BoxUpdate {
+        rate_distribution: Some(rate_distribution()),
+        ..BoxUpdate::default()
+    }).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::FieldLocked { field: String::from("rate_distribution") });
+}
+
+#[test]
+fn set_rate_distribution_after_sale() {
+    let mut suite = Suite::new();
+    suite.create(OWNER, box_info()).unwrap();
+    suite.mint(USER).unwrap();
+
+    // rate distribution which is not set yet can still be set once
+    suite.update(OWNER, rate_distribution()).unwrap();
+    let err = suite.update_box(OWNER, BoxUpdate {
+        rate_distribution: Some(rate_distribution()),
+        ..BoxUpdate::default()
+    }).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::FieldLocked { field: String::from("rate_distribution") });
+}
+
+#[test]
+fn update_mystery_box_deck_size() {
+    let mut suite = Suite::new();
+    suite.create(OWNER, box_info()).unwrap();
+    let mut distribution = rate_distribution();
+    distribution.mode = Some(DrawMode::Deck);
+    suite.update(OWNER, distribution.clone()).unwrap();
+
+    // deck is built for 100 boxes
+    let err = suite.update_box(OWNER, BoxUpdate { max_minted_box: Some(50), ..BoxUpdate::default() }).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidDeckSize {});
+
+    suite.update_box(OWNER, BoxUpdate {
+        max_minted_box: Some(50),
+        rate_distribution: Some(distribution),
+        ..BoxUpdate::default()
+    }).unwrap();
+    let rate_distribution = suite.mystery_box().rate_distribution.unwrap();
+    let common = rate_distribution.vec.iter().find(|item_type| item_type.name == "common").unwrap();
+    assert_eq!(common.supply, 40);
+}
+
+#[test]
+fn update_mystery_box_uri_supply() {
+    let mut suite = Suite::new();
+    let mut info = box_info();
+    info.replacement = true;
+    suite.create(OWNER, info).unwrap();
+
+    // every box takes two uri from shared pool
+    let mut msg = rate_distribution();
+    msg.items_per_box = Some(2);
+    let err = suite.update(OWNER, msg).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InsufficientUriSupply {});
+
+    // rare items can be drawn up to its supply, but its pool only has 5 uri
+    let mut msg = rate_distribution();
+    msg.vec[0].prefix_uri = Some(String::from("ipfs://rare/"));
+    msg.vec[0].total_supply = Some(5);
+    let err = suite.update(OWNER, msg).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidUriPool {});
+
+    // fewer boxes fit into shared pool
+    suite.update_box(OWNER, BoxUpdate {
+        max_minted_box: Some(50),
+        ..BoxUpdate::default()
+    }).unwrap();
+    let mut msg = rate_distribution();
+    msg.items_per_box = Some(2);
+    suite.update(OWNER, msg).unwrap();
+
+    let err = suite.update_box(OWNER, BoxUpdate {
+        max_minted_box: Some(51),
+        ..BoxUpdate::default()
+    }).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InsufficientUriSupply {});
+}
+
+#[test]
+fn update_mystery_box_ended() {
+    let mut suite = Suite::new();
+    suite.create(OWNER, box_info()).unwrap();
+
+    suite.set_time(END + 1);
+    let err = suite.update(OWNER, rate_distribution()).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::MysteryBoxEnded {});
+}
+
+#[test]
+fn update_mystery_box_invalid_rate_distribution() {
+    let mut suite = Suite::new();
+    suite.create(OWNER, box_info()).unwrap();
+
+    let mut msg = rate_distribution();
+    msg.rate_curve = Some(RateCurve::Exponential { base: Decimal::one() });
+    let err = suite.update(OWNER, msg).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidRateCurve {});
+
+    let mut msg = rate_distribution();
+    msg.mode = Some(DrawMode::Deck);
+    msg.vec = vec![item_type("rare", "0.1", 0)];
+    let err = suite.update(OWNER, msg).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidDeckSize {});
+
+    let mut msg = rate_distribution();
+    msg.mode = Some(DrawMode::Deck);
+    msg.vec = vec![item_type("rare", "0.1", 101)];
+    let err = suite.update(OWNER, msg).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidDeckSize {});
+
+    let mut msg = rate_distribution();
+    msg.vec[0].pity = Some(0);
+    let err = suite.update(OWNER, msg).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidPity {});
+
+    let mut msg = rate_distribution();
+    msg.vec[0].prefix_uri = Some(String::from("ipfs://rare/"));
+    let err = suite.update(OWNER, msg).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidUriPool {});
+
+    let mut msg = rate_distribution();
+    msg.items_per_box = Some(0);
+    let err = suite.update(OWNER, msg).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidItemsPerBox {});
+
+    let mut msg = rate_distribution();
+    msg.items_per_box = Some(11);
+    let err = suite.update(OWNER, msg).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidItemsPerBox {});
+
+    let mut msg = rate_distribution();
+    msg.guaranteed_type = Some(String::from("legendary"));
+    let err = suite.update(OWNER, msg).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::ItemTypeNotFound {});
+
+    // item type without item must have reward
+    let mut msg = rate_distribution();
+    msg.vec[0].mint_item = Some(false);
+    let err = suite.update(OWNER, msg).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidReward {});
+
+    // contract does not hold rewards for the whole supply
+    let mut msg = rate_distribution();
+    msg.vec[0].reward = Some(Reward::Native { amount: coin(50, DENOM) });
+    let err = suite.update(OWNER, msg).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InsufficientEscrow {});
+
+    let mut msg = rate_distribution();
+    msg.vec[0].rate = Decimal::one();
+    let err = suite.update(OWNER, msg).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidRate {});
+
+    let mut msg = rate_distribution();
+    msg.vec.push(item_type("common", "0.1", 10));
+    let err = suite.update(OWNER, msg).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::DuplicateItemType {});
+
+    // nothing was saved
+    assert_eq!(suite.mystery_box().rate_distribution, None);
+}
+
+#[test]
+fn mint_box() {
+    let mut suite = Suite::new();
+    let mut info = box_info();
+    info.box_image = Some(String::from("ipfs://box.png"));
+    suite.create(OWNER, info).unwrap();
+
+    let err = suite.execute(USER, ExecuteMsg::MintBox {}, &[]).unwrap_err();
+    assert!(matches!(contract_error(err), ContractError::CustomError { .. }));
+
+    let err = suite.execute(USER, ExecuteMsg::MintBox {}, &coins(PRICE - 1, DENOM)).unwrap_err();
+    assert!(matches!(contract_error(err), ContractError::CustomError { .. }));
+
+    let token_id = suite.mint(USER).unwrap();
+    assert!(token_id.starts_with("0_"));
+    assert_eq!(suite.balance(USER), Uint128::new(INITIAL_BALANCE - PRICE));
+    assert_eq!(suite.balance(suite.contract.as_str()), Uint128::new(PRICE));
+    assert_eq!(suite.box_owner(&token_id).unwrap().owner, USER);
+    assert_eq!(suite.box_status(&token_id), Some(PurchasedBox {
+        is_opened: false,
+        open_time: None,
+        is_received_randomness: false,
+        price: coin(PRICE, DENOM),
+    }));
+
+    // box metadata carries event and purchase info
+    let nft_info: cw721::NftInfoResponse<cw721_box::Extension> = suite.app.wrap()
+        .query_wasm_smart(suite.box_supplier.clone(), &cw721::Cw721QueryMsg::NftInfo { token_id: token_id.clone() })
+        .unwrap();
+    let metadata = nft_info.extension.unwrap();
+    assert_eq!(metadata.name, Some(String::from("Genesis #1")));
+    assert_eq!(metadata.image, Some(String::from("ipfs://box.png")));
+    assert_eq!(metadata.event_id, Some(0));
+    assert_eq!(metadata.price, Some(coin(PRICE, DENOM)));
+
+    let mystery_box = suite.mystery_box();
+    assert_eq!(mystery_box.max_minted_box, 99);
+    assert_eq!(mystery_box.minted_box, 1);
+}
+
+#[test]
+fn mint_box_sold_out_and_ended() {
+    let mut suite = Suite::new();
+    let mut info = box_info();
+    info.max_minted_box = Some(1);
+    suite.create(OWNER, info).unwrap();
+
+    suite.mint(USER).unwrap();
+    let err = suite.mint(USER).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::SoldOut {});
+
+    let mut suite = Suite::new();
+    suite.create(OWNER, box_info()).unwrap();
+    suite.set_time(END);
+    let err = suite.mint(USER).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::MysteryBoxEnded {});
+}
+
+#[test]
+fn open_box() {
+    let mut suite = Suite::with_event();
+    let token_id = suite.mint(USER).unwrap();
+
+    let err = suite.open(USER, "unknown").unwrap_err();
+    assert_eq!(contract_error(err), ContractError::TokenNotRecognized {});
+
+    // box is not approved
+    let err = suite.open(USER, &token_id).unwrap_err();
+    assert!(matches!(contract_error(err), ContractError::CustomError { .. }));
+
+    // approval must never expire
+    suite.approve_box(USER, &token_id, Some(Expiration::AtHeight(1_000_000))).unwrap();
+    let err = suite.open(USER, &token_id).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::Unauthorized {});
+
+    suite.approve_box(USER, &token_id, None).unwrap();
+
+    // only owner of box can open it
+    let err = suite.open(OTHER, &token_id).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::Unauthorized {});
+
+    suite.open(USER, &token_id).unwrap();
+
+    // box is burned and randomness is requested
+    assert!(suite.box_owner(&token_id).is_err());
+    let requests = suite.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].request_id, token_id);
+    assert_eq!(requests[0].requester, suite.contract);
+    assert_eq!(requests[0].num, 2);
+
+    let status = suite.box_status(&token_id).unwrap();
+    assert!(status.is_opened);
+    assert!(!status.is_received_randomness);
+    assert_eq!(status.open_time.unwrap().seconds(), START + 1);
+
+    let jobs: PendingJobsResponse = suite.query(QueryMsg::ListPendingJobs { start_after: None, limit: None });
+    assert_eq!(jobs.jobs.len(), 1);
+    assert_eq!(jobs.jobs[0].request_id, token_id);
+    assert_eq!(jobs.jobs[0].sender, Addr::unchecked(USER));
+    assert_eq!(jobs.jobs[0].kind, JobKind::OpenBox {});
+
+    let err = suite.open(USER, &token_id).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::BoxOpened {});
+}
+
+#[test]
+fn open_box_not_updated() {
+    let mut suite = Suite::new();
+    suite.create(OWNER, box_info()).unwrap();
+    let token_id = suite.mint(USER).unwrap();
+    suite.approve_box(USER, &token_id, None).unwrap();
+
+    let err = suite.open(USER, &token_id).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::MysteryBoxNotUpdated {});
+}
+
+#[test]
+fn open_box_out_of_time() {
+    let mut suite = Suite::new();
+    suite.set_time(START - 100);
+    suite.create(OWNER, box_info()).unwrap();
+    suite.update(OWNER, rate_distribution()).unwrap();
+    let token_id = suite.mint(USER).unwrap();
+    suite.approve_box(USER, &token_id, None).unwrap();
+
+    let err = suite.open(USER, &token_id).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::MysteryBoxNotStarted {});
+
+    suite.set_time(END);
+    let err = suite.open(USER, &token_id).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::MysteryBoxEnded {});
+}
+
+#[test]
+fn open_box_scheduled_by_height() {
+    let mut suite = Suite::new();
+    suite.set_height(100);
+    let mut info = box_info();
+    info.start = EventTime::AtHeight(200);
+    info.end = EventTime::AtHeight(300);
+    suite.create(OWNER, info).unwrap();
+    assert_eq!(suite.mystery_box().start, Scheduled::AtHeight(200));
+    assert_eq!(suite.mystery_box().end, Scheduled::AtHeight(300));
+    suite.update(OWNER, rate_distribution()).unwrap();
+    let token_id = suite.mint(USER).unwrap();
+    suite.approve_box(USER, &token_id, None).unwrap();
+
+    // time has passed end of default event, but only height matters
+    suite.set_time(END + 1);
+    let err = suite.open(USER, &token_id).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::MysteryBoxNotStarted {});
+
+    suite.set_height(200);
+    suite.open(USER, &token_id).unwrap();
+
+    suite.set_height(300);
+    let err = suite.mint(USER).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::MysteryBoxEnded {});
+}
+
+#[test]
+fn receive_randomness() {
+    let mut suite = Suite::new();
+    let mut info = box_info();
+    info.item_metadata = Some(MetadataTemplate {
+        name: Some(String::from("{event_name} {item_type} #{n}")),
+        description: None,
+    });
+    suite.create(OWNER, info).unwrap();
+    suite.update(OWNER, rate_distribution()).unwrap();
+
+    let token_id = suite.mint_and_open(USER).unwrap();
+    suite.fulfill(&token_id, &RARE).unwrap();
+
+    // item has the same token id as the box
+    let item = suite.item(&token_id);
+    assert_eq!(item.access.owner, USER);
+    assert!(item.info.token_uri.unwrap().starts_with(PREFIX_URI));
+    let metadata = item.info.extension.unwrap();
+    assert_eq!(metadata.rarity, "rare");
+    assert_eq!(metadata.name, Some(String::from("Genesis rare #1")));
+
+    assert!(suite.box_status(&token_id).unwrap().is_received_randomness);
+    assert!(suite.requests().is_empty());
+    let jobs: PendingJobsResponse = suite.query(QueryMsg::ListPendingJobs { start_after: None, limit: None });
+    assert!(jobs.jobs.is_empty());
+
+    let mystery_box = suite.mystery_box();
+    assert_eq!(mystery_box.rate_distribution.unwrap().vec[0].supply, 9);
+    assert_eq!(mystery_box.minted_item, 1);
+
+    let token_id = suite.mint_and_open(USER).unwrap();
+    suite.fulfill(&token_id, &COMMON).unwrap();
+    assert_eq!(suite.item(&token_id).info.extension.unwrap().rarity, "common");
+}
+
+#[test]
+fn delayed_reveal() {
+    let mut suite = Suite::new();
+    let mut info = box_info();
+    info.placeholder_uri = Some(String::from(PLACEHOLDER_URI));
+    info.provenance_hash = Some(String::from(PROVENANCE_HASH));
+    suite.create(OWNER, info).unwrap();
+
+    // prefix uri can only be set by reveal
+    let err = suite.update(OWNER, rate_distribution()).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::FieldLocked { field: String::from("prefix_uri") });
+
+    // uri of item type pool are not hidden by provenance hash
+    let mut msg = rate_distribution();
+    msg.vec[0].prefix_uri = Some(String::from("ipfs://rare/"));
+    msg.vec[0].total_supply = Some(10);
+    let err = suite.update_box(OWNER, BoxUpdate {
+        rate_distribution: Some(msg),
+        ..BoxUpdate::default()
+    }).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidUriPool {});
+
+    suite.update_box(OWNER, BoxUpdate {
+        rate_distribution: Some(rate_distribution()),
+        ..BoxUpdate::default()
+    }).unwrap();
+
+    // items are minted with placeholder uri before reveal
+    let first = suite.mint_and_open(USER).unwrap();
+    suite.fulfill(&first, &RARE).unwrap();
+    let second = suite.mint_and_open(USER).unwrap();
+    suite.fulfill(&second, &COMMON).unwrap();
+    let burned = suite.mint_and_open(USER).unwrap();
+    suite.fulfill(&burned, &COMMON).unwrap();
+    assert_eq!(suite.item(&first).info.token_uri, Some(String::from(PLACEHOLDER_URI)));
+    assert_eq!(suite.item(&second).info.token_uri, Some(String::from(PLACEHOLDER_URI)));
+    assert_eq!(suite.mystery_box().prefix_uri, None);
+
+    // burned item is skipped by reveal
+    let item_supplier = suite.item_supplier.clone();
+    suite.app.execute_contract(
+        Addr::unchecked(USER),
+        item_supplier,
+        &Cw721ExecuteMsg::Burn { token_id: burned.clone() },
+        &[],
+    ).unwrap();
+
+    // event can not be replaced before it's revealed
+    suite.set_time(END + 1);
+    let err = suite.create(OWNER, box_info()).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::MysteryBoxNotRevealed {});
+
+    let reveal = |prefix_uri: &str| ExecuteMsg::Reveal {
+        prefix_uri: String::from(prefix_uri),
+        provenance_hash: String::from(PROVENANCE_HASH),
+    };
+    let err = suite.execute(USER, reveal(PREFIX_URI), &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::Unauthorized {});
+
+    // frozen placeholder uri could never be revealed
+    let err = suite.execute(OWNER, ExecuteMsg::FreezeItemMetadata { token_id: None }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::MysteryBoxNotRevealed {});
+
+    // prefix uri does not match committed hash
+    let err = suite.execute(OWNER, reveal("ipfs://other/"), &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidProvenance {});
+
+    let res = suite.execute(OWNER, reveal(PREFIX_URI), &[]).unwrap();
+    assert_eq!(wasm_attribute(&res, "revealed_items"), "3");
+    assert_eq!(wasm_attribute(&res, "finished"), "true");
+    assert_eq!(suite.mystery_box().prefix_uri, Some(String::from(PREFIX_URI)));
+    for token_id in [&first, &second] {
+        let token_uri = suite.item(token_id).info.token_uri.unwrap();
+        assert!(token_uri.starts_with(PREFIX_URI));
+        assert_ne!(token_uri, PREFIX_URI);
+    }
+    assert!(!suite.item_exists(&burned));
+
+    let mut info = box_info();
+    info.end = EventTime::Rfc3339(String::from("2023-03-01T00:00:00Z"));
+    suite.create(OWNER, info).unwrap();
+
+    // new event is not in delayed reveal mode
+    let err = suite.execute(OWNER, reveal(PREFIX_URI), &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::RevealNotAllowed {});
+}
+
+#[test]
+fn freeze_item_metadata() {
+    let mut suite = Suite::with_event();
+    let first = suite.mint_and_open(USER).unwrap();
+    suite.fulfill(&first, &RARE).unwrap();
+    let second = suite.mint_and_open(USER).unwrap();
+    suite.fulfill(&second, &COMMON).unwrap();
+
+    let item_supplier = suite.item_supplier.clone();
+    let contract = suite.contract.clone();
+    let update = |token_id: &str| cw721_rarity::ExecuteMsg::Extension {
+        msg: cw721_rarity::MetadataMsg::UpdateMetadata {
+            token_id: String::from(token_id),
+            token_uri: Some(String::from("ipfs://upgraded")),
+            extension: None,
+        },
+    };
+    let frozen = |suite: &Suite, token_id: &str| -> bool {
+        let res: cw721_rarity::MetadataFrozenResponse = suite.app.wrap().query_wasm_smart(
+            suite.item_supplier.clone(),
+            &cw721_rarity::QueryMsg::Extension {
+                msg: cw721_rarity::RarityQueryMsg::Metadata(cw721_rarity::MetadataQueryMsg::MetadataFrozen { token_id: String::from(token_id) }),
+            },
+        ).unwrap();
+        res.frozen
+    };
+
+    // only minter can update metadata
+    let err = suite.app.execute_contract(Addr::unchecked(USER), item_supplier.clone(), &update(&first), &[]).unwrap_err();
+    assert_eq!(err.downcast::<cw721_rarity::ContractError>().unwrap(), cw721_rarity::ContractError::Unauthorized {});
+    suite.app.execute_contract(contract.clone(), item_supplier.clone(), &update(&first), &[]).unwrap();
+    assert_eq!(suite.item(&first).info.token_uri, Some(String::from("ipfs://upgraded")));
+    assert_eq!(suite.item(&first).info.extension.unwrap().rarity, "rare");
+
+    let err = suite.execute(USER, ExecuteMsg::FreezeItemMetadata { token_id: None }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::Unauthorized {});
+
+    suite.execute(OWNER, ExecuteMsg::FreezeItemMetadata { token_id: Some(first.clone()) }, &[]).unwrap();
+    assert!(frozen(&suite, &first));
+    assert!(!frozen(&suite, &second));
+    let err = suite.app.execute_contract(contract.clone(), item_supplier.clone(), &update(&first), &[]).unwrap_err();
+    assert!(err.root_cause().to_string().contains("Metadata is frozen"));
+    suite.app.execute_contract(contract.clone(), item_supplier.clone(), &update(&second), &[]).unwrap();
+
+    // unknown token can not be frozen
+    suite.execute(OWNER, ExecuteMsg::FreezeItemMetadata { token_id: Some(String::from("unknown")) }, &[]).unwrap_err();
+
+    // whole collection
+    suite.execute(OWNER, ExecuteMsg::FreezeItemMetadata { token_id: None }, &[]).unwrap();
+    assert!(frozen(&suite, &second));
+    let err = suite.app.execute_contract(contract, item_supplier, &update(&second), &[]).unwrap_err();
+    assert!(err.root_cause().to_string().contains("Metadata is frozen"));
+
+    // items of a delayed reveal event could never be revealed
+    suite.set_time(END + 1);
+    let mut info = box_info();
+    info.end = EventTime::Rfc3339(String::from("2023-03-01T00:00:00Z"));
+    info.placeholder_uri = Some(String::from(PLACEHOLDER_URI));
+    info.provenance_hash = Some(String::from(PROVENANCE_HASH));
+    let err = suite.create(OWNER, info.clone()).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::ItemsFrozen {});
+    info.placeholder_uri = None;
+    info.provenance_hash = None;
+    suite.create(OWNER, info).unwrap();
+}
+
+#[test]
+fn receive_randomness_errors() {
+    let mut suite = Suite::with_event();
+    let token_id = suite.mint_and_open(USER).unwrap();
+    let aurand = suite.aurand.to_string();
+
+    // only aurand can send randomness
+    let err = suite.execute(USER, ExecuteMsg::ReceiveHexRandomness {
+        request_id: token_id.clone(),
+        randomness: RARE.to_vec(),
+    }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::Unauthorized {});
+
+    let err = suite.execute(&aurand, ExecuteMsg::ReceiveHexRandomness {
+        request_id: String::from("unknown"),
+        randomness: RARE.to_vec(),
+    }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::JobNotExist {});
+
+    let err = suite.execute(&aurand, ExecuteMsg::ReceiveHexRandomness {
+        request_id: token_id.clone(),
+        randomness: vec![1],
+    }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidRandomness {});
+
+    let err = suite.execute(&aurand, ExecuteMsg::ReceiveHexRandomness {
+        request_id: token_id.clone(),
+        randomness: vec![10001, 0],
+    }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidRandomness {});
+
+    suite.fulfill(&token_id, &RARE).unwrap();
+
+    // job is removed once fulfilled
+    let err = suite.execute(&aurand, ExecuteMsg::ReceiveHexRandomness {
+        request_id: token_id,
+        randomness: RARE.to_vec(),
+    }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::JobNotExist {});
+}
+
+#[test]
+fn re_request_randomness() {
+    let mut suite = Suite::with_event();
+
+    let err = suite.execute(USER, ExecuteMsg::ReRequestRandomness { token_id: String::from("unknown") }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::TokenNotRecognized {});
+
+    // box is not opened
+    let token_id = suite.mint(USER).unwrap();
+    let err = suite.execute(USER, ExecuteMsg::ReRequestRandomness { token_id: token_id.clone() }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidCondition {});
+
+    // randomness can be re-requested one hour after opening
+    suite.approve_box(USER, &token_id, None).unwrap();
+    suite.open(USER, &token_id).unwrap();
+    let err = suite.execute(USER, ExecuteMsg::ReRequestRandomness { token_id: token_id.clone() }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidCondition {});
+
+    suite.advance(3600);
+    suite.execute(USER, ExecuteMsg::ReRequestRandomness { token_id: token_id.clone() }, &[]).unwrap();
+    let jobs: PendingJobsResponse = suite.query(QueryMsg::ListPendingJobs { start_after: None, limit: None });
+    assert_eq!(jobs.jobs[0].requested_time.seconds(), START + 1 + 3600);
+    assert_eq!(jobs.jobs[0].age, 3600);
+
+    suite.fulfill(&token_id, &RARE).unwrap();
+    let err = suite.execute(USER, ExecuteMsg::ReRequestRandomness { token_id }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidCondition {});
+}
+
+#[test]
+fn retry_jobs() {
+    let mut suite = Suite::with_event();
+    let token_id = suite.mint_and_open(USER).unwrap();
+
+    let err = suite.execute(USER, ExecuteMsg::RetryJobs { request_ids: vec![token_id.clone()] }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::Unauthorized {});
+
+    let err = suite.execute(OWNER, ExecuteMsg::RetryJobs { request_ids: vec![] }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidCondition {});
+
+    let err = suite.execute(OWNER, ExecuteMsg::RetryJobs { request_ids: vec![String::from("unknown")] }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::JobNotExist {});
+
+    suite.advance(60);
+    suite.execute(OWNER, ExecuteMsg::RetryJobs { request_ids: vec![token_id.clone()] }, &[]).unwrap();
+
+    let requests = suite.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].request_id, token_id);
+    let jobs: PendingJobsResponse = suite.query(QueryMsg::ListPendingJobs { start_after: None, limit: None });
+    assert_eq!(jobs.jobs[0].requested_time.seconds(), START + 61);
+}
+
+#[test]
+fn retry_jobs_funds() {
+    let mut suite = Suite::with_event();
+    let first = suite.mint_and_open(USER).unwrap();
+    let second = suite.mint_and_open(USER).unwrap();
+
+    let err = suite.execute(OWNER, ExecuteMsg::RetryJobs { request_ids: vec![first.clone(), first.clone()] }, &coins(4, DENOM)).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::DuplicateRequestId {});
+
+    // funds are split evenly, the remainder is refunded
+    suite.execute(OWNER, ExecuteMsg::RetryJobs { request_ids: vec![first, second] }, &coins(5, DENOM)).unwrap();
+    assert_eq!(suite.balance(suite.aurand.as_str()), Uint128::new(4));
+    assert_eq!(suite.balance(OWNER), Uint128::new(INITIAL_BALANCE - 4));
+}
+
+#[test]
+fn cancel_stuck_job() {
+    let mut suite = Suite::with_event();
+    let token_id = suite.mint_and_open(USER).unwrap();
+    assert_eq!(suite.balance(USER), Uint128::new(INITIAL_BALANCE - PRICE));
+
+    // job which is never fulfilled blocks the next event
+    suite.set_time(END + 1);
+    let mut info = box_info();
+    info.end = EventTime::Rfc3339(String::from("2023-03-01T00:00:00Z"));
+    let err = suite.create(OWNER, info.clone()).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::JobsPending {});
+
+    let err = suite.execute(USER, ExecuteMsg::CancelJob { request_id: token_id.clone() }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::Unauthorized {});
+
+    let err = suite.execute(OWNER, ExecuteMsg::CancelJob { request_id: String::from("unknown") }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::JobNotExist {});
+
+    // price of the burned box is refunded
+    let res = suite.execute(OWNER, ExecuteMsg::CancelJob { request_id: token_id.clone() }, &[]).unwrap();
+    assert_eq!(wasm_attribute(&res, "refund"), coin(PRICE, DENOM).to_string());
+    assert_eq!(suite.balance(USER), Uint128::new(INITIAL_BALANCE));
+    assert_eq!(suite.box_status(&token_id), None);
+
+    let err = suite.fulfill(&token_id, &RARE).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::JobNotExist {});
+
+    suite.create(OWNER, info).unwrap();
+}
+
+#[test]
+fn withdraw() {
+    let mut suite = Suite::with_event();
+    suite.mint(USER).unwrap();
+
+    let err = suite.execute(USER, ExecuteMsg::Withdraw { amount: coin(PRICE, DENOM), receiver: String::from(USER) }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::Unauthorized {});
+
+    let err = suite.execute(OWNER, ExecuteMsg::Withdraw { amount: coin(PRICE + 1, DENOM), receiver: String::from(OWNER) }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InsufficientAmount {});
+
+    suite.execute(OWNER, ExecuteMsg::Withdraw { amount: coin(PRICE, DENOM), receiver: String::from(OTHER) }, &[]).unwrap();
+    assert_eq!(suite.balance(OTHER), Uint128::new(INITIAL_BALANCE + PRICE));
+    assert_eq!(suite.balance(suite.contract.as_str()), Uint128::zero());
+}
+
+#[test]
+fn native_reward() {
+    let mut suite = Suite::new();
+    suite.create(OWNER, box_info()).unwrap();
+
+    // escrow rewards for the whole supply of jackpot
+    let contract = suite.contract.clone();
+    suite.app.send_tokens(Addr::unchecked(OWNER), contract, &coins(100, DENOM)).unwrap();
+    let mut msg = rate_distribution();
+    msg.vec = vec![item_type("jackpot", "0.1", 2)];
+    msg.vec[0].reward = Some(Reward::Native { amount: coin(50, DENOM) });
+    msg.vec[0].mint_item = Some(false);
+    suite.update(OWNER, msg).unwrap();
+
+    // reserved rewards can not be withdrawn
+    let token_id = suite.mint_and_open(USER).unwrap();
+    let err = suite.execute(OWNER, ExecuteMsg::Withdraw { amount: coin(PRICE + 1, DENOM), receiver: String::from(OWNER) }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InsufficientAmount {});
+
+    suite.fulfill(&token_id, &RARE).unwrap();
+    assert_eq!(suite.balance(USER), Uint128::new(INITIAL_BALANCE - PRICE + 50));
+    assert!(!suite.item_exists(&token_id));
+}
+
+#[test]
+fn prizes() {
+    let mut suite = Suite::with_event();
+    let nft = suite.instantiate_nft();
+    for (token_id, owner) in [("prize1", OWNER), ("prize2", USER), ("prize3", OWNER)] {
+        suite.app.execute_contract(
+            Addr::unchecked(OWNER),
+            nft.clone(),
+            &cw721_box::ExecuteMsg::Mint(cw721_box::MintMsg {
+                token_id: String::from(token_id),
+                owner: String::from(owner),
+                token_uri: None,
+                extension: None,
+            }),
+            &[],
+        ).unwrap();
+    }
+    let deposit = |item_type: &str, token_id: &str| Cw721ExecuteMsg::SendNft {
+        contract: suite.contract.to_string(),
+        token_id: String::from(token_id),
+        msg: to_json_binary(&ReceiveNftMsg::DepositPrize { item_type: String::from(item_type) }).unwrap(),
+    };
+    let deposit_rare = deposit("rare", "prize1");
+    let deposit_user = deposit("rare", "prize2");
+    let deposit_unknown = deposit("legendary", "prize3");
+
+    // only owner of contract can deposit prizes
+    let err = suite.app.execute_contract(Addr::unchecked(USER), nft.clone(), &deposit_user, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::Unauthorized {});
+
+    // only allowed collections can be used for prizes
+    let err = suite.app.execute_contract(Addr::unchecked(OWNER), nft.clone(), &deposit_rare, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::PrizeCollectionNotAllowed {});
+    let allow = |contract_addr: &Addr, allowed: bool| ExecuteMsg::SetPrizeCollection { contract_addr: contract_addr.to_string(), allowed };
+    let err = suite.execute(USER, allow(&nft, true), &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::Unauthorized {});
+    let box_supplier = suite.box_supplier.clone();
+    let err = suite.execute(OWNER, allow(&box_supplier, true), &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::PrizeCollectionNotAllowed {});
+    suite.execute(OWNER, allow(&nft, true), &[]).unwrap();
+    let collections: PrizeCollectionsResponse = suite.query(QueryMsg::ListPrizeCollections { start_after: None, limit: None });
+    assert_eq!(collections.collections, vec![nft.clone()]);
+
+    let err = suite.app.execute_contract(Addr::unchecked(OWNER), nft.clone(), &deposit_unknown, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::ItemTypeNotFound {});
+
+    suite.app.execute_contract(Addr::unchecked(OWNER), nft.clone(), &deposit_rare, &[]).unwrap();
+    let prizes: PrizesResponse = suite.query(QueryMsg::ListPrizes { id: 0, item_type: String::from("rare"), start_after: None, limit: None });
+    assert_eq!(prizes.count, 1);
+    assert_eq!(prizes.prizes[0].contract, nft);
+
+    let err = suite.execute(OWNER, ExecuteMsg::WithdrawPrizes {
+        id: 0,
+        item_type: String::from("rare"),
+        limit: None,
+        receiver: String::from(OWNER),
+    }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::MysteryBoxNotEnded {});
+
+    // prize is transferred instead of minting an item
+    let token_id = suite.mint_and_open(USER).unwrap();
+    let simulated: SimulateOpenResponse = suite.query(QueryMsg::SimulateOpen { randomness: RARE.to_vec(), address: None });
+    assert!(simulated.items[0].prize.is_some());
+
+    // prizes can not be withdrawn while open jobs of the event are pending
+    suite.set_time(END);
+    let err = suite.execute(OWNER, ExecuteMsg::WithdrawPrizes {
+        id: 0,
+        item_type: String::from("rare"),
+        limit: None,
+        receiver: String::from(OWNER),
+    }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::JobsPending {});
+
+    suite.fulfill(&token_id, &RARE).unwrap();
+    assert!(!suite.item_exists(&token_id));
+    let owner: cw721::OwnerOfResponse = suite.app.wrap().query_wasm_smart(nft.clone(), &cw721::Cw721QueryMsg::OwnerOf {
+        token_id: String::from("prize1"),
+        include_expired: None,
+    }).unwrap();
+    assert_eq!(owner.owner, USER);
+
+    let prizes: PrizesResponse = suite.query(QueryMsg::ListPrizes { id: 0, item_type: String::from("rare"), start_after: None, limit: None });
+    assert_eq!(prizes.count, 0);
+}
+
+#[test]
+fn prize_draw_keeps_uri() {
+    let mut suite = Suite::new();
+    let mut info = box_info();
+    info.total_supply = 2;
+    info.max_minted_box = Some(2);
+    info.replacement = true;
+    suite.create(OWNER, info).unwrap();
+    suite.update(OWNER, rate_distribution()).unwrap();
+
+    let nft = suite.instantiate_nft();
+    suite.app.execute_contract(Addr::unchecked(OWNER), nft.clone(), &cw721_box::ExecuteMsg::Mint(cw721_box::MintMsg {
+        token_id: String::from("prize"),
+        owner: String::from(OWNER),
+        token_uri: None,
+        extension: None,
+    }), &[]).unwrap();
+    suite.execute(OWNER, ExecuteMsg::SetPrizeCollection { contract_addr: nft.to_string(), allowed: true }, &[]).unwrap();
+    suite.app.execute_contract(Addr::unchecked(OWNER), nft, &Cw721ExecuteMsg::SendNft {
+        contract: suite.contract.to_string(),
+        token_id: String::from("prize"),
+        msg: to_json_binary(&ReceiveNftMsg::DepositPrize { item_type: String::from("rare") }).unwrap(),
+    }, &[]).unwrap();
+
+    // prize is sent without taking a token uri from the pool
+    let token_id = suite.mint_and_open(USER).unwrap();
+    let res = suite.fulfill(&token_id, &RARE).unwrap();
+    assert!(!res.events.iter().any(|event| event.attributes.iter().any(|attr| attr.key == "token_uri")));
+    assert_eq!(suite.mystery_box().remaining_tokens, 2);
+
+    // no prize is left, item is minted
+    let token_id = suite.mint_and_open(USER).unwrap();
+    suite.fulfill(&token_id, &RARE).unwrap();
+    assert!(suite.item_exists(&token_id));
+    assert_eq!(suite.mystery_box().remaining_tokens, 1);
+}
+
+#[test]
+fn withdraw_prizes() {
+    let mut suite = Suite::with_event();
+    let nft = suite.instantiate_nft();
+    suite.app.execute_contract(
+        Addr::unchecked(OWNER),
+        nft.clone(),
+        &cw721_box::ExecuteMsg::Mint(cw721_box::MintMsg {
+            token_id: String::from("prize"),
+            owner: String::from(OWNER),
+            token_uri: None,
+            extension: None,
+        }),
+        &[],
+    ).unwrap();
+    suite.execute(OWNER, ExecuteMsg::SetPrizeCollection { contract_addr: nft.to_string(), allowed: true }, &[]).unwrap();
+    suite.app.execute_contract(Addr::unchecked(OWNER), nft.clone(), &Cw721ExecuteMsg::SendNft {
+        contract: suite.contract.to_string(),
+        token_id: String::from("prize"),
+        msg: to_json_binary(&ReceiveNftMsg::DepositPrize { item_type: String::from("rare") }).unwrap(),
+    }, &[]).unwrap();
+
+    let withdraw = ExecuteMsg::WithdrawPrizes {
+        id: 0,
+        item_type: String::from("rare"),
+        limit: None,
+        receiver: String::from(OTHER),
+    };
+    suite.set_time(END);
+    let err = suite.execute(USER, withdraw.clone(), &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::Unauthorized {});
+
+    suite.execute(OWNER, withdraw, &[]).unwrap();
+    let owner: cw721::OwnerOfResponse = suite.app.wrap().query_wasm_smart(nft, &cw721::Cw721QueryMsg::OwnerOf {
+        token_id: String::from("prize"),
+        include_expired: None,
+    }).unwrap();
+    assert_eq!(owner.owner, OTHER);
+}
+
+#[test]
+fn pity() {
+    let mut suite = Suite::new();
+    suite.create(OWNER, box_info()).unwrap();
+    let mut msg = rate_distribution();
+    msg.vec[0].pity = Some(2);
+    suite.update(OWNER, msg).unwrap();
+
+    let token_id = suite.mint_and_open(USER).unwrap();
+    suite.fulfill(&token_id, &COMMON).unwrap();
+    assert_eq!(suite.item(&token_id).info.extension.unwrap().rarity, "common");
+
+    let counters: PityCounterResponse = suite.query(QueryMsg::GetPityCounter { address: String::from(USER) });
+    assert_eq!(counters.counters[0].item_type, "rare");
+    assert_eq!(counters.counters[0].counter, 1);
+    assert_eq!(counters.counters[0].pity, 2);
+
+    // second open in a row without rare is guaranteed to be rare
+    let token_id = suite.mint_and_open(USER).unwrap();
+    suite.fulfill(&token_id, &COMMON).unwrap();
+    assert_eq!(suite.item(&token_id).info.extension.unwrap().rarity, "rare");
+
+    let counters: PityCounterResponse = suite.query(QueryMsg::GetPityCounter { address: String::from(USER) });
+    assert_eq!(counters.counters[0].counter, 0);
+}
+
+#[test]
+fn bundle_with_guaranteed_type() {
+    let mut suite = Suite::new();
+    suite.create(OWNER, box_info()).unwrap();
+    let mut msg = rate_distribution();
+    msg.items_per_box = Some(3);
+    msg.guaranteed_type = Some(String::from("rare"));
+    suite.update(OWNER, msg).unwrap();
+
+    let token_id = suite.mint_and_open(USER).unwrap();
+    assert_eq!(suite.requests()[0].num, 6);
+
+    // last item is forced to be rare
+    suite.fulfill(&token_id, &[0, 0, 0, 1, 0, 2]).unwrap();
+    let rarities: Vec<String> = (0..3)
+        .map(|i| suite.item(&format!("{}_{}", token_id, i)).info.extension.unwrap().rarity)
+        .collect();
+    assert_eq!(rarities, vec!["common", "common", "rare"]);
+}
+
+#[test]
+fn simulate_open() {
+    let mut suite = Suite::with_event();
+
+    let simulated: SimulateOpenResponse = suite.query(QueryMsg::SimulateOpen { randomness: RARE.to_vec(), address: None });
+    assert_eq!(simulated.items.len(), 1);
+    assert_eq!(simulated.items[0].item_type, "rare");
+    assert!(simulated.items[0].token_uri.starts_with(PREFIX_URI));
+    assert_eq!(simulated.rates[0].supply, 9);
+
+    // simulation does not change state
+    assert_eq!(suite.mystery_box().rate_distribution.unwrap().vec[0].supply, 10);
+
+    // the simulated item is the one drawn
+    let token_id = suite.mint_and_open(USER).unwrap();
+    suite.fulfill(&token_id, &RARE).unwrap();
+    assert_eq!(suite.item(&token_id).info.token_uri, Some(simulated.items[0].token_uri.clone()));
+}
+
+#[test]
+fn reroll() {
+    let mut suite = Suite::new();
+    let mut info = box_info();
+    info.reroll_fee = Some(coin(10, DENOM));
+    suite.create(OWNER, info).unwrap();
+    suite.update(OWNER, rate_distribution()).unwrap();
+
+    let token_id = suite.mint_and_open(USER).unwrap();
+    suite.fulfill(&token_id, &RARE).unwrap();
+    let reroll = ExecuteMsg::Reroll { token_id: token_id.clone() };
+
+    // contract must be approved before the item is burned
+    let err = suite.execute(USER, reroll.clone(), &coins(10, DENOM)).unwrap_err();
+    assert!(matches!(contract_error(err), ContractError::CustomError { .. }));
+    suite.approve_items(USER).unwrap();
+
+    let err = suite.execute(USER, ExecuteMsg::Reroll { token_id: String::from("unknown") }, &coins(10, DENOM)).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::RerollNotAllowed {});
+
+    let err = suite.execute(USER, reroll.clone(), &coins(9, DENOM)).unwrap_err();
+    assert!(matches!(contract_error(err), ContractError::CustomError { .. }));
+
+    let err = suite.execute(OTHER, reroll.clone(), &coins(10, DENOM)).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::Unauthorized {});
+
+    let res = suite.execute(USER, reroll.clone(), &coins(10, DENOM)).unwrap();
+    let new_token_id = wasm_attribute(&res, "token_id");
+    assert!(!suite.item_exists(&token_id));
+
+    // burned rare item goes back to mystery box
+    assert_eq!(suite.mystery_box().rate_distribution.unwrap().vec[0].supply, 10);
+    let jobs: PendingJobsResponse = suite.query(QueryMsg::ListPendingJobs { start_after: None, limit: None });
+    assert_eq!(jobs.jobs[0].kind, JobKind::Reroll { burned_token_id: token_id.clone() });
+
+    suite.fulfill(&new_token_id, &COMMON).unwrap();
+    let item = suite.item(&new_token_id);
+    assert_eq!(item.access.owner, USER);
+    assert_eq!(item.info.extension.unwrap().rarity, "common");
+
+    // burned item can not be re-rolled again
+    let err = suite.execute(USER, reroll, &coins(10, DENOM)).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::RerollNotAllowed {});
+}
+
+#[test]
+fn reroll_keeps_pity_counters() {
+    let mut suite = Suite::new();
+    let mut info = box_info();
+    info.reroll_fee = Some(coin(10, DENOM));
+    suite.create(OWNER, info).unwrap();
+    let mut msg = rate_distribution();
+    msg.vec[0].pity = Some(2);
+    suite.update(OWNER, msg).unwrap();
+
+    let token_id = suite.mint_and_open(USER).unwrap();
+    suite.fulfill(&token_id, &COMMON).unwrap();
+    suite.approve_items(USER).unwrap();
+
+    // reroll is not helped by pity and does not move the counter
+    let res = suite.execute(USER, ExecuteMsg::Reroll { token_id }, &coins(10, DENOM)).unwrap();
+    let new_token_id = wasm_attribute(&res, "token_id");
+    suite.fulfill(&new_token_id, &COMMON).unwrap();
+    assert_eq!(suite.item(&new_token_id).info.extension.unwrap().rarity, "common");
+
+    let counters: PityCounterResponse = suite.query(QueryMsg::GetPityCounter { address: String::from(USER) });
+    assert_eq!(counters.counters[0].counter, 1);
+
+    // next open is still guaranteed to be rare
+    let token_id = suite.mint_and_open(USER).unwrap();
+    suite.fulfill(&token_id, &COMMON).unwrap();
+    assert_eq!(suite.item(&token_id).info.extension.unwrap().rarity, "rare");
+}
+
+#[test]
+fn reroll_item_with_reward() {
+    let mut suite = Suite::new();
+    let mut info = box_info();
+    info.reroll_fee = Some(coin(10, DENOM));
+    suite.create(OWNER, info).unwrap();
+
+    // escrow rewards for the whole supply of rare
+    let contract = suite.contract.clone();
+    suite.app.send_tokens(Addr::unchecked(OWNER), contract, &coins(50, DENOM)).unwrap();
+    let mut msg = rate_distribution();
+    msg.vec[0].reward = Some(Reward::Native { amount: coin(5, DENOM) });
+    suite.update(OWNER, msg).unwrap();
+
+    let token_id = suite.mint_and_open(USER).unwrap();
+    suite.fulfill(&token_id, &RARE).unwrap();
+    suite.approve_items(USER).unwrap();
+
+    // reward paid with the item can not be taken back
+    let err = suite.execute(USER, ExecuteMsg::Reroll { token_id }, &coins(10, DENOM)).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::RerollNotAllowed {});
+}
+
+#[test]
+fn reroll_not_allowed() {
+    let mut suite = Suite::with_event();
+    let token_id = suite.mint_and_open(USER).unwrap();
+    suite.fulfill(&token_id, &RARE).unwrap();
+    suite.approve_items(USER).unwrap();
+
+    let err = suite.execute(USER, ExecuteMsg::Reroll { token_id: token_id.clone() }, &coins(10, DENOM)).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::RerollNotAllowed {});
+
+    suite.set_time(END);
+    let err = suite.execute(USER, ExecuteMsg::Reroll { token_id }, &coins(10, DENOM)).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::MysteryBoxEnded {});
+}
+
+fn fusion_output(rarity: &str, rate: &str) -> FusionOutput {
+    FusionOutput {
+        rarity: String::from(rarity),
+        rate: rate.parse::<Decimal>().unwrap(),
+    }
+}
+
+#[test]
+fn fuse() {
+    let mut suite = Suite::with_event();
+    let mut items: Vec<String> = Vec::new();
+    for randomness in [COMMON, COMMON, RARE] {
+        let token_id = suite.mint_and_open(USER).unwrap();
+        suite.fulfill(&token_id, &randomness).unwrap();
+        items.push(token_id);
+    }
+    suite.approve_items(USER).unwrap();
+    let fuse = ExecuteMsg::Fuse { token_ids: vec![items[0].clone(), items[1].clone()] };
+
+    let err = suite.execute(USER, fuse.clone(), &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::FusionRecipeNotFound {});
+
+    let recipe = FusionRecipe { input_count: 2, outputs: vec![fusion_output("epic", "1")] };
+    let err = suite.execute(USER, ExecuteMsg::SetFusionRecipe { rarity: String::from("common"), recipe: Some(recipe.clone()) }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::Unauthorized {});
+
+    let invalid = FusionRecipe { input_count: 2, outputs: vec![fusion_output("epic", "0.5")] };
+    let err = suite.execute(OWNER, ExecuteMsg::SetFusionRecipe { rarity: String::from("common"), recipe: Some(invalid) }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidFusionRecipe {});
+
+    suite.execute(OWNER, ExecuteMsg::SetFusionRecipe { rarity: String::from("common"), recipe: Some(recipe.clone()) }, &[]).unwrap();
+    let saved: Option<FusionRecipe> = suite.query(QueryMsg::GetFusionRecipe { rarity: String::from("common") });
+    assert_eq!(saved, Some(recipe));
+
+    // items must be different, of the same rarity and match input count
+    let err = suite.execute(USER, ExecuteMsg::Fuse { token_ids: vec![items[0].clone(), items[0].clone()] }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidCondition {});
+    let err = suite.execute(USER, ExecuteMsg::Fuse { token_ids: vec![items[0].clone(), items[2].clone()] }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidCondition {});
+    let err = suite.execute(USER, ExecuteMsg::Fuse { token_ids: vec![items[0].clone()] }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidCondition {});
+
+    let err = suite.execute(OTHER, fuse.clone(), &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::Unauthorized {});
+
+    // only one output, fused item is minted immediately
+    let res = suite.execute(USER, fuse, &[]).unwrap();
+    let token_id = wasm_attribute(&res, "token_id");
+    assert!(token_id.starts_with("fuse_"));
+    assert!(!suite.item_exists(&items[0]));
+    assert!(!suite.item_exists(&items[1]));
+    let item = suite.item(&token_id);
+    assert_eq!(item.access.owner, USER);
+    // fused item takes no uri from uri pools of the event
+    assert_eq!(item.info.token_uri, None);
+    let metadata = item.info.extension.unwrap();
+    assert_eq!(metadata.rarity, "epic");
+    assert!(metadata.attributes.unwrap().iter().any(|attr| attr.trait_type == "item_type" && attr.value == "epic"));
+    assert_eq!(suite.mystery_box().minted_item, 4);
+}
+
+#[test]
+fn fuse_with_randomness() {
+    let mut suite = Suite::with_event();
+    let mut items: Vec<String> = Vec::new();
+    for _ in 0..2 {
+        let token_id = suite.mint_and_open(USER).unwrap();
+        suite.fulfill(&token_id, &COMMON).unwrap();
+        items.push(token_id);
+    }
+
+    let recipe = FusionRecipe {
+        input_count: 2,
+        outputs: vec![fusion_output("epic", "0.5"), fusion_output("rare", "0.5")],
+    };
+    suite.execute(OWNER, ExecuteMsg::SetFusionRecipe { rarity: String::from("common"), recipe: Some(recipe) }, &[]).unwrap();
+
+    // contract must be approved before the items are burned
+    let err = suite.execute(USER, ExecuteMsg::Fuse { token_ids: items.clone() }, &[]).unwrap_err();
+    assert!(matches!(contract_error(err), ContractError::CustomError { .. }));
+    suite.approve_items(USER).unwrap();
+
+    let res = suite.execute(USER, ExecuteMsg::Fuse { token_ids: items }, &[]).unwrap();
+    let token_id = wasm_attribute(&res, "token_id");
+    assert!(!suite.item_exists(&token_id));
+    assert_eq!(suite.requests()[0].num, 2);
+
+    suite.fulfill(&token_id, &[0, 0]).unwrap();
+    assert_eq!(suite.item(&token_id).info.extension.unwrap().rarity, "epic");
+    assert_eq!(suite.mystery_box().minted_item, 3);
+
+    // recipe can be removed
+    suite.execute(OWNER, ExecuteMsg::SetFusionRecipe { rarity: String::from("common"), recipe: None }, &[]).unwrap();
+    let saved: Option<FusionRecipe> = suite.query(QueryMsg::GetFusionRecipe { rarity: String::from("common") });
+    assert_eq!(saved, None);
+}
+
+#[test]
+fn fuse_after_uri_pool_sold_out() {
+    let mut suite = Suite::new();
+    let mut info = box_info();
+    info.replacement = true;
+    info.total_supply = 2;
+    info.max_minted_box = Some(2);
+    suite.create(OWNER, info).unwrap();
+    suite.update(OWNER, rate_distribution()).unwrap();
+
+    let mut items: Vec<String> = Vec::new();
+    for _ in 0..2 {
+        let token_id = suite.mint_and_open(USER).unwrap();
+        suite.fulfill(&token_id, &COMMON).unwrap();
+        items.push(token_id);
+    }
+    assert_eq!(suite.mystery_box().remaining_tokens, 0);
+    suite.approve_items(USER).unwrap();
+
+    let recipe = FusionRecipe {
+        input_count: 2,
+        outputs: vec![fusion_output("epic", "0.5"), fusion_output("rare", "0.5")],
+    };
+    suite.execute(OWNER, ExecuteMsg::SetFusionRecipe { rarity: String::from("common"), recipe: Some(recipe) }, &[]).unwrap();
+
+    // burned items are always replaced, fusion does not need uri of boxes
+    let res = suite.execute(USER, ExecuteMsg::Fuse { token_ids: items }, &[]).unwrap();
+    let token_id = wasm_attribute(&res, "token_id");
+    suite.fulfill(&token_id, &[0, 0]).unwrap();
+    let item = suite.item(&token_id);
+    assert_eq!(item.access.owner, USER);
+    assert_eq!(item.info.token_uri, None);
+}
+
+#[test]
+fn sell_box() {
+    let mut suite = Suite::with_event();
+    let token_id = suite.mint(USER).unwrap();
+    suite.approve_box(USER, &token_id, None).unwrap();
+    let sell = ExecuteMsg::SellBox { token_id: token_id.clone() };
+
+    let err = suite.execute(USER, sell.clone(), &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::BuybackNotAllowed {});
+
+    let buyback = BoxBuyback::Percentage { percentage: Decimal::percent(50) };
+    let err = suite.execute(USER, ExecuteMsg::SetBoxBuyback { buyback: Some(buyback.clone()) }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::Unauthorized {});
+
+    let err = suite.execute(OWNER, ExecuteMsg::SetBoxBuyback { buyback: Some(BoxBuyback::Percentage { percentage: Decimal::zero() }) }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidBuyback {});
+
+    // fixed price in another denom is never paid
+    suite.execute(OWNER, ExecuteMsg::SetBoxBuyback { buyback: Some(BoxBuyback::Fixed { price: coin(1, "uother") }) }, &[]).unwrap();
+    let err = suite.execute(USER, sell.clone(), &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidBuyback {});
+
+    // fixed price is capped at box price, contract can not pay more than it holds
+    suite.execute(OWNER, ExecuteMsg::Withdraw { amount: coin(1, DENOM), receiver: String::from(OWNER) }, &[]).unwrap();
+    suite.execute(OWNER, ExecuteMsg::SetBoxBuyback { buyback: Some(BoxBuyback::Fixed { price: coin(PRICE + 1, DENOM) }) }, &[]).unwrap();
+    let err = suite.execute(USER, sell.clone(), &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InsufficientAmount {});
+
+    suite.execute(OWNER, ExecuteMsg::SetBoxBuyback { buyback: Some(buyback.clone()) }, &[]).unwrap();
+    let saved: Option<BoxBuyback> = suite.query(QueryMsg::GetBoxBuyback {});
+    assert_eq!(saved, Some(buyback));
+
+    let err = suite.execute(OTHER, sell.clone(), &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::Unauthorized {});
+
+    let err = suite.execute(USER, ExecuteMsg::SellBox { token_id: String::from("unknown") }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::TokenNotRecognized {});
+
+    suite.execute(USER, sell.clone(), &[]).unwrap();
+    assert_eq!(suite.balance(USER), Uint128::new(INITIAL_BALANCE - PRICE / 2));
+    assert!(suite.box_owner(&token_id).is_err());
+    assert_eq!(suite.box_status(&token_id), None);
+
+    // box is back in stock
+    let mystery_box = suite.mystery_box();
+    assert_eq!(mystery_box.max_minted_box, 100);
+    assert_eq!(mystery_box.minted_box, 0);
+    assert_eq!(mystery_box.bought_back_box, 1);
+
+    // box minted after a buyback in the same block gets a new token id
+    let first = suite.mint(USER).unwrap();
+    let second = suite.mint(USER).unwrap();
+    suite.approve_box(USER, &first, None).unwrap();
+    suite.execute(USER, ExecuteMsg::SellBox { token_id: first.clone() }, &[]).unwrap();
+    let third = suite.mint(USER).unwrap();
+    assert_ne!(third, first);
+    assert_ne!(third, second);
+    assert_eq!(suite.box_owner(&second).unwrap().owner, USER);
+
+    // opened box can not be sold
+    let token_id = suite.mint_and_open(USER).unwrap();
+    let err = suite.execute(USER, ExecuteMsg::SellBox { token_id }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::BoxOpened {});
+
+    // contract must be approved to burn the box, without expiration
+    let token_id = suite.mint(USER).unwrap();
+    let err = suite.execute(USER, ExecuteMsg::SellBox { token_id: token_id.clone() }, &[]).unwrap_err();
+    assert!(matches!(contract_error(err), ContractError::CustomError { .. }));
+    suite.approve_box(USER, &token_id, Some(Expiration::AtHeight(1_000_000))).unwrap();
+    let err = suite.execute(USER, ExecuteMsg::SellBox { token_id }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::Unauthorized {});
+}
+
+#[test]
+fn sell_item() {
+    let mut suite = Suite::with_event();
+    let common = suite.mint_and_open(USER).unwrap();
+    suite.fulfill(&common, &COMMON).unwrap();
+    let rare = suite.mint_and_open(USER).unwrap();
+    suite.fulfill(&rare, &RARE).unwrap();
+
+    let err = suite.execute(USER, ExecuteMsg::SetItemBuyback { rarity: String::from("common"), payout: Some(coin(30, DENOM)) }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::Unauthorized {});
+
+    let err = suite.execute(OWNER, ExecuteMsg::SetItemBuyback { rarity: String::from("common"), payout: Some(coin(0, DENOM)) }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidBuyback {});
+
+    suite.execute(OWNER, ExecuteMsg::SetItemBuyback { rarity: String::from("common"), payout: Some(coin(30, DENOM)) }, &[]).unwrap();
+    let saved: Option<cosmwasm_std::Coin> = suite.query(QueryMsg::GetItemBuyback { rarity: String::from("common") });
+    assert_eq!(saved, Some(coin(30, DENOM)));
+
+    // contract must be approved before the item is burned
+    let err = suite.execute(USER, ExecuteMsg::SellItem { token_id: common.clone() }, &[]).unwrap_err();
+    assert!(matches!(contract_error(err), ContractError::CustomError { .. }));
+    suite.approve_items(USER).unwrap();
+
+    let err = suite.execute(USER, ExecuteMsg::SellItem { token_id: rare }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::BuybackNotAllowed {});
+
+    let err = suite.execute(OTHER, ExecuteMsg::SellItem { token_id: common.clone() }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::Unauthorized {});
+
+    suite.execute(USER, ExecuteMsg::SellItem { token_id: common.clone() }, &[]).unwrap();
+    assert_eq!(suite.balance(USER), Uint128::new(INITIAL_BALANCE - 2 * PRICE + 30));
+    assert!(!suite.item_exists(&common));
+
+    let err = suite.execute(USER, ExecuteMsg::SellItem { token_id: common }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::TokenNotRecognized {});
+}
+
+#[test]
+fn royalties() {
+    let mut suite = Suite::new();
+    let mut info = box_info();
+    info.royalty_payment_address = Some(String::from(OWNER));
+    info.royalty_percentage = Some(10);
+    suite.create(OWNER, info).unwrap();
+    suite.update(OWNER, rate_distribution()).unwrap();
+
+    let token_id = suite.mint_and_open(USER).unwrap();
+    suite.fulfill(&token_id, &RARE).unwrap();
+
+    let metadata = suite.item(&token_id).info.extension.unwrap();
+    assert_eq!(metadata.royalty_payment_address, Some(String::from(OWNER)));
+    assert_eq!(metadata.royalty_percentage, Some(10));
+
+    let royalty: cw721_rarity::RoyaltiesInfoResponse = suite.app.wrap().query_wasm_smart(
+        suite.item_supplier.clone(),
+        &cw721_rarity::QueryMsg::Extension {
+            msg: cw721_rarity::RarityQueryMsg::Royalty(cw721_rarity::Cw2981QueryMsg::RoyaltyInfo { token_id, sale_price: Uint128::new(1000) }),
+        },
+    ).unwrap();
+    assert_eq!(royalty.address, OWNER);
+    assert_eq!(royalty.royalty_amount, Uint128::new(100));
+
+    let check: cw721_rarity::CheckRoyaltiesResponse = suite.app.wrap().query_wasm_smart(
+        suite.item_supplier.clone(),
+        &cw721_rarity::QueryMsg::Extension {
+            msg: cw721_rarity::RarityQueryMsg::Royalty(cw721_rarity::Cw2981QueryMsg::CheckRoyalties {}),
+        },
+    ).unwrap();
+    assert!(check.royalty_payments);
+
+    // royalty percentage over 100 is rejected by both cw721 contracts
+    let contract = suite.contract.clone();
+    let item_supplier = suite.item_supplier.clone();
+    let err = suite.app.execute_contract(contract.clone(), item_supplier, &cw721_rarity::ExecuteMsg::Mint(cw721_rarity::MintMsg {
+        token_id: String::from("invalid"),
+        owner: String::from(USER),
+        token_uri: None,
+        extension: Some(cw721_rarity::Metadata {
+            royalty_payment_address: Some(String::from(OWNER)),
+            royalty_percentage: Some(101),
+            ..cw721_rarity::Metadata::default()
+        }),
+    }), &[]).unwrap_err();
+    assert!(err.root_cause().to_string().contains("Royalty percentage must be between 0 and 100"));
+
+    let box_supplier = suite.box_supplier.clone();
+    let err = suite.app.execute_contract(contract, box_supplier, &cw721_box::ExecuteMsg::Mint(cw721_box::MintMsg {
+        token_id: String::from("invalid"),
+        owner: String::from(USER),
+        token_uri: None,
+        extension: Some(cw721_box::Metadata {
+            royalty_payment_address: Some(String::from(OWNER)),
+            royalty_percentage: Some(101),
+            ..cw721_box::Metadata::default()
+        }),
+    }), &[]).unwrap_err();
+    assert!(err.root_cause().to_string().contains("Royalty percentage must be between 0 and 100"));
+}
+
+#[test]
+fn create_mystery_box_with_pending_jobs() {
+    let mut suite = Suite::with_event();
+    let token_id = suite.mint_and_open(USER).unwrap();
+    let jobs: PendingJobsResponse = suite.query(QueryMsg::ListPendingJobs { start_after: None, limit: None });
+    assert_eq!(jobs.jobs[0].id, 0);
+
+    // pending open job draws from current event, so it can not be replaced
+    suite.set_time(END + 1);
+    let mut info = box_info();
+    info.end = EventTime::Rfc3339(String::from("2023-03-01T00:00:00Z"));
+    let err = suite.create(OWNER, info.clone()).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::JobsPending {});
+
+    suite.fulfill(&token_id, &RARE).unwrap();
+    suite.create(OWNER, info).unwrap();
+    assert_eq!(suite.item(&token_id).info.extension.unwrap().rarity, "rare");
+}
+
+#[test]
+fn prune_history() {
+    let mut suite = Suite::with_event();
+    let first = suite.mint(USER).unwrap();
+    let second = suite.mint(USER).unwrap();
+
+    // purchase records of current event are still in use
+    let err = suite.execute(OWNER, ExecuteMsg::PruneHistory { event_id: 0, limit: None }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::InvalidCondition {});
+
+    suite.set_time(END + 1);
+    let mut info = box_info();
+    info.end = EventTime::Rfc3339(String::from("2023-03-01T00:00:00Z"));
+    suite.create(OWNER, info).unwrap();
+
+    let err = suite.execute(USER, ExecuteMsg::PruneHistory { event_id: 0, limit: None }, &[]).unwrap_err();
+    assert_eq!(contract_error(err), ContractError::Unauthorized {});
+
+    let res = suite.execute(OWNER, ExecuteMsg::PruneHistory { event_id: 0, limit: Some(1) }, &[]).unwrap();
+    assert_eq!(wasm_attribute(&res, "pruned"), "1");
+    let remaining = [&first, &second].iter()
+        .filter(|token_id| suite.query::<Option<PurchasedBox>>(QueryMsg::GetBoxStatus { token_id: token_id.to_string(), id: Some(0) }).is_some())
+        .count();
+    assert_eq!(remaining, 1);
+
+    let res = suite.execute(OWNER, ExecuteMsg::PruneHistory { event_id: 0, limit: None }, &[]).unwrap();
+    assert_eq!(wasm_attribute(&res, "pruned"), "1");
+    let status: Option<PurchasedBox> = suite.query(QueryMsg::GetBoxStatus { token_id: first, id: Some(0) });
+    assert_eq!(status, None);
+}
@@ -1,7 +1,9 @@
 use sha2::{Sha256,Digest};
 use cosmwasm_std::Timestamp;
-use chrono::{DateTime, Local};
+use chrono::DateTime;
+use cw_utils::Scheduled;
 use crate::error::ContractError;
+use crate::msg::EventTime;
 
 /// calculate sha256 hash value
 pub fn sha256_hash(string: &[u8]) -> Vec<u8> {
@@ -11,20 +13,43 @@ pub fn sha256_hash(string: &[u8]) -> Vec<u8> {
     // read hash digest and consume hasher
     let result = hasher.finalize();
 
-    return result.to_vec();
+    result.to_vec()
 }
 
 /// make id string from param1 and param2
 pub fn make_id(params: Vec<String>) -> String {
     let seed = params.join("");
-    return hex::encode(sha256_hash(seed.as_bytes()));
+    hex::encode(sha256_hash(seed.as_bytes()))
 }
 
-/// convert time with format "D:M:Y s:m:hZ" to Timestamp
+/// derive a random u128 number from list of random numbers
+pub fn random_u128(randomness: &[u128]) -> u128 {
+    let bytes: Vec<u8> = randomness.iter().flat_map(|r| r.to_be_bytes()).collect();
+    let hash = sha256_hash(&bytes);
+
+    let mut value = [0u8; 16];
+    value.copy_from_slice(&hash[0..16]);
+    u128::from_be_bytes(value)
+}
+
+/// convert RFC 3339 time (e.g. "2023-01-09T02:01:26Z") to Timestamp
+/// the offset must be given, so result does not depend on timezone of the host
 pub fn convert_datetime_string(data: String) -> Result<Timestamp, ContractError> {
-    let date_time = data.parse::<DateTime<Local>>()
-        .map_err(|_| ContractError::CustomError{val: String::from("Invalid date string format!")})?;
-    return Ok(Timestamp::from_nanos(date_time.timestamp_nanos() as u64));
+    let date_time = DateTime::parse_from_rfc3339(&data)
+        .map_err(|_| ContractError::InvalidDateFormat{})?;
+    let nanos = date_time.timestamp_nanos_opt()
+        .and_then(|nanos| u64::try_from(nanos).ok())
+        .ok_or(ContractError::InvalidDateFormat{})?;
+    Ok(Timestamp::from_nanos(nanos))
+}
+
+/// convert time of an event to Scheduled
+pub fn convert_event_time(time: &EventTime) -> Result<Scheduled, ContractError> {
+    match time {
+        EventTime::AtHeight(height) => Ok(Scheduled::AtHeight(*height)),
+        EventTime::AtTime(time) => Ok(Scheduled::AtTime(*time)),
+        EventTime::Rfc3339(data) => Ok(Scheduled::AtTime(convert_datetime_string(data.clone())?)),
+    }
 }
 
 /* /// max usize value 2 ^ 64 - 1
@@ -40,6 +65,53 @@ pub fn uint256_2_usize(u: Uint256) -> Result<usize, ContractError> {
                 bytes[4], bytes[5], bytes[6], bytes[7]]));
 } */
 
+/// replace each {key} in template with its value
+/// template is scanned once from left to right, so placeholders inside values are not replaced
+pub fn render_template(template: &str, values: &[(&str, String)]) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        rendered.push_str(&rest[..open]);
+        rest = &rest[open..];
+        let value = rest.find('}').and_then(|close| {
+            values.iter()
+                .find(|(key, _)| *key == &rest[1..close])
+                .map(|(_, value)| (close, value))
+        });
+        match value {
+            Some((close, value)) => {
+                rendered.push_str(value);
+                rest = &rest[close + 1..];
+            },
+            // unknown placeholder or single brace is kept
+            None => {
+                rendered.push('{');
+                rest = &rest[1..];
+            },
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// splitmix64, deterministic source of randomness for simulations and statistical tests
+pub struct SplitMix64(pub u64);
+
+impl SplitMix64 {
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// uniform number in 0..=max, same range as aurand randomness
+    pub fn range(&mut self, max: u64) -> u64 {
+        self.next_u64() % (max + 1)
+    }
+}
+
 #[cfg(test)]
 mod unit_tests {
     use super::*;
@@ -51,20 +123,56 @@ mod unit_tests {
         vec![117, 174, 233, 220, 201, 251, 231, 221, 201, 57, 79, 91, 197, 211, 141, 159, 90, 211, 97, 240, 82, 15, 124, 234, 181, 150, 22, 227, 143, 89, 80, 181]);
     }
 
+    #[test]
+    fn render_template_success() {
+        let values = [("event_name", String::from("Genesis")), ("n", String::from("7"))];
+        assert_eq!(render_template("{event_name} #{n}", &values), String::from("Genesis #7"));
+        // unknown placeholders are kept
+        assert_eq!(render_template("{item_type} #{n}", &values), String::from("{item_type} #7"));
+        assert_eq!(render_template("{{n}} {n", &values), String::from("{7} {n"));
+
+        // values are not rendered again
+        let values = [("event_name", String::from("{n}")), ("n", String::from("7"))];
+        assert_eq!(render_template("{event_name} #{n}", &values), String::from("{n} #7"));
+    }
+
     #[test]
     fn convert_datetime_success() {
         let time: String = String::from(r#"2023-01-09 02:01:26Z"#);
         assert_eq!(convert_datetime_string(time).unwrap().seconds(),1673229686);
     }
 
+    #[test]
+    fn convert_datetime_with_offset_success() {
+        let time: String = String::from(r#"2023-01-09T09:01:26+07:00"#);
+        assert_eq!(convert_datetime_string(time).unwrap().seconds(),1673229686);
+    }
+
     #[test]
     fn convert_datetime_fail_with_invalid_format() {
         let time: String = String::from(r#"2023/01/09 02:01:26Z"#);
         let date = convert_datetime_string(time).unwrap_err();
         match date {
-            ContractError::CustomError{val: v} => {assert_eq!(v, String::from("Invalid date string format!"))},
+            ContractError::InvalidDateFormat{} => {},
             _ => panic!(),
         }
+
+        // time without offset is ambiguous
+        let time: String = String::from(r#"2023-01-09 02:01:26"#);
+        assert_eq!(convert_datetime_string(time).unwrap_err(), ContractError::InvalidDateFormat{});
+
+        // time before unix epoch can not be a block time
+        let time: String = String::from(r#"1969-12-31T23:59:59Z"#);
+        assert_eq!(convert_datetime_string(time).unwrap_err(), ContractError::InvalidDateFormat{});
+    }
+
+    #[test]
+    fn convert_event_time_success() {
+        assert_eq!(convert_event_time(&EventTime::AtHeight(100)).unwrap(), Scheduled::AtHeight(100));
+        assert_eq!(convert_event_time(&EventTime::AtTime(Timestamp::from_seconds(1673229686))).unwrap(),
+            Scheduled::AtTime(Timestamp::from_seconds(1673229686)));
+        assert_eq!(convert_event_time(&EventTime::Rfc3339(String::from("2023-01-09T02:01:26Z"))).unwrap(),
+            Scheduled::AtTime(Timestamp::from_seconds(1673229686)));
     }
 
     #[test]
@@ -0,0 +1,423 @@
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use cosmwasm_std::{BlockInfo, Coin, Decimal};
+use cw_utils::Scheduled;
+
+use crate::msg::{BoxInfo, RateDistributionMsg};
+use crate::state::{DrawMode, RateDistribution, DEFAULT_ITEM_TYPE};
+use crate::utils::convert_event_time;
+use crate::ContractError;
+
+/// check information of a new mystery box event created at block
+/// return start and end of the event
+pub fn validate_box_info(box_info: &BoxInfo, block: &BlockInfo) -> Result<(Scheduled, Scheduled), ContractError> {
+    validate_name(&box_info.name)?;
+
+    let start = convert_event_time(&box_info.start)?;
+    let end = convert_event_time(&box_info.end)?;
+    validate_schedule(&start, &end, block)?;
+
+    // uri are made from 0..total_supply, so at least one is needed
+    if box_info.total_supply == 0 {
+        return Err(ContractError::InvalidTotalSupply{});
+    }
+
+    validate_box_supply(box_info.max_minted_box, box_info.total_supply, box_info.replacement, None)?;
+
+    validate_price(&box_info.price)?;
+    if let Some(reroll_fee) = &box_info.reroll_fee {
+        validate_price(reroll_fee)?;
+    }
+
+    validate_provenance(&box_info.placeholder_uri, &box_info.provenance_hash)?;
+
+    Ok((start, end))
+}
+
+/// event must have a name
+pub fn validate_name(name: &str) -> Result<(), ContractError> {
+    if name.trim().is_empty() {
+        return Err(ContractError::InvalidName{});
+    }
+    Ok(())
+}
+
+/// check start and end of an event at block
+pub fn validate_schedule(start: &Scheduled, end: &Scheduled, block: &BlockInfo) -> Result<(), ContractError> {
+    // event must start before it ends
+    // a height and a time can not be compared, so the order is only checked if both are the same kind
+    if matches!(start.partial_cmp(end), Some(Ordering::Equal | Ordering::Greater)) {
+        return Err(ContractError::InvalidTimeRange{});
+    }
+
+    // event must not be over already
+    if end.is_triggered(block) {
+        return Err(ContractError::InvalidTime{});
+    }
+
+    Ok(())
+}
+
+/// check number of boxes of an event against number of uri
+/// total_supply is number of uri left in shared uri pool, rate_distribution is set once the event is updated
+pub fn validate_box_supply(
+    max_minted_box: Option<u64>,
+    total_supply: u64,
+    replacement: bool,
+    rate_distribution: Option<&RateDistribution>,
+) -> Result<(), ContractError> {
+    // event without any box is sold out from the start
+    if max_minted_box == Some(0) {
+        return Err(ContractError::InvalidBoxSupply{});
+    }
+
+    // because of replacement is true minted NFTs will be generated from unique uri
+    // so every item drawn from boxes must find an uri left in its pool
+    if !replacement {
+        return Ok(());
+    }
+    let boxes = max_minted_box.unwrap_or(u64::MAX);
+
+    // until rate distribution is set, each box is counted as one item from shared uri pool
+    let rate_distribution = match rate_distribution {
+        Some(rate_distribution) => rate_distribution,
+        None if boxes > total_supply => return Err(ContractError::InsufficientUriSupply{}),
+        None => return Ok(()),
+    };
+
+    // an item_type can be drawn up to its supply, no uri is taken for reward only item_type
+    let draws = boxes.saturating_mul(rate_distribution.items_per_box as u64);
+    let mut shared_draws = 0u64;
+    for item_type in rate_distribution.vec.iter().filter(|item_type| item_type.mint_item) {
+        let item_type_draws = draws.min(item_type.supply as u64);
+        match &item_type.pool {
+            Some(pool) if item_type_draws > pool.total_supply => return Err(ContractError::InvalidUriPool{}),
+            Some(_) => {},
+            None => shared_draws = shared_draws.saturating_add(item_type_draws),
+        }
+    }
+    if draws.min(shared_draws) > total_supply {
+        return Err(ContractError::InsufficientUriSupply{});
+    }
+
+    Ok(())
+}
+
+/// zero amount can not be sent, so a zero price could never be paid
+pub fn validate_price(price: &Coin) -> Result<(), ContractError> {
+    if price.amount.is_zero() {
+        return Err(ContractError::InvalidPrice{});
+    }
+    Ok(())
+}
+
+/// delayed reveal needs both placeholder uri and provenance hash, which is hex encoded sha256
+pub fn validate_provenance(placeholder_uri: &Option<String>, provenance_hash: &Option<String>) -> Result<(), ContractError> {
+    match (placeholder_uri, provenance_hash) {
+        (None, None) => Ok(()),
+        (Some(_), Some(hash)) if hex::decode(hash).is_ok_and(|bytes| bytes.len() == 32) => Ok(()),
+        _ => Err(ContractError::InvalidProvenance{}),
+    }
+}
+
+/// check rules of a rate distribution which don't depend on draw mode internals
+pub fn validate_rate_distribution(msg: &RateDistributionMsg) -> Result<(), ContractError> {
+    // item types are looked up by name, so names must be set and unique, including default item type
+    let default_type = msg.default_type.clone().unwrap_or_else(|| String::from(DEFAULT_ITEM_TYPE));
+    let mut names: BTreeSet<&str> = BTreeSet::new();
+    for name in msg.vec.iter().map(|item_type| item_type.name.as_str()).chain([default_type.as_str()]) {
+        if name.trim().is_empty() {
+            return Err(ContractError::InvalidName{});
+        }
+        if !names.insert(name) {
+            return Err(ContractError::DuplicateItemType{});
+        }
+    }
+
+    // in deck mode rate is ignored
+    if msg.mode.clone().unwrap_or_default() == DrawMode::Deck {
+        return Ok(());
+    }
+
+    // check if 0 < rate < 1 and total rate <= 1
+    // item type without supply could never be drawn, its rate would always fall to default item type
+    let mut total_rate = Decimal::zero();
+    for item_type in msg.vec.iter() {
+        if item_type.rate.is_zero() || item_type.rate >= Decimal::one() {
+            return Err(ContractError::InvalidRate{});
+        }
+        if item_type.supply == 0 {
+            return Err(ContractError::InvalidItemTypeSupply{});
+        }
+        total_rate = total_rate.checked_add(item_type.rate)
+            .map_err(|_| ContractError::InvalidTotalRate{})?;
+    }
+    if total_rate > Decimal::one() {
+        return Err(ContractError::InvalidTotalRate{});
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use std::str::FromStr;
+    use cosmwasm_std::Timestamp;
+    use cosmwasm_std::testing::mock_env;
+    use crate::msg::{EventTime, ItemTypeMsg};
+
+    // 2023-01-01 00:00:00Z
+    const START: u64 = 1_672_531_200u64;
+    // 2023-01-31 00:00:00Z
+    const END: u64 = 1_675_123_200u64;
+    const HEIGHT: u64 = 12_345u64;
+
+    fn block(seconds: u64) -> BlockInfo {
+        let mut block = mock_env().block;
+        block.height = HEIGHT;
+        block.time = Timestamp::from_seconds(seconds);
+        block
+    }
+
+    fn box_info() -> BoxInfo {
+        BoxInfo {
+            name: String::from("event"),
+            description: String::from("description"),
+            start: EventTime::Rfc3339(String::from("2023-01-01T00:00:00Z")),
+            end: EventTime::AtTime(Timestamp::from_seconds(END)),
+            total_supply: 10,
+            replacement: true,
+            max_minted_box: Some(10),
+            price: Coin::new(100, "uaura"),
+            reroll_fee: None,
+            royalty_payment_address: None,
+            royalty_percentage: None,
+            item_metadata: None,
+            box_image: None,
+            placeholder_uri: None,
+            provenance_hash: None,
+        }
+    }
+
+    fn item_type(name: &str, rate: &str) -> ItemTypeMsg {
+        ItemTypeMsg {
+            name: String::from(name),
+            rate: Decimal::from_str(rate).unwrap(),
+            slip_rate: 1,
+            supply: 10,
+            pity: None,
+            prefix_uri: None,
+            total_supply: None,
+            reward: None,
+            mint_item: None,
+            image: None,
+            attributes: None,
+        }
+    }
+
+    fn rate_distribution(vec: Vec<ItemTypeMsg>) -> RateDistributionMsg {
+        RateDistributionMsg {
+            vec,
+            default_type: None,
+            rate_curve: None,
+            mode: None,
+            items_per_box: None,
+            guaranteed_type: None,
+        }
+    }
+
+    fn box_info_error(box_info: BoxInfo) -> ContractError {
+        validate_box_info(&box_info, &block(START)).unwrap_err()
+    }
+
+    #[test]
+    fn box_info_success() {
+        let (start, end) = validate_box_info(&box_info(), &block(START)).unwrap();
+        assert_eq!(start, Scheduled::AtTime(Timestamp::from_seconds(START)));
+        assert_eq!(end, Scheduled::AtTime(Timestamp::from_seconds(END)));
+
+        // event can be created after it started
+        assert!(validate_box_info(&box_info(), &block(START + 86400)).is_ok());
+
+        // event can be scheduled by block height, even mixed with time
+        let mut info = box_info();
+        info.end = EventTime::AtHeight(HEIGHT + 1);
+        let (start, end) = validate_box_info(&info, &block(START)).unwrap();
+        assert_eq!(start, Scheduled::AtTime(Timestamp::from_seconds(START)));
+        assert_eq!(end, Scheduled::AtHeight(HEIGHT + 1));
+
+        // without replacement, uri can be used many times
+        let mut info = box_info();
+        info.replacement = false;
+        info.max_minted_box = None;
+        assert!(validate_box_info(&info, &block(START)).is_ok());
+    }
+
+    #[test]
+    fn box_info_fail_with_empty_name() {
+        let mut info = box_info();
+        info.name = String::from(" ");
+        assert_eq!(box_info_error(info), ContractError::InvalidName{});
+    }
+
+    #[test]
+    fn box_info_fail_with_invalid_date() {
+        let mut info = box_info();
+        info.start = EventTime::Rfc3339(String::from("2023/01/01 00:00:00Z"));
+        assert_eq!(box_info_error(info), ContractError::InvalidDateFormat{});
+    }
+
+    #[test]
+    fn box_info_fail_with_start_after_end() {
+        let mut info = box_info();
+        info.start = EventTime::Rfc3339(String::from("2023-02-01T00:00:00Z"));
+        assert_eq!(box_info_error(info), ContractError::InvalidTimeRange{});
+
+        let mut info = box_info();
+        info.start = info.end.clone();
+        assert_eq!(box_info_error(info), ContractError::InvalidTimeRange{});
+
+        let mut info = box_info();
+        info.start = EventTime::AtHeight(HEIGHT + 10);
+        info.end = EventTime::AtHeight(HEIGHT + 5);
+        assert_eq!(box_info_error(info), ContractError::InvalidTimeRange{});
+    }
+
+    #[test]
+    fn box_info_fail_with_past_end() {
+        let err = validate_box_info(&box_info(), &block(END)).unwrap_err();
+        assert_eq!(err, ContractError::InvalidTime{});
+
+        let mut info = box_info();
+        info.start = EventTime::AtHeight(HEIGHT - 10);
+        info.end = EventTime::AtHeight(HEIGHT);
+        assert_eq!(box_info_error(info), ContractError::InvalidTime{});
+    }
+
+    #[test]
+    fn box_info_fail_with_zero_total_supply() {
+        let mut info = box_info();
+        info.replacement = false;
+        info.total_supply = 0;
+        assert_eq!(box_info_error(info), ContractError::InvalidTotalSupply{});
+    }
+
+    #[test]
+    fn box_info_fail_with_zero_box_supply() {
+        let mut info = box_info();
+        info.max_minted_box = Some(0);
+        assert_eq!(box_info_error(info), ContractError::InvalidBoxSupply{});
+    }
+
+    #[test]
+    fn box_info_fail_with_insufficient_uri() {
+        let mut info = box_info();
+        info.max_minted_box = Some(11);
+        assert_eq!(box_info_error(info), ContractError::InsufficientUriSupply{});
+
+        // number of boxes is limitless if not set
+        let mut info = box_info();
+        info.max_minted_box = None;
+        assert_eq!(box_info_error(info), ContractError::InsufficientUriSupply{});
+    }
+
+    #[test]
+    fn box_info_fail_with_zero_price() {
+        let mut info = box_info();
+        info.price = Coin::new(0, "uaura");
+        assert_eq!(box_info_error(info), ContractError::InvalidPrice{});
+
+        let mut info = box_info();
+        info.reroll_fee = Some(Coin::new(0, "uaura"));
+        assert_eq!(box_info_error(info), ContractError::InvalidPrice{});
+    }
+
+    #[test]
+    fn box_info_fail_with_invalid_provenance() {
+        // hex sha256 of "ipfs://items/"
+        let hash = String::from("b5b37ecb8595b252aad157b190a4f2f9f9cb31fbf83be966930a2a55010264ca");
+
+        let mut info = box_info();
+        info.placeholder_uri = Some(String::from("ipfs://placeholder"));
+        info.provenance_hash = Some(hash.clone());
+        assert!(validate_box_info(&info, &block(START)).is_ok());
+
+        // placeholder uri without commitment
+        let mut info = box_info();
+        info.placeholder_uri = Some(String::from("ipfs://placeholder"));
+        assert_eq!(box_info_error(info), ContractError::InvalidProvenance{});
+
+        let mut info = box_info();
+        info.provenance_hash = Some(hash.clone());
+        assert_eq!(box_info_error(info), ContractError::InvalidProvenance{});
+
+        // not a sha256 hash
+        let mut info = box_info();
+        info.placeholder_uri = Some(String::from("ipfs://placeholder"));
+        info.provenance_hash = Some(hash[..62].to_string());
+        assert_eq!(box_info_error(info), ContractError::InvalidProvenance{});
+    }
+
+    #[test]
+    fn rate_distribution_success() {
+        let msg = rate_distribution(vec![item_type("rare", "0.1"), item_type("epic", "0.9")]);
+        assert!(validate_rate_distribution(&msg).is_ok());
+
+        // default item type can be renamed
+        let mut msg = rate_distribution(vec![item_type("common", "0.1")]);
+        msg.default_type = Some(String::from("normal"));
+        assert!(validate_rate_distribution(&msg).is_ok());
+    }
+
+    #[test]
+    fn rate_distribution_fail_with_empty_name() {
+        let msg = rate_distribution(vec![item_type("", "0.1")]);
+        assert_eq!(validate_rate_distribution(&msg).unwrap_err(), ContractError::InvalidName{});
+
+        let mut msg = rate_distribution(vec![item_type("rare", "0.1")]);
+        msg.default_type = Some(String::new());
+        assert_eq!(validate_rate_distribution(&msg).unwrap_err(), ContractError::InvalidName{});
+    }
+
+    #[test]
+    fn rate_distribution_fail_with_duplicate_name() {
+        let msg = rate_distribution(vec![item_type("rare", "0.1"), item_type("rare", "0.2")]);
+        assert_eq!(validate_rate_distribution(&msg).unwrap_err(), ContractError::DuplicateItemType{});
+
+        // clash with default item type
+        let msg = rate_distribution(vec![item_type("common", "0.1")]);
+        assert_eq!(validate_rate_distribution(&msg).unwrap_err(), ContractError::DuplicateItemType{});
+
+        let mut msg = rate_distribution(vec![item_type("rare", "0.1")]);
+        msg.default_type = Some(String::from("rare"));
+        assert_eq!(validate_rate_distribution(&msg).unwrap_err(), ContractError::DuplicateItemType{});
+    }
+
+    #[test]
+    fn rate_distribution_fail_with_invalid_rate() {
+        let msg = rate_distribution(vec![item_type("rare", "0")]);
+        assert_eq!(validate_rate_distribution(&msg).unwrap_err(), ContractError::InvalidRate{});
+
+        let msg = rate_distribution(vec![item_type("rare", "1")]);
+        assert_eq!(validate_rate_distribution(&msg).unwrap_err(), ContractError::InvalidRate{});
+
+        // rate is ignored in deck mode
+        let mut msg = rate_distribution(vec![item_type("rare", "0")]);
+        msg.mode = Some(DrawMode::Deck);
+        assert!(validate_rate_distribution(&msg).is_ok());
+    }
+
+    #[test]
+    fn rate_distribution_fail_with_zero_supply() {
+        let mut msg = rate_distribution(vec![item_type("rare", "0.1")]);
+        msg.vec[0].supply = 0;
+        assert_eq!(validate_rate_distribution(&msg).unwrap_err(), ContractError::InvalidItemTypeSupply{});
+    }
+
+    #[test]
+    fn rate_distribution_fail_with_total_rate_over_one() {
+        let msg = rate_distribution(vec![item_type("rare", "0.6"), item_type("epic", "0.5")]);
+        assert_eq!(validate_rate_distribution(&msg).unwrap_err(), ContractError::InvalidTotalRate{});
+    }
+}
@@ -0,0 +1,5 @@
+pub mod msg;
+pub mod royalty;
+
+pub use msg::{CheckRoyaltiesResponse, Cw2981QueryMsg, RoyaltiesInfoResponse};
+pub use royalty::{check_royalties, royalties_info, validate_royalty, RoyaltyMetadata};
@@ -0,0 +1,31 @@
+use cosmwasm_schema::{cw_serde, QueryResponses};
+use cosmwasm_std::{CustomMsg, Uint128};
+
+// see: https://github.com/CosmWasm/cw-nfts/tree/main/contracts/cw2981-royalties
+#[cw_serde]
+#[derive(QueryResponses)]
+pub enum Cw2981QueryMsg {
+    /// royalty to be paid when token_id is sold at sale_price
+    #[returns(RoyaltiesInfoResponse)]
+    RoyaltyInfo {
+        token_id: String,
+        sale_price: Uint128,
+    },
+
+    /// check if contract supports royalties, it always returns true
+    #[returns(CheckRoyaltiesResponse)]
+    CheckRoyalties {},
+}
+
+impl CustomMsg for Cw2981QueryMsg {}
+
+#[cw_serde]
+pub struct RoyaltiesInfoResponse {
+    pub address: String,
+    pub royalty_amount: Uint128,
+}
+
+#[cw_serde]
+pub struct CheckRoyaltiesResponse {
+    pub royalty_payments: bool,
+}
@@ -0,0 +1,116 @@
+use cosmwasm_std::{Decimal, StdError, StdResult, Uint128};
+
+use crate::msg::{CheckRoyaltiesResponse, RoyaltiesInfoResponse};
+
+/// metadata of a token which may have royalties
+pub trait RoyaltyMetadata {
+    /// address receiving royalties
+    fn royalty_payment_address(&self) -> Option<String>;
+    /// percentage of sale price paid as royalties (0 - 100)
+    fn royalty_percentage(&self) -> Option<u64>;
+}
+
+/// check if royalty percentage of minted token is valid
+pub fn validate_royalty<T: RoyaltyMetadata>(extension: &Option<T>) -> StdResult<()> {
+    if let Some(percentage) = extension.as_ref().and_then(T::royalty_percentage) {
+        if percentage > 100 {
+            return Err(StdError::generic_err(
+                "Royalty percentage must be between 0 and 100",
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// royalty of a token, royalty_percentage of sale_price is paid to royalty_payment_address
+pub fn royalties_info<T: RoyaltyMetadata>(
+    extension: &Option<T>,
+    sale_price: Uint128,
+) -> RoyaltiesInfoResponse {
+    let royalty = extension.as_ref().and_then(|metadata| {
+        match (metadata.royalty_payment_address(), metadata.royalty_percentage()) {
+            (Some(address), Some(percentage)) => Some((address, percentage)),
+            _ => None,
+        }
+    });
+
+    match royalty {
+        Some((address, percentage)) => RoyaltiesInfoResponse {
+            address,
+            royalty_amount: sale_price * Decimal::percent(percentage),
+        },
+        None => RoyaltiesInfoResponse {
+            address: String::new(),
+            royalty_amount: Uint128::zero(),
+        },
+    }
+}
+
+/// all tokens of contracts using cw2981 may have royalties
+pub fn check_royalties() -> CheckRoyaltiesResponse {
+    CheckRoyaltiesResponse {
+        royalty_payments: true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Metadata {
+        royalty_payment_address: Option<String>,
+        royalty_percentage: Option<u64>,
+    }
+
+    impl RoyaltyMetadata for Metadata {
+        fn royalty_payment_address(&self) -> Option<String> {
+            self.royalty_payment_address.clone()
+        }
+
+        fn royalty_percentage(&self) -> Option<u64> {
+            self.royalty_percentage
+        }
+    }
+
+    fn metadata(address: Option<&str>, percentage: Option<u64>) -> Option<Metadata> {
+        Some(Metadata {
+            royalty_payment_address: address.map(String::from),
+            royalty_percentage: percentage,
+        })
+    }
+
+    #[test]
+    fn validate_percentage() {
+        validate_royalty::<Metadata>(&None).unwrap();
+        validate_royalty(&metadata(None, None)).unwrap();
+        validate_royalty(&metadata(Some("creator"), Some(0))).unwrap();
+        validate_royalty(&metadata(Some("creator"), Some(100))).unwrap();
+
+        let err = validate_royalty(&metadata(Some("creator"), Some(101))).unwrap_err();
+        assert_eq!(
+            err,
+            StdError::generic_err("Royalty percentage must be between 0 and 100")
+        );
+    }
+
+    #[test]
+    fn royalty_amount() {
+        let res = royalties_info(&metadata(Some("creator"), Some(10)), Uint128::new(1000));
+        assert_eq!(res.address, "creator");
+        assert_eq!(res.royalty_amount, Uint128::new(100));
+
+        // rounded down
+        let res = royalties_info(&metadata(Some("creator"), Some(3)), Uint128::new(10));
+        assert_eq!(res.royalty_amount, Uint128::zero());
+
+        // both address and percentage are required
+        for extension in [None, metadata(None, Some(10)), metadata(Some("creator"), None)] {
+            let res = royalties_info(&extension, Uint128::new(1000));
+            assert_eq!(res.address, "");
+            assert_eq!(res.royalty_amount, Uint128::zero());
+        }
+
+        assert!(check_royalties().royalty_payments);
+    }
+}